@@ -0,0 +1,679 @@
+use std::{cmp::Reverse, fmt};
+
+use crate::{
+    chord::Chord,
+    event::Event,
+    harmony::{Harmony, Kind},
+    key::Key,
+    location::Location,
+    note::Note,
+    rest::Rest,
+    score::Score,
+    tag::Tag,
+    tag_id::TagId,
+    tag_param::TagParam,
+    visitor::{fold_events, Fold, VisitContext, Visitor},
+};
+
+/// Qualities chords are labelled with, triads first.
+const KINDS: [Kind; 9] = [
+    Kind::Major,
+    Kind::Minor,
+    Kind::Diminished,
+    Kind::Augmented,
+    Kind::Dominant,
+    Kind::MajorSeventh,
+    Kind::MinorSeventh,
+    Kind::HalfDiminished,
+    Kind::DiminishedSeventh,
+];
+
+const EPSILON: f32 = 1e-4;
+
+/// A roman numeral, like `V65`, `bVI` or `viiø7/V`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Numeral {
+    /// Degree of the scale the root is on, from 1.
+    pub degree: u8,
+    /// Semitones between the root and that degree, for chromatic chords.
+    pub alter: i8,
+    pub kind: Kind,
+    /// Chord tone in the bass: 0 for the root, 1 for the third, and so on.
+    pub inversion: u8,
+    /// Chord an applied chord leads to, like the V of `V7/V`.
+    pub applied: Option<Box<Numeral>>,
+}
+
+/// How a note foreign to a chord moves in its voice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Embellishment {
+    /// Stepping through, between two notes a third apart.
+    Passing,
+    /// Stepping away from a note and back to it.
+    Neighbor,
+    /// Held over from the previous chord, then resolving a step down.
+    Suspension,
+    Other,
+}
+
+/// A note foreign to the chord it sounds with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NonChordTone {
+    pub staff: u8,
+    /// Index of the voice in its staff.
+    pub voice: usize,
+    /// Onset of the note, which may start before the chord.
+    pub onset: f32,
+    pub pitch: u8,
+    pub embellishment: Embellishment,
+}
+
+/// The chord found at an onset of a score.
+#[derive(Clone, Debug)]
+pub struct Annotation {
+    /// Onset in whole notes.
+    pub onset: f32,
+    pub key: Key,
+    pub numeral: Numeral,
+    /// The chord as a symbol, with its bass when inverted.
+    pub harmony: Harmony,
+    pub non_chord_tones: Vec<NonChordTone>,
+}
+
+/// What annotations are written back as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Label {
+    /// `\text` tags with roman numerals.
+    Numeral,
+    /// `\harmony` tags with chord symbols.
+    Symbol,
+}
+
+impl Numeral {
+    /// Figures of the inversion, like 6 or 43.
+    pub fn figures(&self) -> &'static str {
+        let seventh = self.kind.intervals().len() > 3;
+
+        match (seventh, self.inversion) {
+            (false, 0) => "",
+            (false, 1) => "6",
+            (false, _) => "64",
+            (true, 0) => "7",
+            (true, 1) => "65",
+            (true, 2) => "43",
+            (true, _) => "42",
+        }
+    }
+
+    fn new(degree: u8, alter: i8, kind: Kind) -> Self {
+        Self {
+            degree,
+            alter,
+            kind,
+            inversion: 0,
+            applied: None,
+        }
+    }
+}
+
+impl fmt::Display for Numeral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const ROMAN: [&str; 7] = ["I", "II", "III", "IV", "V", "VI", "VII"];
+
+        let accidental = if self.alter < 0 { "b" } else { "#" };
+        let roman = ROMAN[usize::from(self.degree.clamp(1, 7) - 1)];
+        let symbol = match self.kind {
+            Kind::Diminished | Kind::DiminishedSeventh => "°",
+            Kind::HalfDiminished => "ø",
+            Kind::Augmented => "+",
+            _ => "",
+        };
+
+        f.write_str(&accidental.repeat(self.alter.unsigned_abs().into()))?;
+
+        if is_minor(self.kind) {
+            f.write_str(&roman.to_lowercase())?;
+        } else {
+            f.write_str(roman)?;
+        }
+
+        write!(f, "{symbol}{}", self.figures())?;
+
+        if let Some(applied) = &self.applied {
+            write!(f, "/{applied}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Annotation {
+    /// The annotation as a tag to write in the score.
+    pub fn tag(&self, label: Label) -> Tag {
+        let (id, text) = match label {
+            Label::Numeral => (TagId::Text, self.numeral.to_string()),
+            Label::Symbol => (TagId::Harmony, self.harmony.to_string()),
+        };
+
+        Tag::from_id(id).with_param(TagParam::String(text))
+    }
+}
+
+/// Labels the chords of a score with roman numerals in the key of its
+/// `\key` tags, C major without one.
+///
+/// The notes of all the staffs sounding at each onset make a sonority,
+/// labelled with the triad or seventh chord it holds the most tones of,
+/// preferring the chords of the key, then the ones borrowed from its
+/// parallel mode, then applied chords. The other notes are non-chord tones.
+/// A chord is only annotated when it changes.
+pub fn analyze(score: &Score) -> Vec<Annotation> {
+    let (notes, keys) = sounding(score);
+
+    let mut onsets = notes.iter().map(|n| n.start).collect::<Vec<_>>();
+    onsets.sort_by(f32::total_cmp);
+    onsets.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+
+    let mut annotations: Vec<Annotation> = Vec::new();
+
+    for onset in onsets {
+        let key = keys
+            .iter()
+            .rev()
+            .find(|(time, _)| *time < onset + EPSILON)
+            .map_or_else(Key::default, |(_, key)| *key);
+
+        let sounding = notes
+            .iter()
+            .filter(|n| n.start < onset + EPSILON && n.end > onset + EPSILON)
+            .collect::<Vec<_>>();
+        let pitches = sounding.iter().map(|n| n.pitch).collect::<Vec<_>>();
+
+        let Some((root, kind)) = chord(&pitches, key) else {
+            continue;
+        };
+
+        let tones = kind
+            .intervals()
+            .iter()
+            .map(|i| (root + i) % 12)
+            .collect::<Vec<_>>();
+        let non_chord_tones = sounding
+            .iter()
+            .filter(|n| !tones.contains(&(n.pitch % 12)))
+            .map(|n| NonChordTone {
+                staff: n.staff,
+                voice: n.voice,
+                onset: n.start,
+                pitch: n.pitch,
+                embellishment: embellishment(n, onset, &notes),
+            })
+            .collect::<Vec<_>>();
+
+        let bass = pitches
+            .iter()
+            .filter(|p| tones.contains(&(*p % 12)))
+            .min()
+            .map_or(root, |p| p % 12);
+        let inversion = tones.iter().position(|t| *t == bass).unwrap_or(0);
+
+        let mut numeral = numeral(key, root, kind).0;
+        numeral.inversion = inversion as u8;
+
+        match annotations.last_mut() {
+            Some(last) if last.numeral == numeral && last.key == key => {
+                for tone in non_chord_tones {
+                    if !last.non_chord_tones.contains(&tone) {
+                        last.non_chord_tones.push(tone);
+                    }
+                }
+            },
+            _ => annotations.push(Annotation {
+                onset,
+                key,
+                numeral,
+                harmony: Harmony {
+                    root: key.spell(root),
+                    kind,
+                    suspended: None,
+                    degrees: Vec::new(),
+                    bass: (inversion > 0).then(|| key.spell(bass)),
+                },
+                non_chord_tones,
+            }),
+        }
+    }
+
+    annotations
+}
+
+/// Writes annotations in the first voice of the bass staff, as tags before
+/// the events starting with them.
+///
+/// Annotations starting during a longer event of the bass go to the voices
+/// of the staffs above it, and the ones starting with no event are left
+/// out.
+pub fn annotate(score: &mut Score, annotations: &[Annotation], label: Label) {
+    let mut writer = Writer {
+        pending: annotations
+            .iter()
+            .map(|a| (a.onset, a.tag(label)))
+            .collect(),
+    };
+
+    let mut staffs = score.staffs.iter_mut().collect::<Vec<_>>();
+    staffs.sort_by_key(|s| Reverse(s.id));
+
+    for staff in staffs {
+        for (i, voice) in staff.voices.iter_mut().enumerate() {
+            if writer.pending.is_empty() {
+                return;
+            }
+
+            let mut cx = VisitContext::new(staff.id, i);
+            let events = std::mem::take(&mut voice.events);
+
+            voice.events = fold_events(events, &mut writer, &mut cx);
+        }
+    }
+}
+
+/// Whether chords of a kind are written with lower case numerals.
+fn is_minor(kind: Kind) -> bool {
+    matches!(
+        kind,
+        Kind::Minor
+            | Kind::Diminished
+            | Kind::MinorSeventh
+            | Kind::HalfDiminished
+            | Kind::DiminishedSeventh
+    )
+}
+
+/// The root and kind of the chord best describing some pitches.
+///
+/// A chord needs its root and third, its seventh if it has one, and its
+/// fifth when diminished or augmented.
+fn chord(pitches: &[u8], key: Key) -> Option<(u8, Kind)> {
+    let mut classes = pitches.iter().map(|p| p % 12).collect::<Vec<_>>();
+    classes.sort_unstable();
+    classes.dedup();
+
+    let bass = pitches.iter().min()? % 12;
+
+    classes
+        .iter()
+        .flat_map(|root| KINDS.iter().enumerate().map(move |k| (*root, k)))
+        .filter_map(|(root, (index, kind))| {
+            let intervals = kind.intervals();
+            let has =
+                |i: usize| classes.contains(&((root + intervals[i]) % 12));
+
+            let complete = has(1)
+                && (intervals[2] == 7 || has(2))
+                && (intervals.len() < 4 || has(3));
+            let matched = (0..intervals.len()).filter(|i| has(*i)).count();
+
+            complete.then(|| {
+                let fit = numeral(key, root, *kind).1;
+                ((matched, fit, root == bass, Reverse(index)), (root, *kind))
+            })
+        })
+        .max_by_key(|(rank, _)| *rank)
+        .map(|(_, chord)| chord)
+}
+
+/// The numeral of a chord in root position, and how well it fits the key:
+/// 3 for the chords of the key, 2 for the borrowed ones, 1 for applied
+/// chords, 0 for other chromatic chords.
+fn numeral(key: Key, root: u8, kind: Kind) -> (Numeral, u8) {
+    let interval = (root + 12 - key.tonic()) % 12;
+    let (step, alter) = degree(key, interval);
+    let numeral = Numeral::new(step, alter, kind);
+
+    if is_diatonic(key.minor, interval, kind) {
+        return (numeral, 3);
+    }
+
+    // Borrowed from the parallel mode, or the Neapolitan sixth
+    if is_diatonic(!key.minor, interval, kind)
+        || (interval == 1 && kind == Kind::Major)
+    {
+        return (numeral, 2);
+    }
+
+    let (target, leading) = match kind {
+        Kind::Major | Kind::Dominant => ((interval + 5) % 12, 5),
+        Kind::Diminished | Kind::HalfDiminished | Kind::DiminishedSeventh => {
+            ((interval + 1) % 12, 7)
+        },
+        _ => return (numeral, 0),
+    };
+
+    let target_kind = [Kind::Major, Kind::Minor]
+        .into_iter()
+        .find(|k| is_diatonic(key.minor, target, *k));
+
+    match target_kind {
+        Some(target_kind) if target != 0 => {
+            let (degree, alter) = degree(key, target);
+            let mut applied = Numeral::new(leading, 0, kind);
+            applied.applied =
+                Some(Box::new(Numeral::new(degree, alter, target_kind)));
+
+            (applied, 1)
+        },
+        _ => (numeral, 0),
+    }
+}
+
+/// Degree of the scale and alteration of the note some semitones above the
+/// tonic.
+///
+/// Chromatic notes are flat degrees in major keys and sharp ones in minor
+/// keys, except for the flat second and sharp fourth, and the leading tone
+/// of minor keys is their seventh degree.
+fn degree(key: Key, interval: u8) -> (u8, i8) {
+    let scale = key.scale();
+    let find = |interval: u8| {
+        scale
+            .iter()
+            .position(|i| *i == interval % 12)
+            .map(|d| d as u8 + 1)
+    };
+
+    if let Some(degree) = find(interval) {
+        return (degree, 0);
+    }
+
+    if key.minor && interval == 11 {
+        return (7, 0);
+    }
+
+    let flats = interval == 1 || (!key.minor && interval != 6);
+
+    match (find(interval + 1), find(interval + 11)) {
+        (Some(degree), _) if flats => (degree, -1),
+        (_, Some(degree)) => (degree, 1),
+        (Some(degree), None) => (degree, -1),
+        (None, None) => (1, 0),
+    }
+}
+
+/// Whether a chord some semitones above the tonic belongs to major or minor
+/// keys, with the dominant and leading-tone chords of harmonic minor.
+fn is_diatonic(minor: bool, interval: u8, kind: Kind) -> bool {
+    use Kind::*;
+
+    match (minor, interval) {
+        (false, 0 | 5) => matches!(kind, Major | MajorSeventh),
+        (false, 2 | 4 | 9) => matches!(kind, Minor | MinorSeventh),
+        (false, 7) => matches!(kind, Major | Dominant),
+        (false, 11) => matches!(kind, Diminished | HalfDiminished),
+        (true, 0 | 5) => matches!(kind, Minor | MinorSeventh),
+        (true, 2) => matches!(kind, Diminished | HalfDiminished),
+        (true, 3 | 8) => matches!(kind, Major | MajorSeventh),
+        (true, 7) => matches!(kind, Major | Dominant | Minor | MinorSeventh),
+        (true, 10) => matches!(kind, Major | Dominant),
+        (true, 11) => {
+            matches!(kind, Diminished | HalfDiminished | DiminishedSeventh)
+        },
+        _ => false,
+    }
+}
+
+/// How a non-chord tone sounding at an onset moves from the note before it
+/// in its voice to the note after it.
+fn embellishment(
+    note: &Sounding,
+    onset: f32,
+    notes: &[Sounding],
+) -> Embellishment {
+    // Interval to the closest note of the voice starting or ending at a time
+    let closest = |time: f32, after: bool| {
+        notes
+            .iter()
+            .filter(|n| n.staff == note.staff && n.voice == note.voice)
+            .filter(|n| {
+                let bound = if after { n.start } else { n.end };
+                (bound - time).abs() < EPSILON
+            })
+            .map(|n| i32::from(n.pitch) - i32::from(note.pitch))
+            .min_by_key(|i| i.abs())
+    };
+    let step = |interval: i32| (1..=2).contains(&interval.abs());
+
+    let before = closest(note.start, false);
+    let after = closest(note.end, true);
+
+    if note.start < onset - EPSILON {
+        return match after {
+            Some(after) if step(after) && after < 0 => {
+                Embellishment::Suspension
+            },
+            _ => Embellishment::Other,
+        };
+    }
+
+    match (before, after) {
+        (Some(before), Some(after)) if step(before) && step(after) => {
+            if before.signum() == after.signum() {
+                Embellishment::Neighbor
+            } else {
+                Embellishment::Passing
+            }
+        },
+        _ => Embellishment::Other,
+    }
+}
+
+/// The notes of a score, tied notes making one, and its key changes.
+pub(crate) fn sounding(score: &Score) -> (Vec<Sounding>, Vec<(f32, Key)>) {
+    let mut collector = Collector::default();
+
+    score.visit(&mut collector);
+
+    let Collector { notes, mut keys } = collector;
+
+    keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    (notes, keys)
+}
+
+/// A note and when it sounds.
+pub(crate) struct Sounding {
+    pub(crate) staff: u8,
+    pub(crate) voice: usize,
+    pub(crate) pitch: u8,
+    pub(crate) start: f32,
+    pub(crate) end: f32,
+    /// Where the note, or the first of tied notes, was written.
+    pub(crate) location: Location,
+}
+
+#[derive(Default)]
+struct Collector {
+    notes: Vec<Sounding>,
+    keys: Vec<(f32, Key)>,
+}
+
+impl<'a> Visitor<'a> for Collector {
+    fn on_note(&mut self, note: &'a Note, cx: &VisitContext) {
+        let Some(pitch) = note.midi_pitch() else {
+            return;
+        };
+
+        if note.is_empty() || cx.grace().is_some() {
+            return;
+        }
+
+        let end = cx.onset + note.full_duration().as_f32();
+
+        if cx.is_within(TagId::Tie) {
+            let tied = self.notes.iter_mut().rev().find(|n| {
+                n.staff == cx.staff
+                    && n.voice == cx.voice
+                    && n.pitch == pitch
+                    && (n.end - cx.onset).abs() < EPSILON
+            });
+
+            if let Some(tied) = tied {
+                tied.end = end;
+                return;
+            }
+        }
+
+        self.notes.push(Sounding {
+            staff: cx.staff,
+            voice: cx.voice,
+            pitch,
+            start: cx.onset,
+            end,
+            location: note.location,
+        });
+    }
+
+    fn on_tag(&mut self, tag: &'a Tag, cx: &VisitContext) {
+        if let Some(key) = Key::from_tag(tag) {
+            self.keys.push((cx.onset, key));
+        }
+    }
+}
+
+/// Inserts tags before the first notes, rests or chords starting with them.
+struct Writer {
+    pending: Vec<(f32, Tag)>,
+}
+
+impl Fold for Writer {
+    fn fold_event(
+        &mut self,
+        event: Box<dyn Event>,
+        cx: &VisitContext,
+    ) -> Vec<Box<dyn Event>> {
+        let any = event.as_any();
+        let timed = any.is::<Note>() || any.is::<Rest>() || any.is::<Chord>();
+
+        if !timed || cx.in_chord() || cx.grace().is_some() {
+            return vec![event];
+        }
+
+        let (starting, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(onset, _)| {
+                (onset - cx.onset).abs() < EPSILON
+            });
+        self.pending = pending;
+
+        starting
+            .into_iter()
+            .map(|(_, tag)| Box::new(tag) as Box<dyn Event>)
+            .chain(std::iter::once(event))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    fn numerals(score: &str) -> Result<Vec<String>> {
+        Ok(analyze(&Score::parse(score)?)
+            .iter()
+            .map(|a| a.numeral.to_string())
+            .collect())
+    }
+
+    #[test]
+    fn cadences() -> Result<()> {
+        assert_eq!(
+            numerals(
+                "{ [ \\key<\"G\"> {b0, d1, g1} {c1, g1} {c1, d1, f#1, a1} \
+                     {b0, d1, g1} ],
+                   [ \\staff<2> g-1 e0 d0 g-1 ] }"
+            )?,
+            ["I", "IV6", "V7", "I"]
+        );
+        assert_eq!(
+            numerals(
+                "[ \\key<\"c\"> {c1, e&1, g1} {b0, d1, f1, a&1} {c1, e1, g1} ]"
+            )?,
+            ["i", "vii°7", "I"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn inversions_and_chromatic_chords() -> Result<()> {
+        assert_eq!(
+            numerals(
+                "[ \\key<0> {e0, c1, g1} {f0, g0, b0, d1} {f#0, a0, c1, d1} \
+                 {g0, b0, d1} {a&0, c1, e&1} {d&0, f0, a&0} ]"
+            )?,
+            ["I6", "V42", "V65/V", "V", "bVI", "bII"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn non_chord_tones() -> Result<()> {
+        let score = Score::parse(
+            "{ [ e/4 d c b0 \\tie(d1 d1/2) c1/4 ],
+               [ e0/2 e0/4 d0/4 b-1/2 e0/2 ],
+               [ \\staff<2> c0/2 c0/4 g-1/4 g-1/2 c0/2 ] }",
+        )?;
+        let annotations = analyze(&score);
+
+        let numerals = annotations
+            .iter()
+            .map(|a| a.numeral.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(numerals, ["I", "V", "I"]);
+
+        let embellishments = annotations
+            .iter()
+            .flat_map(|a| &a.non_chord_tones)
+            .map(|t| (t.onset, t.pitch, t.embellishment))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            embellishments,
+            [
+                (0.25, 62, Embellishment::Passing),
+                (1.0, 62, Embellishment::Suspension)
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_back() -> Result<()> {
+        let mut score = Score::parse(
+            "{ [ \\key<\"D\"> {f#/2, a} {e/4, a} {a, g} {f#1/2, a} ],
+               [ \\staff<2> d0/2 c#0/2 d0/2 ] }",
+        )?;
+        let annotations = analyze(&score);
+
+        annotate(&mut score, &annotations, Label::Symbol);
+        annotate(&mut score, &annotations, Label::Numeral);
+
+        let texts = |staff: usize| {
+            score.staffs[staff].voices[0]
+                .events
+                .iter()
+                .filter_map(|e| e.as_any().downcast_ref::<Tag>())
+                .filter(|t| matches!(t.id, TagId::Text | TagId::Harmony))
+                .filter_map(Tag::as_str)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(texts(0), ["A7/C#", "V65"]);
+        assert_eq!(texts(1), ["D", "I", "A/C#", "V6", "D", "I"]);
+
+        Ok(())
+    }
+}
@@ -3,7 +3,6 @@ use std::collections::HashMap;
 use anyhow::Result;
 
 use crate::{duration::Duration, tag::Tag, tag_id::TagId};
-use crate::ptr::Ptr;
 use crate::tag_definitions::TagDefinitions;
 use crate::tag_validator::TagValidator;
 
@@ -13,6 +12,9 @@ pub struct Context {
     pub octave: i8,
     pub duration: Duration,
     pub tags: HashMap<TagId, Tag>,
+    /// Keeps the tags used with the wrong type instead of failing, for the
+    /// tools reporting them.
+    pub lenient: bool,
 }
 
 impl Default for Context {
@@ -26,11 +28,20 @@ impl Default for Context {
             octave: 1,
             duration: Duration::default(),
             tags: HashMap::new(),
+            lenient: false,
         }
     }
 }
 
 impl Context {
+    /// Starts a new sequence, which does not inherit anything from the
+    /// previous ones.
+    pub fn begin_voice(&mut self) {
+        self.octave = 1;
+        self.duration = Duration::default();
+        self.tags.clear();
+    }
+
     pub fn add_tag(&mut self, tag: Tag) {
         self.tags.insert(tag.id, tag);
     }
@@ -44,12 +55,14 @@ impl Context {
     }
 
     pub fn validate(&self, tag: &Tag) -> Result<()> {
+        if self.lenient {
+            return Ok(());
+        }
+
         self.validator.validate(tag, &self.defs)
     }
 }
 
-pub type ContextPtr = Ptr<Context>;
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,4 +76,20 @@ mod tests {
 
         assert_eq!(ctx.get_tag(TagId::Bar).unwrap(), &tag);
     }
+
+    #[test]
+    fn begin_voice() {
+        let mut ctx = Context {
+            octave: 2,
+            duration: Duration::new(1, 8),
+            ..Default::default()
+        };
+
+        ctx.add_tag(Tag::from_id(TagId::Staff));
+        ctx.begin_voice();
+
+        assert_eq!(ctx.octave, 1);
+        assert_eq!(ctx.duration, Duration::new(1, 4));
+        assert!(ctx.get_tag(TagId::Staff).is_none());
+    }
 }
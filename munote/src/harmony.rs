@@ -0,0 +1,765 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+
+use crate::{tag::Tag, tag_id::TagId};
+
+/// A chord symbol, like the ones written with `\harmony<"F#m7b5">`.
+///
+/// Symbols are read as a root, a quality with its seventh or extension,
+/// alterations and added or omitted degrees, and a bass: `C/E`, `Bbmaj9#11`,
+/// `G7sus4`, `Ebdim7`, `A-6/9` or `D7(b9,#11)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Harmony {
+    pub root: Spelling,
+    pub kind: Kind,
+    /// Fourth or second replacing the third of a chord with a seventh.
+    pub suspended: Option<u8>,
+    pub degrees: Vec<Degree>,
+    pub bass: Option<Spelling>,
+}
+
+/// The name of a pitch class, like `F#`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Spelling {
+    /// Letter from `A` to `G`.
+    pub step: char,
+    /// Semitones added by the accidentals.
+    pub alter: i8,
+}
+
+/// Chord qualities, named like MusicXML kinds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Major,
+    Minor,
+    Augmented,
+    Diminished,
+    Dominant,
+    MajorSeventh,
+    MinorSeventh,
+    DiminishedSeventh,
+    AugmentedSeventh,
+    HalfDiminished,
+    MajorMinor,
+    MajorSixth,
+    MinorSixth,
+    DominantNinth,
+    MajorNinth,
+    MinorNinth,
+    Dominant11th,
+    Major11th,
+    Minor11th,
+    Dominant13th,
+    Major13th,
+    Minor13th,
+    SuspendedSecond,
+    SuspendedFourth,
+    Power,
+}
+
+/// A degree added to, altered in or removed from a chord.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Degree {
+    /// Degree of the major scale, like 9 or 11.
+    pub value: u8,
+    pub alter: i8,
+    pub ty: DegreeType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DegreeType {
+    Add,
+    Alter,
+    Subtract,
+}
+
+impl Spelling {
+    pub fn pitch_class(&self) -> u8 {
+        let natural = match self.step {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            _ => 11,
+        };
+
+        (natural + i32::from(self.alter)).rem_euclid(12) as u8
+    }
+
+    /// Spells a pitch class with one accidental at most.
+    pub fn from_pitch_class(pitch_class: u8, flats: bool) -> Self {
+        let (step, alter) = match (pitch_class % 12, flats) {
+            (0, _) => ('C', 0),
+            (1, false) => ('C', 1),
+            (1, true) => ('D', -1),
+            (2, _) => ('D', 0),
+            (3, false) => ('D', 1),
+            (3, true) => ('E', -1),
+            (4, _) => ('E', 0),
+            (5, _) => ('F', 0),
+            (6, false) => ('F', 1),
+            (6, true) => ('G', -1),
+            (7, _) => ('G', 0),
+            (8, false) => ('G', 1),
+            (8, true) => ('A', -1),
+            (9, _) => ('A', 0),
+            (10, false) => ('A', 1),
+            (10, true) => ('B', -1),
+            _ => ('B', 0),
+        };
+
+        Self { step, alter }
+    }
+
+    fn transpose(&self, semitones: i32, flats: bool) -> Self {
+        let pitch_class = i32::from(self.pitch_class()) + semitones;
+
+        Self::from_pitch_class(pitch_class.rem_euclid(12) as u8, flats)
+    }
+
+    /// Reads a spelling at the start of the text, returning the rest.
+    pub(crate) fn parse(text: &str) -> Option<(Self, &str)> {
+        let step = text.chars().next().filter(|c| ('A'..='G').contains(c))?;
+        let mut rest = &text[1..];
+        let mut alter = 0;
+
+        loop {
+            if let Some(r) = rest.strip_prefix(['#', '♯']) {
+                alter += 1;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix(['b', '♭']) {
+                alter -= 1;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        Some((Self { step, alter }, rest))
+    }
+}
+
+impl fmt::Display for Spelling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let accidental = if self.alter < 0 { "b" } else { "#" };
+
+        write!(
+            f,
+            "{}{}",
+            self.step,
+            accidental.repeat(self.alter.unsigned_abs().into())
+        )
+    }
+}
+
+impl Kind {
+    /// Semitones of the chord tones above the root.
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            Kind::Major => &[0, 4, 7],
+            Kind::Minor => &[0, 3, 7],
+            Kind::Augmented => &[0, 4, 8],
+            Kind::Diminished => &[0, 3, 6],
+            Kind::Dominant => &[0, 4, 7, 10],
+            Kind::MajorSeventh => &[0, 4, 7, 11],
+            Kind::MinorSeventh => &[0, 3, 7, 10],
+            Kind::DiminishedSeventh => &[0, 3, 6, 9],
+            Kind::AugmentedSeventh => &[0, 4, 8, 10],
+            Kind::HalfDiminished => &[0, 3, 6, 10],
+            Kind::MajorMinor => &[0, 3, 7, 11],
+            Kind::MajorSixth => &[0, 4, 7, 9],
+            Kind::MinorSixth => &[0, 3, 7, 9],
+            Kind::DominantNinth => &[0, 4, 7, 10, 14],
+            Kind::MajorNinth => &[0, 4, 7, 11, 14],
+            Kind::MinorNinth => &[0, 3, 7, 10, 14],
+            Kind::Dominant11th => &[0, 4, 7, 10, 14, 17],
+            Kind::Major11th => &[0, 4, 7, 11, 14, 17],
+            Kind::Minor11th => &[0, 3, 7, 10, 14, 17],
+            Kind::Dominant13th => &[0, 4, 7, 10, 14, 17, 21],
+            Kind::Major13th => &[0, 4, 7, 11, 14, 17, 21],
+            Kind::Minor13th => &[0, 3, 7, 10, 14, 17, 21],
+            Kind::SuspendedSecond => &[0, 2, 7],
+            Kind::SuspendedFourth => &[0, 5, 7],
+            Kind::Power => &[0, 7],
+        }
+    }
+
+    /// Name of the kind in MusicXML.
+    pub fn musicxml(&self) -> &'static str {
+        match self {
+            Kind::Major => "major",
+            Kind::Minor => "minor",
+            Kind::Augmented => "augmented",
+            Kind::Diminished => "diminished",
+            Kind::Dominant => "dominant",
+            Kind::MajorSeventh => "major-seventh",
+            Kind::MinorSeventh => "minor-seventh",
+            Kind::DiminishedSeventh => "diminished-seventh",
+            Kind::AugmentedSeventh => "augmented-seventh",
+            Kind::HalfDiminished => "half-diminished",
+            Kind::MajorMinor => "major-minor",
+            Kind::MajorSixth => "major-sixth",
+            Kind::MinorSixth => "minor-sixth",
+            Kind::DominantNinth => "dominant-ninth",
+            Kind::MajorNinth => "major-ninth",
+            Kind::MinorNinth => "minor-ninth",
+            Kind::Dominant11th => "dominant-11th",
+            Kind::Major11th => "major-11th",
+            Kind::Minor11th => "minor-11th",
+            Kind::Dominant13th => "dominant-13th",
+            Kind::Major13th => "major-13th",
+            Kind::Minor13th => "minor-13th",
+            Kind::SuspendedSecond => "suspended-second",
+            Kind::SuspendedFourth => "suspended-fourth",
+            Kind::Power => "power",
+        }
+    }
+
+    /// Suffix written after the root in chord symbols.
+    fn suffix(&self) -> &'static str {
+        match self {
+            Kind::Major => "",
+            Kind::Minor => "m",
+            Kind::Augmented => "+",
+            Kind::Diminished => "dim",
+            Kind::Dominant => "7",
+            Kind::MajorSeventh => "maj7",
+            Kind::MinorSeventh => "m7",
+            Kind::DiminishedSeventh => "dim7",
+            Kind::AugmentedSeventh => "+7",
+            Kind::HalfDiminished => "m7b5",
+            Kind::MajorMinor => "m(maj7)",
+            Kind::MajorSixth => "6",
+            Kind::MinorSixth => "m6",
+            Kind::DominantNinth => "9",
+            Kind::MajorNinth => "maj9",
+            Kind::MinorNinth => "m9",
+            Kind::Dominant11th => "11",
+            Kind::Major11th => "maj11",
+            Kind::Minor11th => "m11",
+            Kind::Dominant13th => "13",
+            Kind::Major13th => "maj13",
+            Kind::Minor13th => "m13",
+            Kind::SuspendedSecond => "sus2",
+            Kind::SuspendedFourth => "sus4",
+            Kind::Power => "5",
+        }
+    }
+}
+
+impl Degree {
+    fn new(value: u8, alter: i8, ty: DegreeType) -> Self {
+        Self { value, alter, ty }
+    }
+
+    /// Semitones above the root.
+    pub fn semitones(&self) -> i32 {
+        const SCALE: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+        let index = usize::from(self.value.max(1) - 1);
+
+        SCALE[index % 7] + 12 * (index / 7) as i32 + i32::from(self.alter)
+    }
+}
+
+/// Degree a chord tone stands for, from its semitones above the root.
+fn degree_of(semitones: i32) -> u8 {
+    match semitones {
+        0 => 1,
+        1 | 2 => 2,
+        3 | 4 => 3,
+        5 => 4,
+        6..=8 => 5,
+        9 => 6,
+        10 | 11 => 7,
+        13..=16 => 9,
+        17 | 18 => 11,
+        _ => 13,
+    }
+}
+
+impl Harmony {
+    /// The chord symbol of a `\harmony` tag, if it can be read.
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        if tag.id != TagId::Harmony {
+            return None;
+        }
+
+        let text = tag.get_str("text").or_else(|| tag.as_str())?;
+
+        text.parse().ok()
+    }
+
+    /// Semitones of the chord tones above the root, without the bass.
+    pub fn intervals(&self) -> Vec<i32> {
+        let mut intervals = self
+            .kind
+            .intervals()
+            .iter()
+            .map(|i| i32::from(*i))
+            .collect::<Vec<_>>();
+
+        if let Some(degree) = self.suspended {
+            intervals.retain(|i| degree_of(*i) != 3);
+            intervals.push(Degree::new(degree, 0, DegreeType::Add).semitones());
+        }
+
+        for degree in &self.degrees {
+            if degree.ty != DegreeType::Add {
+                intervals.retain(|i| degree_of(*i) != degree.value);
+            }
+            if degree.ty != DegreeType::Subtract {
+                intervals.push(degree.semitones());
+            }
+        }
+
+        intervals.sort();
+        intervals.dedup();
+        intervals
+    }
+
+    /// The pitch classes of the chord, bass included, from 0 for C.
+    pub fn pitch_classes(&self) -> Vec<u8> {
+        let root = i32::from(self.root.pitch_class());
+
+        let mut classes = self
+            .intervals()
+            .iter()
+            .map(|i| (root + i).rem_euclid(12) as u8)
+            .chain(self.bass.map(|b| b.pitch_class()))
+            .collect::<Vec<_>>();
+
+        classes.sort();
+        classes.dedup();
+        classes
+    }
+
+    /// The same chord, the given number of semitones higher, spelled with
+    /// flats when its root was.
+    pub fn transpose(&self, semitones: i32) -> Self {
+        let flats = self.root.alter < 0;
+
+        Self {
+            root: self.root.transpose(semitones, flats),
+            bass: self.bass.map(|b| b.transpose(semitones, flats)),
+            ..self.clone()
+        }
+    }
+
+    /// MIDI pitches to play the chord with: the bass in the second octave,
+    /// and the chord tones from the third one up.
+    pub fn voicing(&self) -> Vec<u8> {
+        let root = i32::from(self.root.pitch_class());
+        let bass = self.bass.map_or(root, |b| i32::from(b.pitch_class()));
+
+        let tones = self.intervals().into_iter().map(|i| 48 + root + i);
+
+        std::iter::once(36 + bass)
+            .chain(tones)
+            .filter_map(|p| u8::try_from(p).ok())
+            .collect()
+    }
+
+    /// The chord as a MusicXML `<harmony>` element.
+    pub fn to_musicxml(&self) -> String {
+        let mut xml = String::from("<harmony>");
+
+        xml += &format!(
+            "<root><root-step>{}</root-step>{}</root>",
+            self.root.step,
+            alter_element("root-alter", self.root.alter)
+        );
+        xml += &format!(
+            "<kind text=\"{}\">{}</kind>",
+            self.kind.suffix(),
+            self.kind.musicxml()
+        );
+
+        if let Some(bass) = self.bass {
+            xml += &format!(
+                "<bass><bass-step>{}</bass-step>{}</bass>",
+                bass.step,
+                alter_element("bass-alter", bass.alter)
+            );
+        }
+
+        let suspension = self.suspended.into_iter().flat_map(|degree| {
+            [
+                Degree::new(3, 0, DegreeType::Subtract),
+                Degree::new(degree, 0, DegreeType::Add),
+            ]
+        });
+
+        for degree in suspension.chain(self.degrees.iter().copied()) {
+            let ty = match degree.ty {
+                DegreeType::Add => "add",
+                DegreeType::Alter => "alter",
+                DegreeType::Subtract => "subtract",
+            };
+
+            xml += &format!(
+                "<degree><degree-value>{}</degree-value><degree-alter>{}\
+                 </degree-alter><degree-type>{ty}</degree-type></degree>",
+                degree.value, degree.alter
+            );
+        }
+
+        xml + "</harmony>"
+    }
+}
+
+fn alter_element(name: &str, alter: i8) -> String {
+    if alter == 0 {
+        String::new()
+    } else {
+        format!("<{name}>{alter}</{name}>")
+    }
+}
+
+impl fmt::Display for Harmony {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.root, self.kind.suffix())?;
+
+        if let Some(degree) = self.suspended {
+            write!(f, "sus{degree}")?;
+        }
+
+        for degree in &self.degrees {
+            let accidental = match degree.alter {
+                a if a < 0 => "b",
+                a if a > 0 => "#",
+                _ => "",
+            };
+
+            match degree.ty {
+                DegreeType::Add => write!(f, "add{accidental}")?,
+                DegreeType::Alter => write!(f, "{accidental}")?,
+                DegreeType::Subtract => write!(f, "no")?,
+            }
+
+            write!(f, "{}", degree.value)?;
+        }
+
+        if let Some(bass) = self.bass {
+            write!(f, "/{bass}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Triad {
+    Major,
+    Minor,
+    Augmented,
+    Diminished,
+    HalfDiminished,
+}
+
+impl FromStr for Harmony {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid chord symbol \"{s}\"");
+
+        let symbol = s.trim();
+        let (root, rest) = Spelling::parse(symbol).ok_or_else(invalid)?;
+
+        // A bass is the last part after a slash, unlike in 6/9
+        let (mut rest, bass) = match rest.rsplit_once('/') {
+            Some((_, "")) => return Err(invalid()),
+            Some((body, bass)) => match Spelling::parse(bass) {
+                Some((bass, "")) => (body, Some(bass)),
+                _ => (rest, None),
+            },
+            None => (rest, None),
+        };
+
+        let take = |rest: &mut &str, prefixes: &[&str]| {
+            for prefix in prefixes {
+                if let Some(r) = rest.strip_prefix(prefix) {
+                    *rest = r;
+                    return true;
+                }
+            }
+            false
+        };
+
+        const MAJOR: &[&str] = &["maj", "Maj", "MA", "ma", "M"];
+        // Unlike the other major signs, a triangle alone means a seventh
+        const TRIANGLE: &[&str] = &["Δ", "^"];
+
+        let mut major_seventh = false;
+        let mut triangle = false;
+        let triad = if take(&mut rest, TRIANGLE) {
+            (major_seventh, triangle) = (true, true);
+            Triad::Major
+        } else if take(&mut rest, MAJOR) {
+            major_seventh = true;
+            Triad::Major
+        } else if take(&mut rest, &["min", "mi", "m", "-"]) {
+            Triad::Minor
+        } else if take(&mut rest, &["dim", "°", "o"]) {
+            Triad::Diminished
+        } else if take(&mut rest, &["aug", "+"]) {
+            Triad::Augmented
+        } else if take(&mut rest, &["ø", "Ø"]) {
+            Triad::HalfDiminished
+        } else {
+            Triad::Major
+        };
+
+        let mut number = None;
+        let mut suspended = None;
+        let mut degrees = Vec::new();
+
+        while !rest.is_empty() {
+            if take(&mut rest, &["(", ")", ",", " "]) {
+                continue;
+            }
+
+            if take(&mut rest, TRIANGLE) {
+                (major_seventh, triangle) = (true, true);
+            } else if take(&mut rest, MAJOR) {
+                major_seventh = true;
+            } else if take(&mut rest, &["sus2"]) {
+                suspended = Some(2);
+            } else if take(&mut rest, &["sus4", "sus"]) {
+                suspended = Some(4);
+            } else if take(&mut rest, &["add"]) {
+                let (alter, value) = altered_degree(&mut rest);
+                let value = value.ok_or_else(invalid)?;
+                degrees.push(Degree::new(value, alter, DegreeType::Add));
+                continue;
+            } else if take(&mut rest, &["no", "omit"]) {
+                let (_, value) = altered_degree(&mut rest);
+                let value = value.ok_or_else(invalid)?;
+                degrees.push(Degree::new(value, 0, DegreeType::Subtract));
+                continue;
+            } else if rest.starts_with(['b', '#', '♭', '♯', '+', '-']) {
+                let (alter, value) = altered_degree(&mut rest);
+                let value = value.ok_or_else(invalid)?;
+                degrees.push(Degree::new(value, alter, DegreeType::Alter));
+                continue;
+            } else if let Some(value) = digits(&mut rest) {
+                if number.is_none() {
+                    number = Some(value);
+                } else if value == 9 && number == Some(6) {
+                    // 6/9 chords
+                    degrees.push(Degree::new(9, 0, DegreeType::Add));
+                } else {
+                    return Err(invalid());
+                }
+                continue;
+            } else if take(&mut rest, &["/"]) {
+                continue;
+            } else {
+                return Err(invalid());
+            }
+
+            // A seventh may follow maj
+            if let Some(value) = digits(&mut rest) {
+                number = Some(value);
+            }
+        }
+
+        if triangle && number.is_none() {
+            number = Some(7);
+        }
+
+        let kind = kind(triad, number, major_seventh, &mut degrees)
+            .ok_or_else(invalid)?;
+
+        // Suspended triads have their own kinds
+        let (kind, suspended) = match (kind, suspended) {
+            (Kind::Major, Some(2)) => (Kind::SuspendedSecond, None),
+            (Kind::Major, Some(_)) => (Kind::SuspendedFourth, None),
+            (kind, suspended) => (kind, suspended),
+        };
+
+        Ok(Self {
+            root,
+            kind,
+            suspended,
+            degrees,
+            bass,
+        })
+    }
+}
+
+/// The kind of a chord from its triad and highest number, adding the
+/// degrees the kinds cannot tell.
+fn kind(
+    triad: Triad,
+    number: Option<u8>,
+    major_seventh: bool,
+    degrees: &mut Vec<Degree>,
+) -> Option<Kind> {
+    let add = |degrees: &mut Vec<Degree>, value| {
+        degrees.insert(0, Degree::new(value, 0, DegreeType::Add));
+    };
+
+    let kind = match (triad, number, major_seventh) {
+        (Triad::Major, None, _) => Kind::Major,
+        (Triad::Major, Some(5), false) => Kind::Power,
+        (Triad::Major, Some(6), _) => Kind::MajorSixth,
+        (Triad::Major, Some(7), false) => Kind::Dominant,
+        (Triad::Major, Some(7), true) => Kind::MajorSeventh,
+        (Triad::Major, Some(9), false) => Kind::DominantNinth,
+        (Triad::Major, Some(9), true) => Kind::MajorNinth,
+        (Triad::Major, Some(11), false) => Kind::Dominant11th,
+        (Triad::Major, Some(11), true) => Kind::Major11th,
+        (Triad::Major, Some(13), false) => Kind::Dominant13th,
+        (Triad::Major, Some(13), true) => Kind::Major13th,
+        (Triad::Minor, None, false) => Kind::Minor,
+        (Triad::Minor, Some(6), false) => Kind::MinorSixth,
+        (Triad::Minor, Some(7), true) => Kind::MajorMinor,
+        (Triad::Minor, Some(n @ (9 | 11 | 13)), true) => {
+            add(degrees, n);
+            Kind::MajorMinor
+        },
+        (Triad::Minor, Some(7), false) => {
+            // m7b5
+            match degrees.iter().position(|d| {
+                d.ty == DegreeType::Alter && d.value == 5 && d.alter == -1
+            }) {
+                Some(i) => {
+                    degrees.remove(i);
+                    Kind::HalfDiminished
+                },
+                None => Kind::MinorSeventh,
+            }
+        },
+        (Triad::Minor, Some(9), false) => Kind::MinorNinth,
+        (Triad::Minor, Some(11), false) => Kind::Minor11th,
+        (Triad::Minor, Some(13), false) => Kind::Minor13th,
+        (Triad::Diminished, None, false) => Kind::Diminished,
+        (Triad::Diminished, Some(7), false) => Kind::DiminishedSeventh,
+        (Triad::Augmented, None, false) => Kind::Augmented,
+        (Triad::Augmented, Some(7), false) => Kind::AugmentedSeventh,
+        (Triad::HalfDiminished, None | Some(7), false) => Kind::HalfDiminished,
+        (Triad::Diminished | Triad::HalfDiminished, Some(n @ (9 | 11)), _) => {
+            add(degrees, n);
+            match triad {
+                Triad::Diminished => Kind::DiminishedSeventh,
+                _ => Kind::HalfDiminished,
+            }
+        },
+        (Triad::Augmented, Some(n @ (9 | 11 | 13)), false) => {
+            add(degrees, n);
+            Kind::AugmentedSeventh
+        },
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+/// Reads a degree like `#11` or `b9`, with its alteration.
+fn altered_degree(rest: &mut &str) -> (i8, Option<u8>) {
+    let mut alter = 0;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            'b' | '♭' | '-' => alter -= 1,
+            '#' | '♯' | '+' => alter += 1,
+            _ => break,
+        }
+
+        *rest = &rest[c.len_utf8()..];
+    }
+
+    (alter, digits(rest))
+}
+
+fn digits(rest: &mut &str) -> Option<u8> {
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let value = rest[..end].parse().ok()?;
+
+    *rest = &rest[end..];
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn harmony(symbol: &str) -> Harmony {
+        symbol.parse().unwrap()
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(harmony("C").kind, Kind::Major);
+        assert_eq!(harmony("G7").kind, Kind::Dominant);
+        assert_eq!(harmony("Ebdim7").kind, Kind::DiminishedSeventh);
+        assert_eq!(harmony("F#m7b5").kind, Kind::HalfDiminished);
+        assert_eq!(harmony("Bbmaj9#11").kind, Kind::MajorNinth);
+        assert_eq!(harmony("Dsus4").kind, Kind::SuspendedFourth);
+        assert_eq!(harmony("Am(maj7)").kind, Kind::MajorMinor);
+        assert_eq!(harmony("C5").kind, Kind::Power);
+
+        let g7sus4 = harmony("G7sus4");
+        assert_eq!((g7sus4.kind, g7sus4.suspended), (Kind::Dominant, Some(4)));
+
+        assert_eq!(harmony("CM").kind, Kind::Major);
+        assert_eq!(harmony("Cmaj").kind, Kind::Major);
+        assert_eq!(harmony("CΔ").kind, Kind::MajorSeventh);
+        assert_eq!(harmony("C^").kind, Kind::MajorSeventh);
+        assert_eq!(harmony("CΔ9").kind, Kind::MajorNinth);
+
+        assert!("H7".parse::<Harmony>().is_err());
+        assert!("C7/".parse::<Harmony>().is_err());
+        assert!("Cwhat".parse::<Harmony>().is_err());
+    }
+
+    #[test]
+    fn pitch_classes() {
+        assert_eq!(harmony("C/E").pitch_classes(), vec![0, 4, 7]);
+        assert_eq!(harmony("C/E").bass.unwrap().pitch_class(), 4);
+        assert_eq!(harmony("F#m7b5").pitch_classes(), vec![0, 4, 6, 9]);
+        assert_eq!(harmony("Bbmaj9#11").intervals(), vec![0, 4, 7, 11, 14, 18]);
+        assert_eq!(harmony("G7sus4").intervals(), vec![0, 5, 7, 10]);
+        assert_eq!(harmony("C6/9").intervals(), vec![0, 4, 7, 9, 14]);
+        assert_eq!(
+            harmony("D7(b9,#11)").intervals(),
+            vec![0, 4, 7, 10, 13, 18]
+        );
+        assert_eq!(harmony("C7no3").intervals(), vec![0, 7, 10]);
+    }
+
+    #[test]
+    fn transpose_and_display() {
+        assert_eq!(harmony("F#m7b5").transpose(2).to_string(), "G#m7b5");
+        assert_eq!(
+            harmony("Bbmaj9#11/D").transpose(-2).to_string(),
+            "Abmaj9#11/C"
+        );
+        assert_eq!(harmony("G7sus4").to_string(), "G7sus4");
+        assert_eq!(harmony("Cadd9").to_string(), "Cadd9");
+
+        for symbol in ["F#m7b5", "Bbmaj9#11", "Dsus2", "Ebdim7", "A7b9/C#"] {
+            assert_eq!(harmony(&harmony(symbol).to_string()), harmony(symbol));
+        }
+    }
+
+    #[test]
+    fn voicing_and_musicxml() {
+        assert_eq!(harmony("C/E").voicing(), vec![40, 48, 52, 55]);
+        assert_eq!(
+            harmony("F#m7b5").to_musicxml(),
+            "<harmony><root><root-step>F</root-step><root-alter>1</root-alter>\
+             </root><kind text=\"m7b5\">half-diminished</kind></harmony>"
+        );
+        assert!(harmony("Bb7/D")
+            .to_musicxml()
+            .contains("<bass><bass-step>D</bass-step></bass>"));
+    }
+}
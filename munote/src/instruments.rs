@@ -0,0 +1,133 @@
+use std::ops::RangeInclusive;
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::harmony::Spelling;
+
+/// An instrument or voice type, and the pitches it can play.
+///
+/// Pitches of scores are sounding pitches, the transposition only giving
+/// the written pitch of transposing instruments.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Instrument {
+    pub name: String,
+    /// Lower case names `\instr` names are matched with.
+    pub aliases: Vec<String>,
+    /// General MIDI program playing the instrument, from 0.
+    pub program: u8,
+    /// Semitones from the written to the sounding pitch.
+    #[serde(default)]
+    pub transposition: i8,
+    /// Sounding MIDI pitches any player can reach.
+    pub range: (u8, u8),
+    /// Sounding MIDI pitches all players are comfortable with.
+    pub comfortable: (u8, u8),
+}
+
+impl Instrument {
+    /// All the instruments known.
+    pub fn all() -> &'static [Instrument] {
+        lazy_static! {
+            static ref INSTRUMENTS: Vec<Instrument> =
+                load_instruments(include_str!("../assets/instruments.yaml"))
+                    .expect("Could not load instruments");
+        }
+
+        &INSTRUMENTS
+    }
+
+    /// The instrument with the longest alias an instrument name contains as
+    /// whole words, like the clarinet in A for `\instr<"Clarinet in A">`.
+    /// Plurals match too, like the violin for `\instr<"Violins">`.
+    pub fn find(name: &str) -> Option<&'static Instrument> {
+        let name = name.to_lowercase();
+
+        Self::all()
+            .iter()
+            .flat_map(|i| i.aliases.iter().map(move |a| (a, i)))
+            .filter(|(alias, _)| contains_words(&name, alias))
+            .max_by_key(|(alias, _)| alias.len())
+            .map(|(_, instrument)| instrument)
+    }
+
+    pub fn range(&self) -> RangeInclusive<u8> {
+        self.range.0..=self.range.1
+    }
+
+    pub fn comfortable(&self) -> RangeInclusive<u8> {
+        self.comfortable.0..=self.comfortable.1
+    }
+
+    /// Written pitch of a sounding pitch.
+    pub fn written(&self, pitch: u8) -> i32 {
+        i32::from(pitch) - i32::from(self.transposition)
+    }
+}
+
+/// Whether `words` are in `text` without being part of longer words, but
+/// for a plural ending.
+fn contains_words(text: &str, words: &str) -> bool {
+    let boundary = |c: Option<char>| !c.is_some_and(char::is_alphanumeric);
+
+    text.match_indices(words).any(|(i, _)| {
+        let after = &text[i + words.len()..];
+        let after = ["es", "s"]
+            .iter()
+            .find_map(|s| after.strip_prefix(s))
+            .filter(|a| boundary(a.chars().next()))
+            .unwrap_or(after);
+
+        boundary(text[..i].chars().next_back())
+            && boundary(after.chars().next())
+    })
+}
+
+/// Name of a MIDI pitch in scientific pitch notation, like `C#4` for 61.
+pub fn pitch_name(pitch: i32) -> String {
+    let spelling =
+        Spelling::from_pitch_class(pitch.rem_euclid(12) as u8, false);
+
+    format!("{spelling}{}", pitch.div_euclid(12) - 1)
+}
+
+fn load_instruments(input: &str) -> Result<Vec<Instrument>> {
+    let instruments = serde_yaml::from_str(input)?;
+
+    Ok(instruments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find() {
+        let name = |instr: &str| Instrument::find(instr).map(|i| &*i.name);
+
+        assert_eq!(name("Violin I"), Some("Violin"));
+        assert_eq!(name("Clarinet in A"), Some("Clarinet in A"));
+        assert_eq!(name("Clarinet"), Some("Clarinet in Bb"));
+        assert_eq!(name("Bass Clarinet"), Some("Bass clarinet"));
+        assert_eq!(name("Double Bass"), Some("Double bass"));
+        assert_eq!(name("Violins"), Some("Violin"));
+        assert_eq!(name("Double Basses"), Some("Double bass"));
+        assert_eq!(name("Bass Guitar"), Some("Electric bass"));
+        assert_eq!(name("Electric Bass"), Some("Electric bass"));
+        assert_eq!(name("Basso continuo"), Some("Harpsichord"));
+        assert_eq!(name("Bassoonist"), None);
+        assert_eq!(name("Theremin"), None);
+    }
+
+    #[test]
+    fn ranges() {
+        let clarinet = Instrument::find("clarinet").unwrap();
+
+        assert!(clarinet.range().contains(&50));
+        assert!(!clarinet.comfortable().contains(&50));
+        assert_eq!(pitch_name(clarinet.written(50)), "E3");
+        assert_eq!(pitch_name(61), "C#4");
+        assert_eq!(pitch_name(21), "A0");
+    }
+}
@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+
+use crate::{
+    accidentals::Accidentals,
+    chord::Chord,
+    clef::Clef,
+    duration::Duration,
+    key::Key,
+    note::Note,
+    rest::Rest,
+    score::Score,
+    tag::{Tag, TagType},
+    tag_id::TagId,
+    tempo::Tempo,
+    visitor::{VisitContext, Visitor},
+    voice::Voice,
+};
+
+/// Ticks per whole note, to compare onsets exactly.
+const TICKS: f32 = 3840.0;
+
+/// Where an item is drawn in the sequence of the score, items in the same
+/// slot being aligned across staffs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Slot {
+    pub ticks: i64,
+    pub rank: Rank,
+    /// Order of the items of a voice in the same ticks and rank.
+    pub index: u16,
+}
+
+impl Slot {
+    /// Onset in whole notes.
+    pub fn onset(&self) -> f32 {
+        self.ticks as f32 / TICKS
+    }
+}
+
+/// What comes first among items starting at the same time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rank {
+    Bar,
+    Attribute,
+    Grace,
+    Event,
+}
+
+#[derive(Clone, Debug)]
+pub struct Item {
+    pub slot: Slot,
+    /// Index of the staff in display order.
+    pub staff: usize,
+    pub voice: usize,
+    pub kind: Kind,
+}
+
+#[derive(Clone, Debug)]
+pub enum Kind {
+    /// A note or a chord, without heads for `empty` events.
+    Chord(Heads),
+    Rest {
+        /// Denominator of the written value, like 4 for a quarter rest.
+        value: u8,
+        dots: usize,
+        small: bool,
+    },
+    MultiRest(u32),
+    Bar(BarStyle),
+    Clef(Clef),
+    Key(Key),
+    Meter(String),
+    /// Where a `\newLine` or a `\newPage` starts the next system.
+    Break {
+        page: bool,
+    },
+    /// Text above the staff, on the given row from the staff up.
+    Text {
+        text: String,
+        row: u8,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Heads {
+    pub heads: Vec<Head>,
+    /// Denominator of the written value, like 8 for eighth notes.
+    pub value: u8,
+    pub dots: usize,
+    /// Whether these are grace or cue notes.
+    pub small: bool,
+}
+
+impl Heads {
+    pub fn beams(&self) -> u8 {
+        (self.value.max(4).trailing_zeros() - 2) as u8
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Head {
+    /// Steps above the bottom line of the staff.
+    pub position: i32,
+    pub accidental: Option<Accidentals>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BarStyle {
+    Single,
+    Double,
+    End,
+    RepeatBegin,
+    RepeatEnd,
+}
+
+/// The items of a score, with what ties them together.
+#[derive(Debug, Default)]
+pub struct Collection {
+    pub items: Vec<Item>,
+    /// Number of lines of each staff.
+    pub lines: Vec<u8>,
+    /// Number of voices of each staff.
+    pub voices: Vec<usize>,
+    /// First and last chords of the slurs and ties.
+    pub slurs: Vec<(usize, usize)>,
+    /// Chords beamed together.
+    pub beams: Vec<Vec<usize>>,
+    pub title: Option<String>,
+    pub composer: Option<String>,
+    /// The first `\pageFormat` tag.
+    pub page: Option<Tag>,
+}
+
+impl Collection {
+    pub fn new(score: &Score) -> Self {
+        let mut collector = Collector {
+            staffs: score
+                .staffs
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (s.id, i))
+                .collect(),
+            collection: Self {
+                lines: score
+                    .staffs
+                    .iter()
+                    .map(|s| s.lines.unwrap_or(5))
+                    .collect(),
+                voices: score.staffs.iter().map(|s| s.voices.len()).collect(),
+                ..Default::default()
+            },
+            staff: 0,
+            voice: 0,
+            last: None,
+            measure: 1,
+            open: Vec::new(),
+            group: Vec::new(),
+            beat: (0, 0),
+            auto_beams: true,
+        };
+
+        score.visit(&mut collector);
+
+        collector.collection
+    }
+}
+
+/// A slur, tie or beam waiting for its end.
+struct Open {
+    id: TagId,
+    /// Suffix of a `Begin` tag.
+    suffix: Option<u8>,
+    chords: Vec<usize>,
+}
+
+struct Collector {
+    /// Index of the staffs by number.
+    staffs: HashMap<u8, usize>,
+    collection: Collection,
+    staff: usize,
+    voice: usize,
+    /// Last item of the voice, to number the items of the same slot.
+    last: Option<Slot>,
+    /// Measure of the last barline.
+    measure: u32,
+    open: Vec<Open>,
+    /// Chords beamed together by the meter so far.
+    group: Vec<usize>,
+    /// Measure and beat of the group.
+    beat: (u32, i64),
+    auto_beams: bool,
+}
+
+impl Collector {
+    fn push(&mut self, onset: f32, rank: Rank, kind: Kind) -> usize {
+        let ticks = (onset * TICKS).round() as i64;
+        let index = match self.last {
+            Some(last) if (last.ticks, last.rank) == (ticks, rank) => {
+                last.index + 1
+            },
+            _ => 0,
+        };
+        let slot = Slot { ticks, rank, index };
+
+        self.last = Some(slot);
+        self.collection.items.push(Item {
+            slot,
+            staff: self.staff,
+            voice: self.voice,
+            kind,
+        });
+
+        self.collection.items.len() - 1
+    }
+
+    /// Draws the barlines the meter implies before an event.
+    fn bar(&mut self, cx: &VisitContext) {
+        if cx.measure > self.measure {
+            self.measure = cx.measure;
+            self.push(cx.measure_onset, Rank::Bar, Kind::Bar(BarStyle::Single));
+        }
+    }
+
+    fn chord(&mut self, notes: &[&Note], cx: &VisitContext) {
+        if cx.in_chord() || cx.is_within(TagId::Mrest) {
+            return;
+        }
+        let Some(first) = notes.first() else {
+            return;
+        };
+
+        self.bar(cx);
+
+        let grace = cx.grace().is_some();
+        let heads = Heads {
+            heads: notes
+                .iter()
+                .filter(|n| !n.is_empty())
+                .map(|n| Head {
+                    position: cx.staff_position(n),
+                    accidental: (n.accidentals != Accidentals::Natural)
+                        .then(|| n.accidentals.clone()),
+                })
+                .collect(),
+            value: value(first.duration),
+            dots: usize::from(first.dots),
+            small: grace || cx.is_cue(),
+        };
+
+        let beamed = !grace
+            && self.auto_beams
+            && heads.beams() > 0
+            && !heads.heads.is_empty()
+            && !self.open.iter().any(|o| o.id == TagId::Beam);
+        let rank = if grace { Rank::Grace } else { Rank::Event };
+        let i = self.push(cx.onset, rank, Kind::Chord(heads));
+
+        for open in &mut self.open {
+            open.chords.push(i);
+        }
+
+        // Beams by quarter notes
+        let beat = ((cx.onset - cx.measure_onset) * 4.0 + 1e-4).floor() as i64;
+        if !beamed || self.beat != (cx.measure, beat) {
+            self.flush();
+        }
+        if beamed {
+            self.beat = (cx.measure, beat);
+            self.group.push(i);
+        }
+    }
+
+    /// Beams the chords grouped so far.
+    fn flush(&mut self) {
+        let group = std::mem::take(&mut self.group);
+
+        if group.len() > 1 {
+            self.collection.beams.push(group);
+        }
+    }
+
+    fn open(&mut self, tag: &Tag, suffix: Option<u8>) {
+        self.open.push(Open {
+            id: tag.id,
+            suffix,
+            chords: Vec::new(),
+        });
+    }
+
+    fn close(&mut self, id: TagId, suffix: Option<u8>) {
+        let Some(i) = self
+            .open
+            .iter()
+            .rposition(|o| o.id == id && o.suffix == suffix)
+        else {
+            return;
+        };
+        let open = self.open.remove(i);
+        let (Some(&first), Some(&last)) =
+            (open.chords.first(), open.chords.last())
+        else {
+            return;
+        };
+
+        if id != TagId::Beam {
+            if first != last {
+                self.collection.slurs.push((first, last));
+            }
+            return;
+        }
+
+        let beamed = open.chords.iter().all(|&i| {
+            let kind = &self.collection.items[i].kind;
+            matches!(kind, Kind::Chord(h) if h.beams() > 0)
+        });
+        if beamed && open.chords.len() > 1 {
+            self.collection.beams.push(open.chords);
+        }
+    }
+}
+
+impl Visitor<'_> for Collector {
+    fn on_voice(&mut self, _voice: &Voice, cx: &VisitContext) {
+        self.staff = self.staffs[&cx.staff];
+        self.voice = cx.voice;
+        self.last = None;
+        self.measure = 1;
+        self.open.clear();
+        self.group.clear();
+        self.auto_beams = true;
+    }
+
+    fn on_voice_end(&mut self, _voice: &Voice, cx: &VisitContext) {
+        self.flush();
+
+        let ended =
+            self.last.is_some_and(|s| s.rank == Rank::Bar) || cx.onset <= 0.0;
+        if !ended {
+            self.push(cx.onset, Rank::Bar, Kind::Bar(BarStyle::End));
+        }
+    }
+
+    fn on_note(&mut self, note: &Note, cx: &VisitContext) {
+        self.chord(&[note], cx);
+    }
+
+    fn on_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        self.chord(&chord.notes().collect::<Vec<_>>(), cx);
+    }
+
+    fn on_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        if cx.in_chord() || cx.is_within(TagId::Mrest) {
+            return;
+        }
+
+        self.bar(cx);
+        self.flush();
+        self.push(
+            cx.onset,
+            Rank::Event,
+            Kind::Rest {
+                value: value(rest.duration),
+                dots: usize::from(rest.dots),
+                small: cx.grace().is_some() || cx.is_cue(),
+            },
+        );
+    }
+
+    fn on_tag(&mut self, tag: &Tag, cx: &VisitContext) {
+        let style = match tag.id {
+            TagId::Bar => Some(BarStyle::Single),
+            TagId::DoubleBar => Some(BarStyle::Double),
+            TagId::EndBar => Some(BarStyle::End),
+            TagId::RepeatBegin => Some(BarStyle::RepeatBegin),
+            TagId::RepeatEnd => Some(BarStyle::RepeatEnd),
+            _ => None,
+        };
+
+        if let Some(style) = style {
+            self.measure = cx.measure;
+            self.flush();
+
+            if tag.get_str("hidden") != Some("true") {
+                self.push(cx.onset, Rank::Bar, Kind::Bar(style));
+            }
+            return;
+        }
+
+        let text = |row| {
+            let text = tag.get_str("text").or_else(|| tag.as_str())?;
+            Some(Kind::Text {
+                text: text.to_string(),
+                row,
+            })
+        };
+
+        let attribute = match tag.id {
+            TagId::Clef => Clef::from_tag(tag).map(Kind::Clef),
+            TagId::Key => Key::from_tag(tag).map(Kind::Key),
+            TagId::Meter => tag
+                .get_str("type")
+                .or_else(|| tag.as_str())
+                .map(|m| Kind::Meter(m.to_string())),
+            TagId::NewLine => Some(Kind::Break { page: false }),
+            TagId::NewPage => Some(Kind::Break { page: true }),
+            _ => None,
+        };
+
+        if let Some(kind) = attribute {
+            self.push(cx.onset, Rank::Attribute, kind);
+            return;
+        }
+
+        let event = match tag.id {
+            TagId::Text => text(0),
+            TagId::Harmony => text(1),
+            TagId::Tempo => Tempo::from_tag(tag).map(|tempo| {
+                let text = match tempo.mark {
+                    Some(mark) => format!("{} {mark}", tempo.text),
+                    None => tempo.text,
+                };
+
+                Kind::Text {
+                    text: text.trim().to_string(),
+                    row: 1,
+                }
+            }),
+            TagId::Mrest => {
+                self.bar(cx);
+                self.flush();
+                tag.rest_measures().map(Kind::MultiRest)
+            },
+            _ => None,
+        };
+
+        if let Some(kind) = event {
+            self.push(cx.onset, Rank::Event, kind);
+            return;
+        }
+
+        let name = || tag.get_str("name").or_else(|| tag.as_str());
+        let collection = &mut self.collection;
+
+        match tag.id {
+            TagId::Title if collection.title.is_none() => {
+                collection.title = name().map(str::to_string);
+            },
+            TagId::Composer if collection.composer.is_none() => {
+                collection.composer = name().map(str::to_string);
+            },
+            TagId::PageFormat if collection.page.is_none() => {
+                collection.page = Some(tag.clone());
+            },
+            TagId::BeamsOff => {
+                self.flush();
+                self.auto_beams = false;
+            },
+            TagId::BeamsAuto | TagId::BeamsFull => self.auto_beams = true,
+            TagId::Slur | TagId::Tie | TagId::Beam => match tag.ty {
+                TagType::Begin(suffix) => self.open(tag, Some(suffix)),
+                TagType::End(suffix) => self.close(tag.id, Some(suffix)),
+                _ if !tag.events.is_empty() => self.open(tag, None),
+                _ => {},
+            },
+            _ => {},
+        }
+    }
+
+    fn on_tag_end(&mut self, tag: &Tag, _cx: &VisitContext) {
+        let spans = matches!(tag.id, TagId::Slur | TagId::Tie | TagId::Beam);
+
+        if spans && !tag.events.is_empty() {
+            self.close(tag.id, None);
+        }
+    }
+}
+
+/// Denominator of the written value of a duration, like 8 for an eighth
+/// note or the eighth notes of triplets.
+fn value(duration: Duration) -> u8 {
+    let mut value = 1u8;
+
+    if duration.num == 1 {
+        while value < 128 && value * 2 <= duration.denom {
+            value *= 2;
+        }
+    } else {
+        let length = duration.as_f32();
+        while value < 128 && 1.0 / (value as f32) > length + f32::EPSILON {
+            value *= 2;
+        }
+    }
+
+    value
+}
@@ -0,0 +1,1264 @@
+use std::ops::Range;
+
+use crate::{
+    accidentals::Accidentals,
+    clef::{Clef, ClefSign},
+    key::Key,
+    layout::{
+        collect::{BarStyle, Collection, Heads, Kind, Rank, Slot},
+        Align, Layout, LayoutOptions, Onset, Page, PageFormat, Point,
+        Primitive,
+    },
+    note::{Diatonic, Note},
+    symbols::Symbols,
+};
+
+// Sizes in staff spaces
+const HEAD_WIDTH: f32 = 1.2;
+const WHOLE_WIDTH: f32 = 1.6;
+const ACCIDENTAL_WIDTH: f32 = 1.0;
+const DOT_WIDTH: f32 = 0.5;
+const FLAG_WIDTH: f32 = 1.0;
+const CLEF_WIDTH: f32 = 3.0;
+const METER_WIDTH: f32 = 2.0;
+const REST_WIDTH: f32 = 1.2;
+const MULTI_REST_WIDTH: f32 = 8.0;
+/// Room left after the items of a column.
+const PADDING: f32 = 0.6;
+const STEM_LENGTH: f32 = 3.5;
+/// Shortest stem of a beamed note.
+const BEAMED_STEM: f32 = 2.5;
+const STAFF_LINE: f32 = 0.13;
+const STEM: f32 = 0.12;
+const LEDGER: f32 = 0.16;
+/// How far ledger lines go past the heads.
+const LEDGER_OVERHANG: f32 = 0.4;
+const THIN_BAR: f32 = 0.16;
+const THICK_BAR: f32 = 0.5;
+const BEAM: f32 = 0.5;
+const BEAM_DISTANCE: f32 = 0.75;
+const SLUR: f32 = 0.15;
+const TEXT_SIZE: f32 = 1.8;
+/// Size of grace and cue notes, relative to the other ones.
+const SMALL_NOTES: f32 = 0.7;
+
+/// Steps above the bottom line of the sharps and flats of key signatures
+/// in a treble clef.
+const SHARPS: [i32; 7] = [8, 5, 9, 6, 3, 7, 4];
+const FLATS: [i32; 7] = [4, 7, 3, 6, 2, 5, 1];
+
+pub fn engrave(collection: &Collection, options: &LayoutOptions) -> Layout {
+    let page = collection
+        .page
+        .as_ref()
+        .and_then(|tag| PageFormat::from_tag(tag, &options.page))
+        .unwrap_or(options.page);
+
+    let mut engraver = Engraver::new(collection, options, page);
+    engraver.break_systems();
+    engraver.place();
+    engraver.draw();
+
+    Layout {
+        onsets: engraver.onsets(),
+        pages: engraver.pages,
+    }
+}
+
+/// How the heads, stem and accidentals of a chord are arranged.
+#[derive(Clone, Debug, Default)]
+struct Shape {
+    up: bool,
+    head_width: f32,
+    /// Positions of the heads, with their offset to the right for the heads
+    /// on the wrong side of the stem.
+    heads: Vec<(i32, f32)>,
+    /// Accidentals with their offset to the heads.
+    accidentals: Vec<(i32, f32, Accidentals)>,
+    left: f32,
+    width: f32,
+}
+
+/// Items drawn at the same horizontal position.
+#[derive(Debug)]
+struct Column {
+    slot: Slot,
+    items: Vec<usize>,
+    /// Room taken left of the position, by accidentals.
+    left: f32,
+    width: f32,
+}
+
+#[derive(Debug)]
+struct System {
+    columns: Range<usize>,
+    /// First column drawn, the clefs and keys before it being drawn by the
+    /// header.
+    first: usize,
+    /// Clef and key of each staff at the start.
+    header: Vec<(Clef, Key)>,
+    /// Whether a `\newPage` starts it.
+    new_page: bool,
+    page: usize,
+    /// Positions of the columns, from the left of the page.
+    x: Vec<f32>,
+    /// Where the header ends and the staffs end.
+    start: f32,
+    end: f32,
+    /// Top lines of the staffs.
+    tops: Vec<f32>,
+}
+
+struct Engraver<'a> {
+    collection: &'a Collection,
+    options: &'a LayoutOptions,
+    page: PageFormat,
+    up: Vec<bool>,
+    shapes: Vec<Option<Shape>>,
+    columns: Vec<Column>,
+    /// Column of each item.
+    column: Vec<usize>,
+    systems: Vec<System>,
+    /// System of each column.
+    system: Vec<usize>,
+    pages: Vec<Page>,
+}
+
+impl<'a> Engraver<'a> {
+    fn new(
+        collection: &'a Collection,
+        options: &'a LayoutOptions,
+        page: PageFormat,
+    ) -> Self {
+        let items = &collection.items;
+        let mut up = items
+            .iter()
+            .map(|item| match &item.kind {
+                // Stems of the first voice up and the second one down
+                _ if collection.voices[item.staff] > 1 => item.voice % 2 == 0,
+                Kind::Chord(heads) => direction(&[heads]),
+                _ => true,
+            })
+            .collect::<Vec<_>>();
+
+        for group in &collection.beams {
+            if collection.voices[items[group[0]].staff] > 1 {
+                continue;
+            }
+
+            let heads = group
+                .iter()
+                .filter_map(|&i| match &items[i].kind {
+                    Kind::Chord(heads) => Some(heads),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let up_group = direction(&heads);
+
+            for &i in group {
+                up[i] = up_group;
+            }
+        }
+
+        let shapes = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| match &item.kind {
+                Kind::Chord(heads) => {
+                    let flagged = heads.beams() > 0
+                        && !collection.beams.iter().any(|g| g.contains(&i));
+                    Some(shape(heads, up[i], flagged))
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut order = (0..items.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| (items[i].slot, items[i].staff));
+
+        let mut columns: Vec<Column> = Vec::new();
+        let mut column = vec![0; items.len()];
+
+        for i in order {
+            let (left, width) = match &shapes[i] {
+                Some(shape) => (shape.left, shape.width),
+                None => (0.0, width(&items[i].kind)),
+            };
+
+            match columns.last_mut() {
+                Some(c) if c.slot == items[i].slot => {
+                    c.items.push(i);
+                    c.left = c.left.max(left);
+                    c.width = c.width.max(width);
+                },
+                _ => columns.push(Column {
+                    slot: items[i].slot,
+                    items: vec![i],
+                    left,
+                    width,
+                }),
+            }
+
+            column[i] = columns.len() - 1;
+        }
+
+        Self {
+            collection,
+            options,
+            page,
+            up,
+            shapes,
+            columns,
+            column,
+            systems: Vec::new(),
+            system: Vec::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Room from a column to the next one, proportional to the time between
+    /// them for events.
+    fn gap(&self, i: usize) -> f32 {
+        let column = &self.columns[i];
+        let padding = if column.width > 0.0 { PADDING } else { 0.0 };
+
+        let Some(next) = self.columns.get(i + 1) else {
+            return column.width + padding;
+        };
+
+        let ideal = if column.slot.rank == Rank::Event {
+            self.options.whole_width * (next.slot.onset() - column.slot.onset())
+        } else {
+            0.0
+        };
+
+        (column.width + padding + next.left).max(ideal)
+    }
+
+    /// Whether the clefs and keys of a column can be drawn by the header
+    /// of the system it starts.
+    fn is_header(&self, i: usize) -> bool {
+        self.columns[i].items.iter().all(|&item| {
+            matches!(
+                self.collection.items[item].kind,
+                Kind::Clef(_) | Kind::Key(_) | Kind::Break { .. }
+            )
+        })
+    }
+
+    fn is_bar(&self, i: usize) -> bool {
+        self.columns[i].slot.rank == Rank::Bar
+    }
+
+    /// Whether a `\newLine` or a `\newPage` comes before the column, and
+    /// which one.
+    fn break_before(&self, i: usize) -> Option<bool> {
+        self.columns[i].items.iter().find_map(|&item| {
+            match self.collection.items[item].kind {
+                Kind::Break { page } => Some(page),
+                _ => None,
+            }
+        })
+    }
+
+    fn apply(&self, i: usize, state: &mut [(Clef, Key)]) {
+        for &item in &self.columns[i].items {
+            let item = &self.collection.items[item];
+
+            match &item.kind {
+                Kind::Clef(clef) => state[item.staff].0 = *clef,
+                Kind::Key(key) => state[item.staff].1 = *key,
+                _ => {},
+            }
+        }
+    }
+
+    /// Breaks the columns into systems filling the width of the page,
+    /// preferably after a barline.
+    fn break_systems(&mut self) {
+        let gaps = (0..self.columns.len())
+            .map(|i| self.gap(i))
+            .collect::<Vec<_>>();
+        let mut state = vec![
+            (Clef::default(), Key::default());
+            self.collection.lines.len()
+        ];
+        let mut start = 0;
+
+        while start < self.columns.len() {
+            let new_page = self.break_before(start) == Some(true);
+
+            let mut first = start;
+            while first < self.columns.len() && self.is_header(first) {
+                if first > start && self.break_before(first).is_some() {
+                    break;
+                }
+                self.apply(first, &mut state);
+                first += 1;
+            }
+
+            let header = state.clone();
+            let available = self.page.content_width() - header_width(&header);
+            let mut end = self.columns.len();
+            let mut last_bar = None;
+            let mut x = self.columns.get(first).map_or(0.0, |c| c.left);
+
+            for (i, gap) in gaps.iter().enumerate().skip(first) {
+                if i > start && self.break_before(i).is_some() {
+                    end = i;
+                    break;
+                }
+
+                if i > first && x + self.columns[i].width > available {
+                    end = match last_bar {
+                        Some(bar) if bar > first => bar + 1,
+                        _ => i,
+                    };
+                    break;
+                }
+
+                if self.is_bar(i) {
+                    last_bar = Some(i);
+                }
+
+                x += gap;
+            }
+
+            for i in first..end {
+                self.apply(i, &mut state);
+            }
+
+            self.systems.push(System {
+                columns: start..end,
+                first,
+                header,
+                new_page,
+                page: 0,
+                x: Vec::new(),
+                start: 0.0,
+                end: 0.0,
+                tops: Vec::new(),
+            });
+
+            start = end;
+        }
+
+        self.system = vec![0; self.columns.len()];
+        for (s, system) in self.systems.iter().enumerate() {
+            for i in system.columns.clone() {
+                self.system[i] = s;
+            }
+        }
+    }
+
+    /// Positions the columns in their systems, stretched to the width of
+    /// the page but for the last system, and the systems on the pages.
+    fn place(&mut self) {
+        let gaps = (0..self.columns.len())
+            .map(|i| self.gap(i))
+            .collect::<Vec<_>>();
+        let staffs = self.collection.lines.len();
+        let height =
+            staffs.saturating_sub(1) as f32 * self.options.staff_distance + 4.0;
+        let last = self.systems.len().saturating_sub(1);
+
+        let mut page = 0;
+        let mut y = self.page.top;
+        let mut on_page = 0;
+        self.pages.push(self.new_page());
+
+        for (s, system) in self.systems.iter_mut().enumerate() {
+            let drawn = system.first..system.columns.end;
+            let start = self.page.left + header_width(&system.header);
+            let available =
+                self.page.content_width() - header_width(&system.header);
+
+            let mut gaps = drawn.clone().map(|i| gaps[i]).collect::<Vec<_>>();
+            if let (Some(gap), Some(i)) =
+                (gaps.last_mut(), drawn.clone().last())
+            {
+                // Barlines end their system
+                *gap = self.columns[i].width;
+            }
+
+            let left =
+                drawn.clone().next().map_or(0.0, |i| self.columns[i].left);
+            let natural = left + gaps.iter().sum::<f32>();
+            let stretched = drawn
+                .clone()
+                .zip(&gaps)
+                .take(gaps.len().saturating_sub(1))
+                .filter(|(i, _)| self.columns[*i].slot.rank == Rank::Event)
+                .map(|(_, gap)| gap)
+                .sum::<f32>();
+
+            if s < last && natural < available && stretched > 0.0 {
+                let factor = 1.0 + (available - natural) / stretched;
+
+                for (i, gap) in drawn.clone().zip(gaps.iter_mut()) {
+                    if self.columns[i].slot.rank == Rank::Event
+                        && i + 1 < drawn.end
+                    {
+                        *gap *= factor;
+                    }
+                }
+            }
+
+            let mut x = start + left;
+            system.x = vec![start; system.columns.len()];
+            for (i, gap) in drawn.clone().zip(&gaps) {
+                system.x[i - system.columns.start] = x;
+                x += gap;
+            }
+
+            system.start = start;
+            system.end = if s < last && stretched > 0.0 {
+                self.page.width - self.page.right
+            } else {
+                x
+            };
+
+            let full = y + height > self.page.height - self.page.bottom;
+            if on_page > 0 && (system.new_page || full) {
+                page += 1;
+                y = self.page.top;
+                on_page = 0;
+                self.pages.push(Page {
+                    width: self.page.width,
+                    height: self.page.height,
+                    primitives: Vec::new(),
+                });
+            }
+
+            system.page = page;
+            system.tops = (0..staffs)
+                .map(|i| y + i as f32 * self.options.staff_distance)
+                .collect();
+
+            y += height + self.options.system_distance;
+            on_page += 1;
+        }
+    }
+
+    /// Where the first event of each time is drawn.
+    fn onsets(&self) -> Vec<Onset> {
+        let mut onsets: Vec<Onset> = Vec::new();
+
+        for system in &self.systems {
+            let (Some(&top), Some(&last)) =
+                (system.tops.first(), system.tops.last())
+            else {
+                continue;
+            };
+
+            for i in system.first..system.columns.end {
+                let slot = self.columns[i].slot;
+                if slot.rank != Rank::Event
+                    || onsets.last().is_some_and(|o| o.time >= slot.onset())
+                {
+                    continue;
+                }
+
+                onsets.push(Onset {
+                    time: slot.onset(),
+                    page: system.page,
+                    x: system.x[i - system.columns.start],
+                    top,
+                    bottom: last + 4.0,
+                });
+            }
+        }
+
+        onsets
+    }
+
+    fn new_page(&self) -> Page {
+        let mut primitives = Vec::new();
+        let collection = self.collection;
+
+        if let Some(title) = &collection.title {
+            primitives.push(Primitive::Text {
+                at: Point::new(self.page.width / 2.0, self.page.top / 2.0),
+                text: title.clone(),
+                size: 4.0,
+                align: Align::Center,
+            });
+        }
+
+        if let Some(composer) = &collection.composer {
+            primitives.push(Primitive::Text {
+                at: Point::new(
+                    self.page.width - self.page.right,
+                    self.page.top - 2.0,
+                ),
+                text: composer.clone(),
+                size: TEXT_SIZE,
+                align: Align::Right,
+            });
+        }
+
+        Page {
+            width: self.page.width,
+            height: self.page.height,
+            primitives,
+        }
+    }
+
+    /// Position of an item, and the top line of its staff.
+    fn position(&self, item: usize) -> Option<(usize, f32, f32)> {
+        let column = self.column[item];
+        let s = self.system[column];
+        let system = &self.systems[s];
+
+        if column < system.first {
+            return None;
+        }
+
+        let x = system.x[column - system.columns.start];
+        let top = system.tops[self.collection.items[item].staff];
+
+        Some((s, x, top))
+    }
+
+    fn draw(&mut self) {
+        let mut pages = std::mem::take(&mut self.pages);
+
+        for system in &self.systems {
+            let out = &mut pages[system.page].primitives;
+
+            self.draw_staffs(system, out);
+        }
+
+        // Beamed chords get their stems with their beams
+        let mut beamed = vec![false; self.collection.items.len()];
+        for group in &self.collection.beams {
+            for part in self.parts(group) {
+                if part.len() > 1 {
+                    let page =
+                        self.systems[self.position(part[0]).unwrap().0].page;
+                    self.draw_beams(&part, &mut pages[page].primitives);
+
+                    for i in part {
+                        beamed[i] = true;
+                    }
+                }
+            }
+        }
+
+        let mut bars = Vec::new();
+        for (i, item) in self.collection.items.iter().enumerate() {
+            let Some((s, x, top)) = self.position(i) else {
+                continue;
+            };
+            let out = &mut pages[self.systems[s].page].primitives;
+            let lines = self.collection.lines[item.staff];
+
+            match &item.kind {
+                Kind::Chord(heads) => {
+                    let shape = self.shapes[i].as_ref().unwrap();
+
+                    draw_chord(heads, shape, x, top, lines, !beamed[i], out);
+                },
+                Kind::Rest { value, dots, small } => {
+                    draw_rest(*value, *dots, *small, x, top, out);
+                },
+                Kind::MultiRest(count) => draw_multi_rest(*count, x, top, out),
+                Kind::Bar(style) => {
+                    // Once for all the voices of the staff
+                    if !bars.contains(&(self.column[i], item.staff)) {
+                        bars.push((self.column[i], item.staff));
+                        draw_bar(*style, x, top, lines, out);
+                    }
+                },
+                Kind::Clef(clef) => draw_clef(clef, x, top, 0.8, out),
+                Kind::Key(key) => {
+                    let clef = self.clef_at(i);
+                    draw_key(key, &clef, x, top, out);
+                },
+                Kind::Meter(meter) => draw_meter(meter, x, top, out),
+                Kind::Break { .. } => {},
+                Kind::Text { text, row } => out.push(Primitive::Text {
+                    at: Point::new(x, top - 1.5 - 2.5 * f32::from(*row)),
+                    text: text.clone(),
+                    size: TEXT_SIZE,
+                    align: Align::Left,
+                }),
+            }
+        }
+
+        for &(first, last) in &self.collection.slurs {
+            self.draw_slur(first, last, &mut pages);
+        }
+
+        self.pages = pages;
+    }
+
+    /// The clef of the staff of an item, before it.
+    fn clef_at(&self, item: usize) -> Clef {
+        let staff = self.collection.items[item].staff;
+        let column = self.column[item];
+        let system = &self.systems[self.system[column]];
+        let mut clef = system.header[staff].0;
+
+        for i in system.first..column {
+            for &other in &self.columns[i].items {
+                let other = &self.collection.items[other];
+                if let (Kind::Clef(c), true) =
+                    (&other.kind, other.staff == staff)
+                {
+                    clef = *c;
+                }
+            }
+        }
+
+        clef
+    }
+
+    /// Staff lines, clefs and key signatures of a system.
+    fn draw_staffs(&self, system: &System, out: &mut Vec<Primitive>) {
+        let left = self.page.left;
+
+        for (staff, &top) in system.tops.iter().enumerate() {
+            let lines = self.collection.lines[staff];
+
+            for position in line_positions(lines) {
+                let y = y(top, position);
+                out.push(line(
+                    Point::new(left, y),
+                    Point::new(system.end, y),
+                    STAFF_LINE,
+                ));
+            }
+
+            let (clef, key) = &system.header[staff];
+            draw_clef(clef, left + 0.5, top, 1.0, out);
+            draw_key(key, clef, left + 0.5 + CLEF_WIDTH, top, out);
+        }
+
+        // Joins the staffs of the system
+        if let (Some(first), Some(last)) =
+            (system.tops.first(), system.tops.last())
+        {
+            if system.tops.len() > 1 {
+                out.push(line(
+                    Point::new(left, *first),
+                    Point::new(left, last + 4.0),
+                    THIN_BAR,
+                ));
+            }
+        }
+    }
+
+    /// The chords of a beam group, split by the systems they are drawn in.
+    fn parts(&self, group: &[usize]) -> Vec<Vec<usize>> {
+        let mut parts: Vec<Vec<usize>> = Vec::new();
+
+        for &i in group {
+            let Some((s, _, _)) = self.position(i) else {
+                continue;
+            };
+
+            match parts.last_mut() {
+                Some(part) if self.position(part[0]).unwrap().0 == s => {
+                    part.push(i)
+                },
+                _ => parts.push(vec![i]),
+            }
+        }
+
+        parts
+    }
+
+    /// Stems and beams of chords beamed together, the beams sloping like
+    /// the chords within limits.
+    fn draw_beams(&self, group: &[usize], out: &mut Vec<Primitive>) {
+        let up = self.up[group[0]];
+        let sign = if up { -1.0 } else { 1.0 };
+
+        // Stem position, far and near head, and beams of each chord
+        let stems = group
+            .iter()
+            .filter_map(|&i| {
+                let (_, x, top) = self.position(i)?;
+                let shape = self.shapes[i].as_ref()?;
+                let Kind::Chord(heads) = &self.collection.items[i].kind else {
+                    return None;
+                };
+                let (low, high) = extremes(shape)?;
+                let stem_x = if up { x + shape.head_width } else { x };
+                let (far, near) = if up { (low, high) } else { (high, low) };
+
+                Some((stem_x, y(top, far), y(top, near), heads.beams()))
+            })
+            .collect::<Vec<_>>();
+
+        let (Some(first), Some(last)) = (stems.first(), stems.last()) else {
+            return;
+        };
+
+        let run = last.0 - first.0;
+        let slope = if run > 0.0 {
+            ((last.2 - first.2) / run).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        let ideal =
+            |x: f32| first.2 + sign * STEM_LENGTH + slope * (x - first.0);
+
+        // Keeps the stems long enough
+        let shift = stems
+            .iter()
+            .map(|&(x, _, near, _)| {
+                (near + sign * BEAMED_STEM - ideal(x)) * sign
+            })
+            .fold(0.0f32, f32::max);
+        let beam = |x: f32| ideal(x) + sign * shift;
+
+        for &(x, far, _, _) in &stems {
+            out.push(line(Point::new(x, far), Point::new(x, beam(x)), STEM));
+        }
+
+        let levels = stems.iter().map(|s| s.3).max().unwrap_or(0);
+        for level in 0..levels {
+            let offset =
+                -sign * (BEAM / 2.0 + f32::from(level) * BEAM_DISTANCE);
+            let at = |x: f32| Point::new(x, beam(x) + offset);
+
+            for (i, &(x, _, _, beams)) in stems.iter().enumerate() {
+                if beams <= level {
+                    continue;
+                }
+
+                let next = stems.get(i + 1).filter(|s| s.3 > level);
+                let previous =
+                    i.checked_sub(1).map(|j| stems[j]).filter(|s| s.3 > level);
+
+                match (previous, next) {
+                    (_, Some(&(to, ..))) => out.push(Primitive::Beam {
+                        from: at(x),
+                        to: at(to),
+                        thickness: BEAM,
+                    }),
+                    // A partial beam towards the other chords
+                    (None, None) => {
+                        let to = if i == 0 { x + 1.0 } else { x - 1.0 };
+                        out.push(Primitive::Beam {
+                            from: at(x.min(to)),
+                            to: at(x.max(to)),
+                            thickness: BEAM,
+                        });
+                    },
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    /// A slur or tie on the other side of the stems, split at the end of
+    /// its system.
+    fn draw_slur(&self, first: usize, last: usize, pages: &mut [Page]) {
+        let below = self.up[first];
+        let end = |i: usize| {
+            let (s, x, top) = self.position(i)?;
+            let shape = self.shapes[i].as_ref()?;
+            let (low, high) = extremes(shape)?;
+            let y = if below {
+                y(top, low) + 1.0
+            } else {
+                y(top, high) - 1.0
+            };
+
+            Some((s, Point::new(x + shape.head_width / 2.0, y)))
+        };
+
+        let (Some((s1, from)), Some((s2, to))) = (end(first), end(last)) else {
+            return;
+        };
+
+        let curves = if s1 == s2 {
+            vec![(s1, from, to)]
+        } else {
+            vec![
+                (s1, from, Point::new(self.systems[s1].end, from.y)),
+                (s2, Point::new(self.systems[s2].start, to.y), to),
+            ]
+        };
+
+        for (s, from, to) in curves {
+            let run = to.x - from.x;
+            let height = (run * 0.15).clamp(0.8, 2.5);
+            let bend = if below { height } else { -height };
+
+            pages[self.systems[s].page]
+                .primitives
+                .push(Primitive::Curve {
+                    points: [
+                        from,
+                        Point::new(from.x + run / 4.0, from.y + bend),
+                        Point::new(to.x - run / 4.0, to.y + bend),
+                        to,
+                    ],
+                    thickness: SLUR,
+                });
+        }
+    }
+}
+
+/// Whether the stems of chords go up, when most of their heads are below
+/// the middle line.
+fn direction(chords: &[&Heads]) -> bool {
+    let balance = chords
+        .iter()
+        .filter_map(|heads| {
+            let low = heads.heads.iter().map(|h| h.position).min()?;
+            let high = heads.heads.iter().map(|h| h.position).max()?;
+            Some(low + high - 8)
+        })
+        .sum::<i32>();
+
+    balance < 0
+}
+
+/// Arranges the heads of a chord around its stem, with seconds on both
+/// sides, and its accidentals in columns not to overlap.
+fn shape(heads: &Heads, up: bool, flagged: bool) -> Shape {
+    let scale = if heads.small { SMALL_NOTES } else { 1.0 };
+    let head_width = scale
+        * if heads.value == 1 {
+            WHOLE_WIDTH
+        } else {
+            HEAD_WIDTH
+        };
+
+    let mut positions =
+        heads.heads.iter().map(|h| h.position).collect::<Vec<_>>();
+    positions.sort_unstable();
+    if !up {
+        positions.reverse();
+    }
+
+    let mut shape = Shape {
+        up,
+        head_width,
+        ..Default::default()
+    };
+    let mut previous: Option<(i32, bool)> = None;
+
+    for position in positions {
+        let moved =
+            matches!(previous, Some((p, false)) if (p - position).abs() == 1);
+        let dx = match (moved, up) {
+            (false, _) => 0.0,
+            (true, true) => head_width,
+            (true, false) => -head_width,
+        };
+
+        shape.heads.push((position, dx));
+        previous = Some((position, moved));
+    }
+
+    let moved_left = shape.heads.iter().any(|h| h.1 < 0.0);
+    let moved_right = shape.heads.iter().any(|h| h.1 > 0.0);
+    let start = if moved_left { head_width } else { 0.0 };
+
+    let mut accidentals = heads
+        .heads
+        .iter()
+        .filter_map(|h| Some((h.position, h.accidental.clone()?)))
+        .collect::<Vec<_>>();
+    accidentals.sort_by_key(|a| -a.0);
+
+    // Lowest accidental of each column
+    let mut columns: Vec<i32> = Vec::new();
+    for (position, accidental) in accidentals {
+        let column = match columns.iter().position(|&p| p - position >= 6) {
+            Some(c) => {
+                columns[c] = position;
+                c
+            },
+            None => {
+                columns.push(position);
+                columns.len() - 1
+            },
+        };
+        let dx = -start - scale * ACCIDENTAL_WIDTH * (column + 1) as f32 - 0.1;
+
+        shape.accidentals.push((position, dx, accidental));
+    }
+
+    shape.left = shape.accidentals.iter().map(|a| -a.1).fold(start, f32::max);
+    shape.width = head_width
+        + if moved_right { head_width } else { 0.0 }
+        + scale * DOT_WIDTH * heads.dots as f32
+        + if flagged { scale * FLAG_WIDTH } else { 0.0 };
+
+    shape
+}
+
+/// Lowest and highest heads of a chord.
+fn extremes(shape: &Shape) -> Option<(i32, i32)> {
+    let low = shape.heads.iter().map(|h| h.0).min()?;
+    let high = shape.heads.iter().map(|h| h.0).max()?;
+
+    Some((low, high))
+}
+
+fn width(kind: &Kind) -> f32 {
+    match kind {
+        Kind::Chord(_) | Kind::Break { .. } | Kind::Text { .. } => 0.0,
+        Kind::Rest { dots, small, .. } => {
+            let scale = if *small { SMALL_NOTES } else { 1.0 };
+            scale * (REST_WIDTH + DOT_WIDTH * *dots as f32)
+        },
+        Kind::MultiRest(_) => MULTI_REST_WIDTH,
+        Kind::Bar(style) => bar_width(*style),
+        Kind::Clef(_) => CLEF_WIDTH * 0.8,
+        Kind::Key(key) => {
+            ACCIDENTAL_WIDTH * f32::from(key.fifths.unsigned_abs())
+        },
+        Kind::Meter(_) => METER_WIDTH,
+    }
+}
+
+fn bar_width(style: BarStyle) -> f32 {
+    match style {
+        BarStyle::Single => THIN_BAR,
+        BarStyle::Double => 0.6,
+        BarStyle::End => 1.0,
+        BarStyle::RepeatBegin | BarStyle::RepeatEnd => 1.6,
+    }
+}
+
+/// Room taken by the clefs and keys at the start of a system.
+fn header_width(header: &[(Clef, Key)]) -> f32 {
+    let fifths = header
+        .iter()
+        .map(|(_, key)| key.fifths.unsigned_abs())
+        .max()
+        .unwrap_or(0);
+
+    0.5 + CLEF_WIDTH + ACCIDENTAL_WIDTH * f32::from(fifths) + PADDING
+}
+
+/// Steps above the bottom line of the lines of a staff.
+fn line_positions(lines: u8) -> impl Iterator<Item = i32> {
+    let lines = i32::from(lines.max(1));
+
+    (0..lines).map(move |i| 5 - lines + 2 * i)
+}
+
+/// Vertical position of a step above the bottom line of a staff.
+fn y(top: f32, position: i32) -> f32 {
+    top + 4.0 - position as f32 / 2.0
+}
+
+fn line(from: Point, to: Point, thickness: f32) -> Primitive {
+    Primitive::Line {
+        from,
+        to,
+        thickness,
+    }
+}
+
+fn glyph(x: f32, y: f32, name: &str, scale: f32) -> Primitive {
+    Primitive::Glyph {
+        at: Point::new(x, y),
+        glyph: Symbols::get(name),
+        scale,
+    }
+}
+
+fn accidental_glyph(accidental: &Accidentals) -> &'static str {
+    match accidental {
+        Accidentals::Natural => "MUSIC NATURAL SIGN",
+        Accidentals::Sharp => "MUSIC SHARP SIGN",
+        Accidentals::Flat => "MUSIC FLAT SIGN",
+        Accidentals::DoubleSharp => "DOUBLE SHARP",
+        Accidentals::DoubleFlat => "DOUBLE FLAT",
+    }
+}
+
+fn draw_chord(
+    heads: &Heads,
+    shape: &Shape,
+    x: f32,
+    top: f32,
+    lines: u8,
+    stem: bool,
+    out: &mut Vec<Primitive>,
+) {
+    let scale = if heads.small { SMALL_NOTES } else { 1.0 };
+    let Some((low, high)) = extremes(shape) else {
+        return;
+    };
+
+    let name = match heads.value {
+        1 => "WHOLE NOTE",
+        2 => "VOID NOTEHEAD",
+        _ => "NOTEHEAD BLACK",
+    };
+
+    // Ledger lines under and over the five lines of the staff
+    if lines == 5 {
+        let left = shape.heads.iter().map(|h| h.1).fold(0.0, f32::min);
+        let right = shape.heads.iter().map(|h| h.1).fold(0.0, f32::max);
+        let ledgers = (low..=-2).chain(10..=high).filter(|p| p % 2 == 0);
+
+        for position in ledgers {
+            let y = y(top, position);
+            out.push(line(
+                Point::new(x + left - LEDGER_OVERHANG, y),
+                Point::new(x + right + shape.head_width + LEDGER_OVERHANG, y),
+                LEDGER,
+            ));
+        }
+    }
+
+    for &(position, dx) in &shape.heads {
+        out.push(glyph(x + dx, y(top, position), name, scale));
+    }
+
+    for (position, dx, accidental) in &shape.accidentals {
+        out.push(glyph(
+            x + dx,
+            y(top, *position),
+            accidental_glyph(accidental),
+            scale,
+        ));
+    }
+
+    // Dots in the spaces
+    let right = shape.heads.iter().map(|h| h.1).fold(0.0, f32::max);
+    for &(position, _) in &shape.heads {
+        for dot in 0..heads.dots {
+            out.push(glyph(
+                x + right + shape.head_width + scale * DOT_WIDTH * dot as f32,
+                y(top, position | 1),
+                "COMBINING AUGMENTATION DOT",
+                scale,
+            ));
+        }
+    }
+
+    if !stem || heads.value < 2 {
+        return;
+    }
+
+    let sign = if shape.up { -1.0 } else { 1.0 };
+    let (far, near) = if shape.up { (low, high) } else { (high, low) };
+    let stem_x = if shape.up { x + shape.head_width } else { x };
+    let beams = heads.beams();
+    let length = scale
+        * (STEM_LENGTH + BEAM_DISTANCE * f32::from(beams.saturating_sub(2)));
+
+    // Stems reach the middle line
+    let middle = y(top, 4);
+    let mut tip = y(top, near) + sign * length;
+    if (tip - middle) * sign < 0.0 {
+        tip = middle;
+    }
+
+    out.push(line(
+        Point::new(stem_x, y(top, far)),
+        Point::new(stem_x, tip),
+        STEM * scale,
+    ));
+
+    for flag in 0..beams {
+        let from = Point::new(
+            stem_x,
+            tip - sign * BEAM_DISTANCE * scale * f32::from(flag),
+        );
+        let towards = |dx: f32, dy: f32| {
+            Point::new(from.x + dx * scale, from.y - sign * dy * scale)
+        };
+
+        out.push(Primitive::Curve {
+            points: [
+                from,
+                towards(0.0, 1.0),
+                towards(1.2, 1.2),
+                towards(1.0, 2.5),
+            ],
+            thickness: STEM * scale,
+        });
+    }
+}
+
+fn draw_rest(
+    value: u8,
+    dots: usize,
+    small: bool,
+    x: f32,
+    top: f32,
+    out: &mut Vec<Primitive>,
+) {
+    let scale = if small { SMALL_NOTES } else { 1.0 };
+    let name = match value {
+        1 => "WHOLE REST",
+        2 => "HALF REST",
+        4 => "QUARTER REST",
+        8 => "EIGHTH REST",
+        16 => "SIXTEENTH REST",
+        32 => "THIRTY-SECOND REST",
+        64 => "SIXTY-FOURTH REST",
+        _ => "ONE HUNDRED TWENTY-EIGHTH REST",
+    };
+
+    // Whole rests hang from the fourth line
+    let position = if value == 1 { 6 } else { 4 };
+    out.push(glyph(x, y(top, position), name, scale));
+
+    for dot in 0..dots {
+        out.push(glyph(
+            x + scale * (REST_WIDTH + DOT_WIDTH * dot as f32),
+            y(top, 5),
+            "COMBINING AUGMENTATION DOT",
+            scale,
+        ));
+    }
+}
+
+fn draw_multi_rest(count: u32, x: f32, top: f32, out: &mut Vec<Primitive>) {
+    let (left, right) = (x + 0.5, x + MULTI_REST_WIDTH - 0.5);
+
+    out.push(line(
+        Point::new(left, y(top, 4)),
+        Point::new(right, y(top, 4)),
+        0.8,
+    ));
+    for x in [left, right] {
+        out.push(line(
+            Point::new(x, y(top, 2)),
+            Point::new(x, y(top, 6)),
+            THIN_BAR,
+        ));
+    }
+
+    out.push(Primitive::Text {
+        at: Point::new(x + MULTI_REST_WIDTH / 2.0, top - 1.0),
+        text: count.to_string(),
+        size: 3.0,
+        align: Align::Center,
+    });
+}
+
+fn draw_bar(
+    style: BarStyle,
+    x: f32,
+    top: f32,
+    lines: u8,
+    out: &mut Vec<Primitive>,
+) {
+    // Single lines bar two spaces
+    let span = i32::from(lines.max(2)) - 1;
+    let (from, to) = (y(top, 4 + span), y(top, 4 - span));
+    let bar = |x: f32, thickness: f32| {
+        line(Point::new(x, from), Point::new(x, to), thickness)
+    };
+    let width = bar_width(style);
+
+    match style {
+        BarStyle::Single => out.push(bar(x + THIN_BAR / 2.0, THIN_BAR)),
+        BarStyle::Double => out.extend([
+            bar(x + THIN_BAR / 2.0, THIN_BAR),
+            bar(x + width - THIN_BAR / 2.0, THIN_BAR),
+        ]),
+        BarStyle::End => out.extend([
+            bar(x + THIN_BAR / 2.0, THIN_BAR),
+            bar(x + width - THICK_BAR / 2.0, THICK_BAR),
+        ]),
+        BarStyle::RepeatBegin => out.extend([
+            bar(x + THICK_BAR / 2.0, THICK_BAR),
+            bar(x + 0.8, THIN_BAR),
+            glyph(x + 1.1, y(top, 4), "REPEAT DOTS", 1.0),
+        ]),
+        BarStyle::RepeatEnd => out.extend([
+            glyph(x, y(top, 4), "REPEAT DOTS", 1.0),
+            bar(x + 0.8, THIN_BAR),
+            bar(x + width - THICK_BAR / 2.0, THICK_BAR),
+        ]),
+    }
+}
+
+fn draw_clef(
+    clef: &Clef,
+    x: f32,
+    top: f32,
+    scale: f32,
+    out: &mut Vec<Primitive>,
+) {
+    let name = match (clef.sign, clef.octave) {
+        (ClefSign::G, 1) => "G CLEF OTTAVA ALTA",
+        (ClefSign::G, -1) => "G CLEF OTTAVA BASSA",
+        (ClefSign::G, _) => "G CLEF",
+        (ClefSign::F, 1) => "F CLEF OTTAVA ALTA",
+        (ClefSign::F, -1) => "F CLEF OTTAVA BASSA",
+        (ClefSign::F, _) => "F CLEF",
+        (ClefSign::C, _) => "C CLEF",
+        (ClefSign::Percussion, _) => "DRUM CLEF-1",
+        (ClefSign::Tab | ClefSign::None, _) => return,
+    };
+    let position = 2 * (i32::from(clef.line) - 1);
+
+    out.push(glyph(x, y(top, position), name, scale));
+}
+
+/// The sharps or flats of a key, where the clef puts them.
+fn draw_key(
+    key: &Key,
+    clef: &Clef,
+    x: f32,
+    top: f32,
+    out: &mut Vec<Primitive>,
+) {
+    let (positions, name) = if key.fifths < 0 {
+        (FLATS, "MUSIC FLAT SIGN")
+    } else {
+        (SHARPS, "MUSIC SHARP SIGN")
+    };
+
+    // The same notes as in a treble clef, within an octave
+    let note = Note::from_name(Diatonic::C);
+    let shift = (clef.staff_position(&note, 0)
+        - Clef::default().staff_position(&note, 0))
+    .rem_euclid(7);
+    let shift = if shift > 3 { shift - 7 } else { shift };
+
+    for (i, position) in positions
+        .iter()
+        .take(usize::from(key.fifths.unsigned_abs()))
+        .enumerate()
+    {
+        out.push(glyph(
+            x + ACCIDENTAL_WIDTH * i as f32,
+            y(top, position + shift),
+            name,
+            1.0,
+        ));
+    }
+}
+
+/// A meter like `3/4`, `C` or `C/`.
+fn draw_meter(meter: &str, x: f32, top: f32, out: &mut Vec<Primitive>) {
+    match meter {
+        "C" => out.push(glyph(x, y(top, 4), "COMMON TIME", 1.0)),
+        "C/" => out.push(glyph(x, y(top, 4), "CUT TIME", 1.0)),
+        _ => {
+            let x = x + METER_WIDTH / 2.0;
+            let rows = match meter.split_once('/') {
+                Some((beats, unit)) => vec![(beats, 4), (unit, 0)],
+                None => vec![(meter, 2)],
+            };
+
+            for (text, baseline) in rows {
+                out.push(Primitive::Text {
+                    at: Point::new(x, y(top, baseline)),
+                    text: text.trim().to_string(),
+                    size: 3.0,
+                    align: Align::Center,
+                });
+            }
+        },
+    }
+}
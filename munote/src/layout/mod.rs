@@ -0,0 +1,356 @@
+use crate::score::Score;
+
+pub use page::PageFormat;
+
+mod collect;
+mod engrave;
+mod page;
+
+/// A point on a page, in staff spaces from its top left corner, `y` going
+/// down.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// What is drawn on a page, whatever draws it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Primitive {
+    /// A glyph of the Unicode musical symbols, drawn 4 staff spaces per em
+    /// times `scale`, from its left side and on the line or space it stands
+    /// for, like the line of a clef or the position of a note.
+    Glyph { at: Point, glyph: char, scale: f32 },
+    Line {
+        from: Point,
+        to: Point,
+        thickness: f32,
+    },
+    /// A beam from the end of a stem to the end of another, `thickness`
+    /// high around the line between them.
+    Beam {
+        from: Point,
+        to: Point,
+        thickness: f32,
+    },
+    /// A cubic Bézier curve, like a slur or a tie.
+    Curve { points: [Point; 4], thickness: f32 },
+    /// Text with its baseline at `at`, `size` staff spaces per em.
+    Text {
+        at: Point,
+        text: String,
+        size: f32,
+        align: Align,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Page {
+    pub width: f32,
+    pub height: f32,
+    pub primitives: Vec<Primitive>,
+}
+
+/// Where the events starting at the same time are drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Onset {
+    /// Time in whole notes.
+    pub time: f32,
+    pub page: usize,
+    pub x: f32,
+    /// Top line of the first staff and bottom line of the last one of the
+    /// system.
+    pub top: f32,
+    pub bottom: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutOptions {
+    /// Format of the pages, unless the score gives its own with
+    /// `\pageFormat`.
+    pub page: PageFormat,
+    /// Room taken by a whole note, in staff spaces, the room of the other
+    /// events being proportional to their duration.
+    pub whole_width: f32,
+    /// Distance between the top lines of the staffs of a system.
+    pub staff_distance: f32,
+    /// Distance between the bottom of a system and the top of the next one.
+    pub system_distance: f32,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            page: PageFormat::default(),
+            whole_width: 16.0,
+            staff_distance: 10.0,
+            system_distance: 8.0,
+        }
+    }
+}
+
+/// The pages of a score, as drawn by the GUI or any exporter.
+///
+/// Events starting at the same time are aligned across staffs and spaced
+/// by their duration, then broken into systems filling the width of the
+/// pages, at barlines and where `\newLine` and `\newPage` say.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    pub pages: Vec<Page>,
+    /// Every time an event starts, in order.
+    pub onsets: Vec<Onset>,
+}
+
+impl Layout {
+    pub fn new(score: &Score, options: &LayoutOptions) -> Self {
+        engrave::engrave(&collect::Collection::new(score), options)
+    }
+
+    /// The page and the ends of a line across the system played at a time
+    /// in whole notes, moving evenly from an onset to the next one of the
+    /// same system.
+    pub fn cursor(&self, time: f32) -> Option<(usize, Point, Point)> {
+        let i = self
+            .onsets
+            .partition_point(|o| o.time <= time)
+            .checked_sub(1)?;
+        let onset = &self.onsets[i];
+
+        let x = match self.onsets.get(i + 1) {
+            Some(next) if next.page == onset.page && next.top == onset.top => {
+                let progress = (time - onset.time) / (next.time - onset.time);
+                onset.x + (next.x - onset.x) * progress
+            },
+            _ => onset.x,
+        };
+
+        Some((
+            onset.page,
+            Point::new(x, onset.top),
+            Point::new(x, onset.bottom),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{symbols::Symbols, unit::Unit};
+
+    fn layout(input: &str) -> Result<Layout> {
+        Ok(Layout::new(
+            &Score::parse(input)?,
+            &LayoutOptions::default(),
+        ))
+    }
+
+    fn glyphs(page: &Page, names: &[&str]) -> Vec<Point> {
+        let glyphs = names.iter().map(|n| Symbols::get(n)).collect::<Vec<_>>();
+
+        page.primitives
+            .iter()
+            .filter_map(|p| match p {
+                Primitive::Glyph { at, glyph, .. }
+                    if glyphs.contains(glyph) =>
+                {
+                    Some(*at)
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn heads(page: &Page) -> Vec<Point> {
+        glyphs(page, &["NOTEHEAD BLACK", "VOID NOTEHEAD", "WHOLE NOTE"])
+    }
+
+    fn assert_approx(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 0.001, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn spacing() -> Result<()> {
+        let layout = layout("[ c1/4 d/2 e/4 ]")?;
+        let x = heads(&layout.pages[0])
+            .iter()
+            .map(|p| p.x)
+            .collect::<Vec<_>>();
+
+        // 16 staff spaces per whole note
+        assert_approx(&[x[1] - x[0], x[2] - x[1]], &[4.0, 8.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn alignment() -> Result<()> {
+        let layout = layout("{ [ c1/2 d/4 e ], [ \\staff<2> g0/4 a b c1 ] }")?;
+        let mut x = heads(&layout.pages[0])
+            .iter()
+            .map(|p| p.x)
+            .collect::<Vec<_>>();
+
+        assert_eq!(x.len(), 7);
+        x.sort_by(f32::total_cmp);
+        x.dedup_by(|a, b| (*a - *b).abs() < 0.001);
+        assert_eq!(x.len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ledger_lines() -> Result<()> {
+        let ledgers = |input| -> Result<usize> {
+            let layout = layout(input)?;
+
+            Ok(layout.pages[0]
+                .primitives
+                .iter()
+                .filter(|p| {
+                    matches!(p, Primitive::Line { from, to, thickness }
+                        if from.y == to.y && *thickness == 0.16)
+                })
+                .count())
+        };
+
+        assert_eq!(ledgers("[ e1/4 f2 ]")?, 0);
+        assert_eq!(ledgers("[ c1/4 ]")?, 1);
+        assert_eq!(ledgers("[ a2/4 ]")?, 1);
+        assert_eq!(ledgers("[ c3/4 ]")?, 2);
+        assert_eq!(ledgers("[ {c1/4, c3} ]")?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn accidentals() -> Result<()> {
+        let layout = layout("[ c#1/4 {c, e&, g} ]")?;
+        let page = &layout.pages[0];
+        let heads = heads(page);
+        let sharps = glyphs(page, &["MUSIC SHARP SIGN"]);
+        let flats = glyphs(page, &["MUSIC FLAT SIGN"]);
+
+        assert_eq!((sharps.len(), flats.len()), (1, 1));
+        assert!(sharps[0].x < heads[0].x);
+        assert_eq!(sharps[0].y, heads[0].y);
+        assert!(flats[0].x < heads[1].x);
+
+        Ok(())
+    }
+
+    #[test]
+    fn systems() -> Result<()> {
+        let lines = heads(&layout("[ c1/4 c \\newLine c c ]")?.pages[0]);
+
+        assert_eq!(lines[0].y, lines[1].y);
+        assert!(lines[2].y > lines[1].y);
+        assert!(lines[2].x < lines[1].x);
+
+        // Systems filling the width of the page but for the last one
+        let measures = "c1/4 d e f \\bar ".repeat(20);
+        let layout = layout(&format!("[ {measures} ]"))?;
+        let page = &layout.pages[0];
+        let right = PageFormat::default().width - PageFormat::default().right;
+        let ends = page
+            .primitives
+            .iter()
+            .filter_map(|p| match p {
+                Primitive::Line {
+                    from,
+                    to,
+                    thickness,
+                } if *thickness == 0.13 => Some(from.x.max(to.x)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert!(ends.len() > 5);
+        assert!(ends.iter().all(|x| *x <= right + 0.001));
+        assert_approx(&ends[..5], &[right; 5]);
+        assert!(heads(page).iter().all(|p| p.x < right));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pages() -> Result<()> {
+        assert_eq!(layout("[ c1/4 d \\newPage e ]")?.pages.len(), 2);
+
+        let layout = layout("[ \\pageFormat<20, 30, 1, 2, 1, 2> c1/4 ]")?;
+        let page = &layout.pages[0];
+        let cm = |cm| Unit::Cm.half_spaces(cm) / 2.0;
+
+        assert_approx(&[page.width, page.height], &[cm(20.0), cm(30.0)]);
+        assert!(page.primitives.iter().any(|p| matches!(p,
+            Primitive::Line { from, .. }
+                if (from.x - cm(1.0)).abs() < 0.001
+                    && (from.y - cm(2.0)).abs() < 0.001)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cursor() -> Result<()> {
+        let layout = layout("[ c1/4 d/2 e/4 \\newPage f ]")?;
+        let x = |page| {
+            heads(&layout.pages[page])
+                .iter()
+                .map(|p| p.x)
+                .collect::<Vec<_>>()
+        };
+        let (first, second) = (x(0), x(1));
+
+        let times = layout.onsets.iter().map(|o| o.time).collect::<Vec<_>>();
+        assert_approx(&times, &[0.0, 0.25, 0.75, 1.0]);
+
+        let (page, top, bottom) = layout.cursor(0.5).unwrap();
+        assert_eq!(page, 0);
+        assert_approx(&[top.x], &[(first[1] + first[2]) / 2.0]);
+        assert_approx(&[bottom.y - top.y], &[4.0]);
+
+        // Staying on the last onset of a system
+        let (page, top, _) = layout.cursor(0.9).unwrap();
+        assert_eq!(page, 0);
+        assert_approx(&[top.x], &[first[2]]);
+
+        let (page, top, _) = layout.cursor(1.0).unwrap();
+        assert_eq!(page, 1);
+        assert_approx(&[top.x], &[second[0]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn slurs_and_beams() -> Result<()> {
+        let layout = layout("[ \\slur(c1/4 d e) f/8 g a b ]")?;
+        let page = &layout.pages[0];
+        let count = |f: fn(&Primitive) -> bool| {
+            page.primitives.iter().filter(|p| f(p)).count()
+        };
+
+        assert_eq!(count(|p| matches!(p, Primitive::Curve { .. })), 1);
+        assert_eq!(count(|p| matches!(p, Primitive::Beam { .. })), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{tag::Tag, tag_id::TagId, tag_param::TagParam, unit::Unit};
+
+/// Size and margins of the pages, in staff spaces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageFormat {
+    pub width: f32,
+    pub height: f32,
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl PageFormat {
+    /// A page of the given size, with the default margins of `\pageFormat`.
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            left: spaces(2.0, Unit::Cm),
+            top: spaces(5.0, Unit::Cm),
+            right: spaces(2.0, Unit::Cm),
+            bottom: spaces(3.0, Unit::Cm),
+        }
+    }
+
+    /// A page of a standard size, like `A4` or `letter`.
+    pub fn named(name: &str) -> Option<Self> {
+        let (width, height, unit) = match name.to_lowercase().as_str() {
+            "a3" => (29.7, 42.0, Unit::Cm),
+            "a4" => (21.0, 29.7, Unit::Cm),
+            "a5" => (14.8, 21.0, Unit::Cm),
+            "letter" => (8.5, 11.0, Unit::In),
+            "legal" => (8.5, 14.0, Unit::In),
+            _ => return None,
+        };
+
+        Some(Self::new(spaces(width, unit), spaces(height, unit)))
+    }
+
+    pub fn with_margins(
+        mut self,
+        left: f32,
+        top: f32,
+        right: f32,
+        bottom: f32,
+    ) -> Self {
+        self.left = left;
+        self.top = top;
+        self.right = right;
+        self.bottom = bottom;
+        self
+    }
+
+    /// The format of a `\pageFormat` tag, either `\pageFormat<"A4", lm, tm,
+    /// rm, bm>` or `\pageFormat<w, h, lm, tm, rm, bm>`, the lengths being
+    /// positional or named and in centimeters without a unit.
+    ///
+    /// Sizes and margins not given are taken from `fallback`.
+    pub fn from_tag(tag: &Tag, fallback: &Self) -> Option<Self> {
+        if tag.id != TagId::PageFormat {
+            return None;
+        }
+
+        let named = tag.get_str("type").or_else(|| tag.as_str());
+        let (mut page, names) = match named {
+            Some(name) => (Self::named(name)?, &NAMES[2..]),
+            None => (*fallback, &NAMES[..]),
+        };
+
+        let positional = tag.params.iter().filter_map(|p| match p {
+            TagParam::Number(v) => Some(spaces(*v, Unit::Cm)),
+            TagParam::NumberUnit(v, unit) => Some(spaces(*v, *unit)),
+            _ => None,
+        });
+        let mut lengths =
+            names.iter().copied().zip(positional).collect::<Vec<_>>();
+
+        lengths.extend(tag.params.iter().filter_map(|p| match p {
+            TagParam::VarNumber(n, v) => Some((name(n)?, spaces(*v, Unit::Cm))),
+            TagParam::VarNumberUnit(n, v, unit) => {
+                Some((name(n)?, spaces(*v, *unit)))
+            },
+            _ => None,
+        }));
+
+        for (name, length) in lengths {
+            match name {
+                "w" => page.width = length,
+                "h" => page.height = length,
+                "lm" => page.left = length,
+                "tm" => page.top = length,
+                "rm" => page.right = length,
+                _ => page.bottom = length,
+            }
+        }
+
+        Some(page)
+    }
+
+    /// Width between the left and right margins.
+    pub fn content_width(&self) -> f32 {
+        self.width - self.left - self.right
+    }
+}
+
+impl Default for PageFormat {
+    fn default() -> Self {
+        Self::named("A4").unwrap()
+    }
+}
+
+/// Parameters of `\pageFormat`, in order.
+const NAMES: [&str; 6] = ["w", "h", "lm", "tm", "rm", "bm"];
+
+fn name(param: &str) -> Option<&'static str> {
+    NAMES.into_iter().find(|n| *n == param)
+}
+
+/// Converts a length to staff spaces.
+fn spaces(value: f32, unit: Unit) -> f32 {
+    unit.half_spaces(value) / 2.0
+}
@@ -4,5 +4,20 @@ pub use models::*;
 pub mod models;
 pub mod context;
 pub mod visitor;
-pub mod ptr;
+pub mod performance;
+pub mod midi;
+pub mod synth;
+pub mod wav;
+pub mod scheduler;
+pub mod query;
+pub mod preprocess;
+pub mod lyrics;
+pub mod harmony;
+pub mod layout;
+pub mod tempo;
+pub mod analysis;
+pub mod lint;
+pub mod instruments;
+#[cfg(feature = "playback")]
+pub mod output;
 
@@ -0,0 +1,764 @@
+use crate::{
+    analysis::{sounding, Sounding},
+    key::Key,
+    lint::{Diagnostic, Rule},
+    location::Location,
+    score::Score,
+};
+
+const GROUP: &str = "counterpoint";
+
+const EPSILON: f32 = 1e-4;
+
+/// The voice-leading rules, for exercises of species counterpoint.
+///
+/// They read every voice as a melody of its highest notes, from the top
+/// voice of the first staff to the bottom voice of the last one, and are
+/// only run when enabled, with the `counterpoint` group or their names.
+pub fn rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(Parallel {
+            name: "parallel-fifths",
+            interval: 7,
+        }),
+        Box::new(Parallel {
+            name: "parallel-octaves",
+            interval: 0,
+        }),
+        Box::new(Hidden {
+            name: "hidden-fifths",
+            interval: 7,
+        }),
+        Box::new(Hidden {
+            name: "hidden-octaves",
+            interval: 0,
+        }),
+        Box::new(Crossing),
+        Box::new(Overlap),
+        Box::new(Spacing),
+        Box::new(LeadingTone),
+        Box::new(Dissonance),
+    ]
+}
+
+/// Consecutive fifths or octaves between two voices moving the same way.
+struct Parallel {
+    name: &'static str,
+    /// Semitones of the interval, octaves left out.
+    interval: i32,
+}
+
+/// Fifths or octaves between the outer voices reached by similar motion,
+/// with a leap in the upper voice.
+struct Hidden {
+    name: &'static str,
+    interval: i32,
+}
+
+/// A voice going below the voice under it.
+struct Crossing;
+
+/// A voice moving past the previous note of the voice next to it.
+struct Overlap;
+
+/// More than an octave between two adjacent upper voices.
+struct Spacing;
+
+/// A leading tone of an outer voice not going to the tonic, while the bass
+/// does.
+struct LeadingTone;
+
+/// A dissonance that is neither passing, nor neighboring, nor a suspension
+/// resolving down by step.
+struct Dissonance;
+
+impl Rule for Parallel {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        if self.interval == 0 {
+            "Consecutive octaves or unisons in similar motion"
+        } else {
+            "Consecutive fifths in similar motion"
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        false
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let parts = Parts::new(score);
+        let mut diagnostics = Vec::new();
+
+        for (upper, lower) in parts.pairs() {
+            for motion in motions(upper, lower) {
+                if motion.is_similar()
+                    && motion.from.interval() % 12 == self.interval
+                    && motion.to.interval() % 12 == self.interval
+                {
+                    diagnostics.push(self.diagnostic(
+                        format!(
+                            "Parallel {} between {} and {}",
+                            interval_name(self.interval),
+                            upper.name(),
+                            lower.name()
+                        ),
+                        motion.locations(),
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+impl Rule for Hidden {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        if self.interval == 0 {
+            "Octaves between the outer voices reached by similar motion"
+        } else {
+            "Fifths between the outer voices reached by similar motion"
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        false
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let parts = Parts::new(score);
+        let Some((upper, lower)) = parts.outer() else {
+            return Vec::new();
+        };
+
+        motions(upper, lower)
+            .into_iter()
+            .filter(|m| {
+                m.is_similar()
+                    && m.upper_motion().abs() > 2
+                    && m.from.interval() % 12 != self.interval
+                    && m.to.interval() % 12 == self.interval
+            })
+            .map(|m| {
+                self.diagnostic(
+                    format!(
+                        "Hidden {} between {} and {}",
+                        interval_name(self.interval),
+                        upper.name(),
+                        lower.name()
+                    ),
+                    m.locations(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Rule for Crossing {
+    fn name(&self) -> &'static str {
+        "voice-crossing"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Voices going below the voice under them"
+    }
+
+    fn is_default(&self) -> bool {
+        false
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let parts = Parts::new(score);
+        let mut diagnostics = Vec::new();
+
+        for (upper, lower) in parts.adjacent() {
+            for pair in simultaneities(upper, lower).into_iter().flatten() {
+                if pair.upper.pitch < pair.lower.pitch {
+                    diagnostics.push(self.diagnostic(
+                        format!(
+                            "Crossing of {} below {}",
+                            upper.name(),
+                            lower.name()
+                        ),
+                        pair.locations(),
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+impl Rule for Overlap {
+    fn name(&self) -> &'static str {
+        "voice-overlap"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Voices moving past the previous note of the voice next to them"
+    }
+
+    fn is_default(&self) -> bool {
+        false
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let parts = Parts::new(score);
+        let mut diagnostics = Vec::new();
+
+        for (upper, lower) in parts.adjacent() {
+            for motion in motions(upper, lower) {
+                let (from, to) = (motion.from, motion.to);
+
+                let (moving, other, location) = if motion.upper_motion() != 0
+                    && to.upper.pitch < from.lower.pitch
+                {
+                    (upper, lower, to.upper.location)
+                } else if motion.lower_motion() != 0
+                    && to.lower.pitch > from.upper.pitch
+                {
+                    (lower, upper, to.lower.location)
+                } else {
+                    continue;
+                };
+
+                diagnostics.push(self.diagnostic(
+                    format!(
+                        "Overlap of {} past the previous note of {}",
+                        moving.name(),
+                        other.name()
+                    ),
+                    vec![location],
+                ));
+            }
+        }
+
+        diagnostics
+    }
+}
+
+impl Rule for Spacing {
+    fn name(&self) -> &'static str {
+        "spacing"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "More than an octave between adjacent upper voices"
+    }
+
+    fn is_default(&self) -> bool {
+        false
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let parts = Parts::new(score);
+        // Leaving out the bass
+        let upper_pairs = parts.lines.len().saturating_sub(2);
+        let mut diagnostics = Vec::new();
+
+        for (upper, lower) in parts.adjacent().take(upper_pairs) {
+            for pair in simultaneities(upper, lower).into_iter().flatten() {
+                if pair.interval() > 12 {
+                    diagnostics.push(self.diagnostic(
+                        format!(
+                            "More than an octave between {} and {}",
+                            upper.name(),
+                            lower.name()
+                        ),
+                        pair.locations(),
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+impl Rule for LeadingTone {
+    fn name(&self) -> &'static str {
+        "leading-tone"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Leading tones of outer voices not resolving to the tonic"
+    }
+
+    fn is_default(&self) -> bool {
+        false
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let parts = Parts::new(score);
+        let (Some(top), Some(bass)) = (parts.lines.first(), parts.lines.last())
+        else {
+            return Vec::new();
+        };
+
+        let outer = if parts.lines.len() > 1 {
+            vec![top, bass]
+        } else {
+            vec![top]
+        };
+        let mut diagnostics = Vec::new();
+
+        for line in outer {
+            for (i, tone) in line.tones.iter().enumerate() {
+                let key = parts.key(tone.start);
+                let tonic = key.tonic();
+
+                if tone.pitch % 12 != (tonic + 11) % 12 {
+                    continue;
+                }
+
+                let Some(next) = line.after(i) else {
+                    continue;
+                };
+                let cadence =
+                    bass.at(next.start).is_some_and(|n| n.pitch % 12 == tonic);
+
+                if cadence
+                    && next.pitch != tone.pitch
+                    && next.pitch != tone.pitch + 1
+                {
+                    diagnostics.push(self.diagnostic(
+                        format!(
+                            "Leading tone {} of {} does not resolve to the \
+                             tonic",
+                            key.spell(tone.pitch % 12),
+                            line.name()
+                        ),
+                        vec![tone.location, next.location],
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+impl Rule for Dissonance {
+    fn name(&self) -> &'static str {
+        "dissonance"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Dissonances that are not passing, neighboring or suspended"
+    }
+
+    fn is_default(&self) -> bool {
+        false
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let parts = Parts::new(score);
+        let bass = parts.lines.len().saturating_sub(1);
+        let mut diagnostics = Vec::new();
+
+        for (_, j, upper, lower) in parts.indexed_pairs() {
+            for (time, pair) in simultaneities_at(upper, lower) {
+                let Some(pair) = pair else {
+                    continue;
+                };
+
+                let interval = pair.interval() % 12;
+                let dissonant = matches!(interval, 1 | 2 | 6 | 10 | 11)
+                    || (interval == 5 && j == bass);
+
+                // Either note may be passing or neighboring, when it starts
+                // there, or suspended, when held over
+                let treated = [(upper, pair.upper), (lower, pair.lower)]
+                    .into_iter()
+                    .any(|(line, tone)| {
+                        let next = line.after_tone(tone);
+
+                        if tone.start < time - EPSILON {
+                            next.is_some_and(|n| {
+                                n.pitch < tone.pitch && is_step(n, tone)
+                            })
+                        } else {
+                            next.is_some_and(|n| is_step(n, tone))
+                                && line
+                                    .before_tone(tone)
+                                    .is_some_and(|p| is_step(p, tone))
+                        }
+                    });
+
+                if dissonant && !treated {
+                    diagnostics.push(self.diagnostic(
+                        format!(
+                            "Dissonant {} between {} and {} is neither \
+                             passing, neighboring nor a resolved suspension",
+                            interval_name(interval),
+                            upper.name(),
+                            lower.name()
+                        ),
+                        pair.locations(),
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Whether two notes are a second apart.
+fn is_step(a: &Sounding, b: &Sounding) -> bool {
+    (1..=2).contains(&a.pitch.abs_diff(b.pitch))
+}
+
+fn interval_name(semitones: i32) -> &'static str {
+    match semitones % 12 {
+        0 => "octaves",
+        1 => "minor second",
+        2 => "major second",
+        3 => "minor third",
+        4 => "major third",
+        5 => "fourth",
+        6 => "tritone",
+        7 => "fifths",
+        8 => "minor sixth",
+        9 => "major sixth",
+        10 => "minor seventh",
+        _ => "major seventh",
+    }
+}
+
+/// A voice as a melody.
+struct Line {
+    staff: u8,
+    voice: usize,
+    /// The highest notes starting at each onset.
+    tones: Vec<Sounding>,
+}
+
+impl Line {
+    fn name(&self) -> String {
+        format!("staff {} voice {}", self.staff, self.voice + 1)
+    }
+
+    /// The note sounding at a time.
+    fn at(&self, time: f32) -> Option<&Sounding> {
+        self.tones
+            .iter()
+            .find(|t| t.start < time + EPSILON && t.end > time + EPSILON)
+    }
+
+    /// The note following another one without a rest.
+    fn after(&self, index: usize) -> Option<&Sounding> {
+        let (tone, next) = (self.tones.get(index)?, self.tones.get(index + 1)?);
+
+        ((next.start - tone.end).abs() < EPSILON).then_some(next)
+    }
+
+    fn after_tone(&self, tone: &Sounding) -> Option<&Sounding> {
+        self.after(self.index(tone)?)
+    }
+
+    /// The note followed by another one without a rest.
+    fn before_tone(&self, tone: &Sounding) -> Option<&Sounding> {
+        let index = self.index(tone)?.checked_sub(1)?;
+
+        self.after(index).and(self.tones.get(index))
+    }
+
+    fn index(&self, tone: &Sounding) -> Option<usize> {
+        self.tones.iter().position(|t| std::ptr::eq(t, tone))
+    }
+}
+
+/// The voices of a score, from top to bottom, and its keys.
+struct Parts {
+    lines: Vec<Line>,
+    keys: Vec<(f32, Key)>,
+}
+
+impl Parts {
+    fn new(score: &Score) -> Self {
+        let (notes, keys) = sounding(score);
+        let mut lines: Vec<Line> = Vec::new();
+
+        for note in notes {
+            let line = lines
+                .iter_mut()
+                .find(|l| l.staff == note.staff && l.voice == note.voice);
+
+            match line {
+                Some(line) => line.tones.push(note),
+                None => lines.push(Line {
+                    staff: note.staff,
+                    voice: note.voice,
+                    tones: vec![note],
+                }),
+            }
+        }
+
+        for line in &mut lines {
+            line.tones.sort_by(|a, b| {
+                a.start.total_cmp(&b.start).then(b.pitch.cmp(&a.pitch))
+            });
+            line.tones
+                .dedup_by(|b, a| (a.start - b.start).abs() < EPSILON);
+        }
+
+        Self { lines, keys }
+    }
+
+    /// Key at a time, C major without a `\key`.
+    fn key(&self, time: f32) -> Key {
+        self.keys
+            .iter()
+            .rev()
+            .find(|(t, _)| *t < time + EPSILON)
+            .map_or_else(Key::default, |(_, key)| *key)
+    }
+
+    /// All pairs of voices, with their indices, the upper one first.
+    fn indexed_pairs(
+        &self,
+    ) -> impl Iterator<Item = (usize, usize, &Line, &Line)> {
+        let lines = &self.lines;
+
+        (0..lines.len()).flat_map(move |i| {
+            (i + 1..lines.len()).map(move |j| (i, j, &lines[i], &lines[j]))
+        })
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = (&Line, &Line)> {
+        self.indexed_pairs()
+            .map(|(_, _, upper, lower)| (upper, lower))
+    }
+
+    /// Pairs of voices next to each other, from the top.
+    fn adjacent(&self) -> impl Iterator<Item = (&Line, &Line)> {
+        self.lines.windows(2).map(|w| (&w[0], &w[1]))
+    }
+
+    fn outer(&self) -> Option<(&Line, &Line)> {
+        match self.lines.as_slice() {
+            [top, .., bass] => Some((top, bass)),
+            _ => None,
+        }
+    }
+}
+
+/// Notes of two voices sounding together.
+#[derive(Clone, Copy)]
+struct Pair<'a> {
+    upper: &'a Sounding,
+    lower: &'a Sounding,
+}
+
+impl Pair<'_> {
+    /// Semitones between the notes, octaves included.
+    fn interval(&self) -> i32 {
+        (i32::from(self.upper.pitch) - i32::from(self.lower.pitch)).abs()
+    }
+
+    fn locations(&self) -> Vec<Location> {
+        vec![self.upper.location, self.lower.location]
+    }
+}
+
+/// Two voices going from some notes to the next ones.
+struct Motion<'a> {
+    from: Pair<'a>,
+    to: Pair<'a>,
+}
+
+impl Motion<'_> {
+    fn upper_motion(&self) -> i32 {
+        i32::from(self.to.upper.pitch) - i32::from(self.from.upper.pitch)
+    }
+
+    fn lower_motion(&self) -> i32 {
+        i32::from(self.to.lower.pitch) - i32::from(self.from.lower.pitch)
+    }
+
+    /// Whether both voices move the same way.
+    fn is_similar(&self) -> bool {
+        let (upper, lower) = (self.upper_motion(), self.lower_motion());
+
+        upper != 0 && upper.signum() == lower.signum()
+    }
+
+    /// The notes reached, then the ones left.
+    fn locations(&self) -> Vec<Location> {
+        let mut locations = self.to.locations();
+        locations.extend(self.from.locations());
+        locations
+    }
+}
+
+/// The notes two voices sound together at each onset of either, or none
+/// when one of them rests.
+fn simultaneities_at<'a>(
+    upper: &'a Line,
+    lower: &'a Line,
+) -> Vec<(f32, Option<Pair<'a>>)> {
+    let mut times = upper
+        .tones
+        .iter()
+        .chain(&lower.tones)
+        .map(|t| t.start)
+        .collect::<Vec<_>>();
+    times.sort_by(f32::total_cmp);
+    times.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+
+    times
+        .into_iter()
+        .map(|time| {
+            let pair = upper
+                .at(time)
+                .zip(lower.at(time))
+                .map(|(upper, lower)| Pair { upper, lower });
+
+            (time, pair)
+        })
+        .collect()
+}
+
+fn simultaneities<'a>(
+    upper: &'a Line,
+    lower: &'a Line,
+) -> Vec<Option<Pair<'a>>> {
+    simultaneities_at(upper, lower)
+        .into_iter()
+        .map(|(_, pair)| pair)
+        .collect()
+}
+
+/// Consecutive simultaneities of two voices, rests left out.
+fn motions<'a>(upper: &'a Line, lower: &'a Line) -> Vec<Motion<'a>> {
+    simultaneities(upper, lower)
+        .windows(2)
+        .filter_map(|w| {
+            Some(Motion {
+                from: w[0]?,
+                to: w[1]?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::lint::{Config, Linter};
+
+    fn rules(upper: &str, lower: &str) -> Result<Vec<&'static str>> {
+        let source = format!("{{ [ {upper} ], [ \\staff<2> {lower} ] }}");
+        let score = Score::parse(&source)?;
+        let linter = Linter::new(Config::default().enable(GROUP));
+
+        let mut rules = linter
+            .lint(&score, &source)
+            .iter()
+            .map(|d| d.rule)
+            .collect::<Vec<_>>();
+        rules.dedup();
+
+        Ok(rules)
+    }
+
+    #[test]
+    fn parallels() -> Result<()> {
+        assert_eq!(rules("g1 a1", "c1 d1")?, ["parallel-fifths"]);
+        assert_eq!(rules("c1 d1", "c0 d0")?, ["parallel-octaves"]);
+        assert_eq!(rules("e1 c2", "g0 c1")?, ["hidden-octaves"]);
+        // By contrary motion
+        assert!(rules("c2 d2", "f1 g0")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn crossing_and_overlap() -> Result<()> {
+        assert_eq!(rules("c1", "e1")?, ["voice-crossing"]);
+        assert_eq!(rules("g1 c1", "e1 a0")?, ["voice-overlap"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn spacing() -> Result<()> {
+        let source = "{ [ e2 ], [ c1 ], [ \\staff<2> c0 ] }";
+        let score = Score::parse(source)?;
+        let diagnostics =
+            Linter::new(Config::default().enable(GROUP)).lint(&score, source);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "More than an octave between staff 1 voice 1 and staff 1 voice 2"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn leading_tone() -> Result<()> {
+        assert_eq!(rules("b1 a1", "g0 c0")?, ["leading-tone"]);
+        assert!(rules("b1 c2", "g0 c0")?.is_empty());
+        assert_eq!(rules("\\key<\"a\"> g#1 e1", "e0 a0")?, ["leading-tone"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dissonance() -> Result<()> {
+        // Passing and neighbor notes, and a suspension
+        assert!(rules("c1 d1 e1 f1 e1", "c0/2 c0/4 c0")?.is_empty());
+        let suspension = "\\tie(c2/2 c2/4) b1/4 c2/2";
+        assert!(rules(suspension, "c1/2 g0/2 c1")?.is_empty());
+
+        assert_eq!(rules("b1 f1 e1", "g0 g0 c1")?, ["dissonance"]);
+        assert_eq!(
+            rules("\\tie(c2/2 c2/4) d2/4", "c1/2 g0/2")?,
+            ["dissonance"]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{
+    context::Context, error::SyntaxError, location::Location,
+    preprocess::Source, score::Score, tag_definitions::TagDefinitions,
+};
+
+pub mod counterpoint;
+pub mod range;
+pub mod report;
+pub mod structure;
+pub mod style;
+
+/// Name of the files configuring the linter of the scores of a directory.
+pub const CONFIG_FILE: &str = "munote.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A problem found in a score by a rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Name of the rule.
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// The offending events, most relevant first.
+    pub locations: Vec<Location>,
+}
+
+/// A check of scores, like the ones of species counterpoint.
+pub trait Rule: Send + Sync {
+    /// Name the rule is enabled or disabled with, like `parallel-fifths`.
+    fn name(&self) -> &'static str;
+
+    /// Family of rules the rule belongs to, which can be enabled or
+    /// disabled at once.
+    fn group(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Whether the rule runs without being enabled.
+    fn is_default(&self) -> bool {
+        true
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Diagnostics of a score, parsed from `source`.
+    fn check(&self, score: &Score, source: &str) -> Vec<Diagnostic>;
+
+    /// A diagnostic of the rule.
+    fn diagnostic(
+        &self,
+        message: String,
+        locations: Vec<Location>,
+    ) -> Diagnostic {
+        Diagnostic {
+            rule: self.name(),
+            severity: self.severity(),
+            message,
+            locations,
+        }
+    }
+}
+
+/// What the linter checks, read from the `[rules]` and `[instruments]`
+/// tables of a `munote.toml`, with the tags the scores may use besides the
+/// built-in ones:
+///
+/// ```toml
+/// tags = "house-style.yaml"
+///
+/// [rules]
+/// counterpoint = true
+/// redundant-marker = true
+/// deprecated-alias = false
+///
+/// [instruments]
+/// 2 = "cello"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Which rules run, by rule or group name, the name of a rule taking
+    /// precedence over the one of its group.
+    pub rules: HashMap<String, bool>,
+    /// Instruments played on staffs, by staff number or `\instr` name,
+    /// when their `\instr` names are not enough to find them.
+    pub instruments: HashMap<String, String>,
+    /// YAML file of extra tag definitions, relative to the `munote.toml`.
+    pub tags: Option<PathBuf>,
+}
+
+/// Runs the enabled rules on scores.
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    config: Config,
+    defs: TagDefinitions,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read \"{}\": {e}", path.display()))?;
+
+        let mut config: Self = toml::from_str(&text)
+            .map_err(|e| anyhow!("Invalid \"{}\": {e}", path.display()))?;
+
+        if let (Some(tags), Some(dir)) = (&config.tags, path.parent()) {
+            config.tags = Some(dir.join(tags));
+        }
+
+        Ok(config)
+    }
+
+    /// The built-in tag definitions, with the ones of `tags`.
+    pub fn defs(&self) -> Result<TagDefinitions> {
+        match &self.tags {
+            Some(path) => TagDefinitions::load(path),
+            None => Ok(TagDefinitions::default()),
+        }
+    }
+
+    /// The `munote.toml` closest to a score, in its directory or the ones
+    /// above.
+    pub fn find(score: &Path) -> Option<PathBuf> {
+        score
+            .ancestors()
+            .skip(1)
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| path.is_file())
+    }
+
+    pub fn enable(mut self, name: &str) -> Self {
+        self.rules.insert(name.to_string(), true);
+        self
+    }
+
+    pub fn disable(mut self, name: &str) -> Self {
+        self.rules.insert(name.to_string(), false);
+        self
+    }
+
+    pub fn instrument(mut self, staff_or_name: &str, instrument: &str) -> Self {
+        self.instruments
+            .insert(staff_or_name.to_string(), instrument.to_string());
+        self
+    }
+
+    pub fn is_enabled(&self, rule: &dyn Rule) -> bool {
+        self.rules
+            .get(rule.name())
+            .or_else(|| self.rules.get(rule.group()))
+            .copied()
+            .unwrap_or_else(|| rule.is_default())
+    }
+}
+
+impl Linter {
+    /// A linter with all the rules of the crate.
+    pub fn new(config: Config) -> Self {
+        Self::with_defs(config, TagDefinitions::default())
+    }
+
+    /// A linter of scores using the tags of `defs`, usually the ones of
+    /// [`Config::defs`].
+    pub fn with_defs(config: Config, defs: TagDefinitions) -> Self {
+        let mut rules = structure::rules(&defs);
+        rules.extend(style::rules(&defs));
+        rules.extend(counterpoint::rules());
+        rules.extend(range::rules(&config));
+
+        Self {
+            rules,
+            config,
+            defs,
+        }
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|r| r.as_ref())
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Diagnostics of the enabled rules, in the order of the source.
+    pub fn lint(&self, score: &Score, source: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = self
+            .rules()
+            .filter(|r| self.config.is_enabled(*r))
+            .flat_map(|r| r.check(score, source))
+            .collect::<Vec<_>>();
+
+        diagnostics.sort_by_key(|d| d.locations.first().map(|l| l.offset));
+
+        diagnostics
+    }
+
+    /// Diagnostics of an expanded score, or the `syntax` error it cannot
+    /// be parsed because of.
+    ///
+    /// Tags of the wrong type are parsed, to be reported by the `tag-type`
+    /// rule.
+    pub fn lint_source(&self, source: &Source) -> Vec<Diagnostic> {
+        let context = Context {
+            defs: self.defs.clone(),
+            lenient: true,
+            ..Default::default()
+        };
+        let error = match Score::parse_with(&source.text, context) {
+            Ok(score) => return self.lint(&score, &source.text),
+            Err(e) => e,
+        };
+
+        let (message, locations) = match error.downcast_ref::<SyntaxError>() {
+            Some(e) if e.found.is_empty() => {
+                ("Unexpected end of score".to_string(), vec![e.location])
+            },
+            Some(e) => {
+                (format!("Unexpected \"{}\"", e.found), vec![e.location])
+            },
+            None => (error.to_string(), Vec::new()),
+        };
+
+        vec![Diagnostic {
+            rule: "syntax",
+            severity: Severity::Error,
+            message,
+            locations,
+        }]
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new(Config::default())
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = self.locations.first() {
+            write!(f, "{location}: ")?;
+        }
+
+        write!(f, "{}: {} [{}]", self.severity, self.message, self.rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn config() -> Result<()> {
+        let source = "{ [ c1 d1 ],
+                        [ \\staff<2> f0 g0 ] }";
+        let score = Score::parse(source)?;
+        let rules = |config: Config| {
+            Linter::new(config)
+                .lint(&score, source)
+                .iter()
+                .map(|d| d.rule)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(rules(Config::default()).is_empty());
+        assert_eq!(
+            rules(Config::default().enable("counterpoint")),
+            ["parallel-fifths"]
+        );
+        assert!(rules(
+            Config::default()
+                .enable("counterpoint")
+                .disable("parallel-fifths")
+        )
+        .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn custom_tags() -> Result<()> {
+        let mut defs = TagDefinitions::default();
+        defs.extend("cueText:\n  type: position\n  alternatives: [ ct ]")?;
+
+        let source = Source::expand(
+            "[ \\cueText c \\ct d \\cueText(e) ]",
+            Path::new("a.gmn"),
+        )?;
+        let messages = Linter::with_defs(Config::default(), defs)
+            .lint_source(&source)
+            .iter()
+            .map(|d| d.message.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            [
+                "\\ct is an alias of \\cueText",
+                "\\cueText is a position tag, not a range tag",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn display() {
+        let diagnostic = Diagnostic {
+            rule: "spacing",
+            severity: Severity::Warning,
+            message: "Too far".to_string(),
+            locations: vec![Location {
+                line: 2,
+                column: 5,
+                ..Default::default()
+            }],
+        };
+
+        assert_eq!(diagnostic.to_string(), "2:5: warning: Too far [spacing]");
+    }
+}
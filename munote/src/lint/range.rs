@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use crate::{
+    instruments::{pitch_name, Instrument},
+    lint::{Config, Diagnostic, Rule, Severity},
+    location::Location,
+    note::Note,
+    score::Score,
+    tag::Tag,
+    tag_id::TagId,
+    visitor::{VisitContext, Visitor},
+    voice::Voice,
+};
+
+const GROUP: &str = "range";
+
+/// The rules checking the notes of each staff against the range of its
+/// instrument, found from `\instr` tags or the instruments of the config.
+pub fn rules(config: &Config) -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(Range {
+            name: "range",
+            comfortable: false,
+            instruments: config.instruments.clone(),
+        }),
+        Box::new(Range {
+            name: "comfortable-range",
+            comfortable: true,
+            instruments: config.instruments.clone(),
+        }),
+    ]
+}
+
+/// Notes out of the range of their instrument, or only out of the range
+/// all players are comfortable with.
+struct Range {
+    name: &'static str,
+    comfortable: bool,
+    /// Instrument names, by staff number or `\instr` name.
+    instruments: HashMap<String, String>,
+}
+
+impl Range {
+    /// The instrument of a staff, or of an `\instr` name.
+    fn instrument(&self, staff: u8, name: Option<&str>) -> Option<&Instrument> {
+        let mapped = self.instruments.get(&staff.to_string()).or_else(|| {
+            let name = name?;
+
+            self.instruments
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v)
+        });
+
+        mapped
+            .map(String::as_str)
+            .or(name)
+            .and_then(Instrument::find)
+    }
+}
+
+impl Rule for Range {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        if self.comfortable {
+            "Notes some players of their instrument are not comfortable with"
+        } else {
+            "Notes out of the range of their instrument"
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        if self.comfortable {
+            Severity::Info
+        } else {
+            Severity::Error
+        }
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let mut finder = Finder {
+            names: score
+                .staffs
+                .iter()
+                .map(|s| (s.id, s.name.clone()))
+                .collect(),
+            current: None,
+            notes: Vec::new(),
+        };
+
+        score.visit(&mut finder);
+
+        let mut diagnostics = Vec::new();
+
+        for (staff, name, pitch, location) in finder.notes {
+            let Some(instrument) = self.instrument(staff, name.as_deref())
+            else {
+                continue;
+            };
+
+            let (low, high) = if self.comfortable {
+                // Out of the whole range is for the other rule
+                if !instrument.range().contains(&pitch) {
+                    continue;
+                }
+
+                instrument.comfortable
+            } else {
+                instrument.range
+            };
+
+            let side = if pitch < low {
+                "below"
+            } else if pitch > high {
+                "above"
+            } else {
+                continue;
+            };
+
+            let written = if instrument.transposition != 0 {
+                format!(", written {}", pitch_name(instrument.written(pitch)))
+            } else {
+                String::new()
+            };
+            let range = if self.comfortable {
+                "comfortable range"
+            } else {
+                "range"
+            };
+
+            diagnostics.push(self.diagnostic(
+                format!(
+                    "{}{written} is {side} the {range} of the {} ({}-{})",
+                    pitch_name(pitch.into()),
+                    instrument.name.to_lowercase(),
+                    pitch_name(low.into()),
+                    pitch_name(high.into()),
+                ),
+                vec![location],
+            ));
+        }
+
+        diagnostics
+    }
+}
+
+/// Gathers the notes of a score, with the `\instr` names they are played
+/// with.
+struct Finder {
+    /// First `\instr` names of the staffs.
+    names: HashMap<u8, Option<String>>,
+    current: Option<String>,
+    notes: Vec<(u8, Option<String>, u8, Location)>,
+}
+
+impl<'a> Visitor<'a> for Finder {
+    fn on_voice(&mut self, _voice: &'a Voice, cx: &VisitContext) {
+        self.current = self.names.get(&cx.staff).cloned().flatten();
+    }
+
+    fn on_note(&mut self, note: &'a Note, cx: &VisitContext) {
+        if note.is_empty() {
+            return;
+        }
+
+        if let Some(pitch) = note.midi_pitch() {
+            self.notes.push((
+                cx.staff,
+                self.current.clone(),
+                pitch,
+                note.location,
+            ));
+        }
+    }
+
+    fn on_tag(&mut self, tag: &'a Tag, _cx: &VisitContext) {
+        if tag.id == TagId::Instrument {
+            if let Some(name) = tag.get_str("name").or_else(|| tag.as_str()) {
+                self.current = Some(name.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::lint::Linter;
+
+    fn messages(source: &str, config: Config) -> Result<Vec<String>> {
+        let score = Score::parse(source)?;
+
+        Ok(Linter::new(config.disable("deprecated-alias"))
+            .lint(&score, source)
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect())
+    }
+
+    #[test]
+    fn instruments() -> Result<()> {
+        let score = "{ [ \\instr<\"Violin I\"> f0 g0 b3 ],
+                       [ \\staff<2> \\instr<\"Clarinet\"> d0 c0 ] }";
+
+        assert_eq!(
+            messages(score, Config::default())?,
+            [
+                "1:24: error: F3 is below the range of the violin (G3-G7) \
+                 [range]",
+                "1:30: info: B6 is above the comfortable range of the violin \
+                 (G3-A6) [comfortable-range]",
+                "2:55: info: D3, written E3 is below the comfortable range of \
+                 the clarinet in bb (E3-F6) [comfortable-range]",
+                "2:58: error: C3, written D3 is below the range of the \
+                 clarinet in bb (D3-A#6) [range]"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn mapping() -> Result<()> {
+        let score = "{ [ \\instr<\"Vl.\"> c0 ], [ \\staff<2> a-2 ] }";
+
+        assert!(messages(score, Config::default())?.is_empty());
+        assert_eq!(
+            messages(
+                score,
+                Config::default()
+                    .instrument("Vl.", "violin")
+                    .instrument("2", "cello")
+                    .disable("comfortable-range")
+            )?,
+            [
+                "1:19: error: C3 is below the range of the violin (G3-G7) \
+                 [range]",
+                "1:37: error: A1 is below the range of the cello (C2-C6) \
+                 [range]"
+            ]
+        );
+
+        Ok(())
+    }
+}
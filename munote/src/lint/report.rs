@@ -0,0 +1,249 @@
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::{bail, Error};
+use serde_json::{json, Value};
+
+use crate::{
+    lint::{Diagnostic, Linter, Severity},
+    preprocess::Source,
+};
+
+/// How the diagnostics of the linted files are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// One line per diagnostic, like `score.gmn:3:5: warning: ...`.
+    #[default]
+    Human,
+    /// An array of diagnostics, with their file, line and column.
+    Json,
+    /// A SARIF log, which code review tools annotate changes with.
+    Sarif,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            "sarif" => Ok(Self::Sarif),
+            _ => bail!("Unknown format \"{s}\" (human, json or sarif)"),
+        }
+    }
+}
+
+/// The diagnostics of the files linted, pointing to the original files
+/// instead of the expanded sources.
+#[derive(Default)]
+pub struct Report<'a> {
+    files: Vec<(&'a Source, Vec<Diagnostic>)>,
+}
+
+impl<'a> Report<'a> {
+    pub fn add(&mut self, source: &'a Source, diagnostics: Vec<Diagnostic>) {
+        self.files.push((source, diagnostics));
+    }
+
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.files.iter().flat_map(|(_, d)| d)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics().any(|d| d.severity == Severity::Error)
+    }
+
+    pub fn format(&self, format: Format, linter: &Linter) -> String {
+        match format {
+            Format::Human => self.human(),
+            Format::Json => pretty(&self.json()),
+            Format::Sarif => pretty(&self.sarif(linter)),
+        }
+    }
+
+    fn human(&self) -> String {
+        let mut out = String::new();
+
+        for (source, diagnostic) in self.iter() {
+            let origin = diagnostic
+                .locations
+                .first()
+                .map(|l| source.origin(l.offset).to_string())
+                .unwrap_or_else(|| source.path().display().to_string());
+
+            writeln!(
+                out,
+                "{origin}: {}: {} [{}]",
+                diagnostic.severity, diagnostic.message, diagnostic.rule
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    fn json(&self) -> Value {
+        self.iter()
+            .map(|(source, diagnostic)| {
+                let origin = diagnostic
+                    .locations
+                    .first()
+                    .map(|l| source.origin(l.offset));
+
+                json!({
+                    "file": origin
+                        .map_or_else(|| source.path(), |o| o.file)
+                        .display()
+                        .to_string(),
+                    "line": origin.map(|o| o.line),
+                    "column": origin.map(|o| o.column),
+                    "rule": diagnostic.rule,
+                    "severity": diagnostic.severity.to_string(),
+                    "message": diagnostic.message,
+                })
+            })
+            .collect()
+    }
+
+    /// A log of the SARIF 2.1.0 format, with the rules of the linter.
+    fn sarif(&self, linter: &Linter) -> Value {
+        let rules = linter
+            .rules()
+            .map(|rule| {
+                json!({
+                    "id": rule.name(),
+                    "shortDescription": { "text": rule.description() },
+                    "defaultConfiguration": {
+                        "level": level(rule.severity()),
+                        "enabled": linter.config().is_enabled(rule),
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let results = self
+            .iter()
+            .map(|(source, diagnostic)| {
+                let locations = diagnostic
+                    .locations
+                    .iter()
+                    .map(|l| {
+                        let origin = source.origin(l.offset);
+
+                        json!({
+                            "physicalLocation": {
+                                "artifactLocation": {
+                                    "uri": uri(&origin.file.display()),
+                                },
+                                "region": {
+                                    "startLine": origin.line,
+                                    "startColumn": origin.column,
+                                },
+                            },
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                json!({
+                    "ruleId": diagnostic.rule,
+                    "level": level(diagnostic.severity),
+                    "message": { "text": diagnostic.message },
+                    "locations": locations,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "version": "2.1.0",
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    },
+                },
+                "results": results,
+            }],
+        })
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&'a Source, &Diagnostic)> {
+        self.files
+            .iter()
+            .flat_map(|(source, d)| d.iter().map(move |d| (*source, d)))
+    }
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+/// A relative URI of a path, with forward slashes.
+fn uri(path: &impl ToString) -> String {
+    path.to_string()
+        .trim_start_matches("./")
+        .replace('\\', "/")
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn formats() -> Result<()> {
+        let source =
+            Source::expand("[ \\clef<\"x\"> c d ]", Path::new("a.gmn"))?;
+        let linter = Linter::default();
+
+        let mut report = Report::default();
+        report.add(&source, linter.lint_source(&source));
+
+        assert!(report.has_errors());
+        assert_eq!(
+            report.format(Format::Human, &linter),
+            "a.gmn:1:3: error: Unknown clef \"x\" [unknown-clef]\n"
+        );
+
+        let json = report.json();
+        assert_eq!(json[0]["file"], "a.gmn");
+        assert_eq!(json[0]["column"], 3);
+
+        let sarif = report.sarif(&linter);
+        let result = &sarif["runs"][0]["results"][0];
+        let region = &result["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(result["ruleId"], "unknown-clef");
+        assert_eq!(result["level"], "error");
+        assert_eq!(region["startLine"], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn syntax_errors() -> Result<()> {
+        let source = Source::expand("[ c d ) ]", Path::new("a.gmn"))?;
+
+        let mut report = Report::default();
+        report.add(&source, Linter::default().lint_source(&source));
+
+        assert_eq!(
+            report.human(),
+            "a.gmn:1:7: error: Unexpected \")\" [syntax]\n"
+        );
+
+        Ok(())
+    }
+}
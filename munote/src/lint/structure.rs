@@ -0,0 +1,529 @@
+use std::collections::HashMap;
+
+use crate::{
+    clef::Clef,
+    duration::fraction,
+    lint::{Diagnostic, Rule, Severity},
+    location::Location,
+    score::Score,
+    tag::{Tag, TagType},
+    tag_definitions::TagDefinitions,
+    tag_id::TagId,
+    tag_validator::TagValidator,
+    visitor::{meter_length, VisitContext, Visitor},
+    voice::Voice,
+};
+
+const GROUP: &str = "structure";
+
+const EPSILON: f32 = 1e-4;
+
+/// The rules checking that scores are well formed: measures filling their
+/// meter, voices of the same length and tags used the way they are
+/// defined.
+pub fn rules(defs: &TagDefinitions) -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(MeasureLength),
+        Box::new(VoiceLength),
+        Box::new(UnknownClef),
+        Box::new(UnbalancedTags),
+        Box::new(WrongType { defs: defs.clone() }),
+    ]
+}
+
+/// Measures ended by a barline whose duration is not the one of their
+/// `\meter`, pickups and multi-measure rests aside.
+struct MeasureLength;
+
+/// Voices of a staff ending before the longest one.
+struct VoiceLength;
+
+/// `\clef` tags with a type no renderer knows.
+struct UnknownClef;
+
+/// `Begin` tags never ended, and `End` tags never begun, in a voice.
+struct UnbalancedTags;
+
+/// Tags used as another type than the one they are defined with, like a
+/// position tag enclosing events.
+struct WrongType {
+    defs: TagDefinitions,
+}
+
+impl Rule for MeasureLength {
+    fn name(&self) -> &'static str {
+        "measure-length"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Measures whose duration does not match their meter"
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let mut measures = Measures::default();
+        score.visit(&mut measures);
+
+        measures
+            .wrong
+            .into_iter()
+            .map(|(number, length, meter, location)| {
+                self.diagnostic(
+                    format!(
+                        "Measure {number} lasts {} instead of {meter}",
+                        fraction(length)
+                    ),
+                    vec![location],
+                )
+            })
+            .collect()
+    }
+}
+
+/// Follows the barlines of each voice, keeping the measures of the wrong
+/// length.
+#[derive(Default)]
+struct Measures {
+    /// Meter and length of its measures.
+    meter: Option<(String, f32)>,
+    /// Number and onset of the current measure.
+    number: u32,
+    start: f32,
+    /// Measures of the multi-measure rest of the current measure.
+    rest: Option<u32>,
+    wrong: Vec<(u32, f32, String, Location)>,
+}
+
+impl<'a> Visitor<'a> for Measures {
+    fn on_voice(&mut self, _voice: &'a Voice, _cx: &VisitContext) {
+        *self = Self {
+            wrong: std::mem::take(&mut self.wrong),
+            number: 1,
+            ..Default::default()
+        };
+    }
+
+    fn on_tag(&mut self, tag: &'a Tag, cx: &VisitContext) {
+        if cx.in_chord() {
+            return;
+        }
+
+        match tag.id {
+            TagId::Bar
+            | TagId::DoubleBar
+            | TagId::EndBar
+            | TagId::RepeatBegin
+            | TagId::RepeatEnd => {
+                let length = cx.onset - self.start;
+
+                // Barlines following each other, or a meter
+                if length < EPSILON {
+                    return;
+                }
+
+                if let Some((meter, expected)) = &self.meter {
+                    let pickup = self.number == 1 && length < *expected;
+
+                    if !pickup
+                        && self.rest.is_none()
+                        && (length - expected).abs() > EPSILON
+                    {
+                        self.wrong.push((
+                            self.number,
+                            length,
+                            meter.clone(),
+                            tag.location,
+                        ));
+                    }
+                }
+
+                self.number += self.rest.take().unwrap_or(1);
+                self.start = cx.onset;
+            },
+            TagId::Meter => {
+                self.meter = tag.as_str().and_then(|meter| {
+                    Some((meter.to_string(), meter_length(meter)?))
+                });
+                self.start = cx.onset;
+            },
+            TagId::Mrest => self.rest = tag.rest_measures(),
+            _ => {},
+        }
+    }
+}
+
+impl Rule for VoiceLength {
+    fn name(&self) -> &'static str {
+        "voice-length"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Voices of a staff that do not last as long as the others"
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let mut ends = Ends::default();
+        score.visit(&mut ends);
+
+        let mut diagnostics = Vec::new();
+
+        for staff in &score.staffs {
+            let voices = ends
+                .voices
+                .iter()
+                .filter(|v| v.0 == staff.id)
+                .collect::<Vec<_>>();
+            let Some(longest) =
+                voices.iter().rev().max_by(|a, b| a.2.total_cmp(&b.2))
+            else {
+                continue;
+            };
+
+            for (_, voice, end, location) in &voices {
+                if longest.2 - end > EPSILON {
+                    diagnostics.push(self.diagnostic(
+                        format!(
+                            "Voice {} of staff {} lasts {}, voice {} lasts {}",
+                            voice + 1,
+                            staff.id,
+                            fraction(*end),
+                            longest.1 + 1,
+                            fraction(longest.2),
+                        ),
+                        vec![*location],
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Gathers where each voice ends.
+#[derive(Default)]
+struct Ends {
+    /// Staff, voice, onset of the end and last event of the voices.
+    voices: Vec<(u8, usize, f32, Location)>,
+}
+
+impl<'a> Visitor<'a> for Ends {
+    fn on_voice_end(&mut self, voice: &'a Voice, cx: &VisitContext) {
+        let location = voice
+            .events
+            .last()
+            .map(|e| e.location())
+            .unwrap_or_default();
+
+        self.voices.push((cx.staff, cx.voice, cx.onset, location));
+    }
+}
+
+impl Rule for UnknownClef {
+    fn name(&self) -> &'static str {
+        "unknown-clef"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Clefs of an unknown type"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let mut tags = Tags::new(|tag| tag.id == TagId::Clef);
+        score.visit(&mut tags);
+
+        tags.found
+            .into_iter()
+            .filter_map(|(tag, _)| {
+                let clef = tag.get_str("type").or_else(|| tag.as_str())?;
+
+                clef.parse::<Clef>().is_err().then(|| {
+                    self.diagnostic(
+                        format!("Unknown clef \"{clef}\""),
+                        vec![tag.location],
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl Rule for UnbalancedTags {
+    fn name(&self) -> &'static str {
+        "unbalanced-tags"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Begin tags without a matching End tag, and the other way around"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let mut tags = Tags::new(|tag| {
+            matches!(tag.ty, TagType::Begin(_) | TagType::End(_))
+        });
+        score.visit(&mut tags);
+
+        let mut diagnostics = Vec::new();
+        let mut voices = HashMap::<_, Vec<&Tag>>::new();
+
+        for (tag, voice) in &tags.found {
+            let open = voices.entry(voice).or_default();
+
+            match tag.ty {
+                TagType::Begin(_) => open.push(tag),
+                TagType::End(suffix) => {
+                    let begin = open.iter().rposition(|t| {
+                        t.id == tag.id
+                            && matches!(t.ty, TagType::Begin(s) if s == suffix)
+                    });
+
+                    match begin {
+                        Some(i) => {
+                            open.remove(i);
+                        },
+                        None => diagnostics.push(self.diagnostic(
+                            format!(
+                                "{} without {}",
+                                name(tag.id, tag.ty),
+                                name(tag.id, TagType::Begin(suffix))
+                            ),
+                            vec![tag.location],
+                        )),
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        for tag in voices.into_values().flatten() {
+            let TagType::Begin(suffix) = tag.ty else {
+                continue;
+            };
+
+            diagnostics.push(self.diagnostic(
+                format!(
+                    "{} without {}",
+                    name(tag.id, tag.ty),
+                    name(tag.id, TagType::End(suffix))
+                ),
+                vec![tag.location],
+            ));
+        }
+
+        diagnostics
+    }
+}
+
+/// How a tag of a given type is written, like `\slurBegin:2`.
+fn name(id: TagId, ty: TagType) -> String {
+    match ty {
+        TagType::Begin(0) => format!("\\{id}Begin"),
+        TagType::Begin(suffix) => format!("\\{id}Begin:{suffix}"),
+        TagType::End(0) => format!("\\{id}End"),
+        TagType::End(suffix) => format!("\\{id}End:{suffix}"),
+        _ => format!("\\{id}"),
+    }
+}
+
+impl Rule for WrongType {
+    fn name(&self) -> &'static str {
+        "tag-type"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Tags used as another type than the one they are defined with"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, score: &Score, _source: &str) -> Vec<Diagnostic> {
+        let defs = &self.defs;
+        let validator = TagValidator;
+
+        let mut tags = Tags::new(|_| true);
+        score.visit(&mut tags);
+
+        tags.found
+            .into_iter()
+            .filter(|(tag, _)| validator.validate(tag, defs).is_err())
+            .filter_map(|(tag, _)| {
+                let def = defs.get(tag.id)?;
+
+                Some(self.diagnostic(
+                    format!(
+                        "\\{} is {}, not {}",
+                        tag.id,
+                        kind(def.ty),
+                        kind(tag.ty)
+                    ),
+                    vec![tag.location],
+                ))
+            })
+            .collect()
+    }
+}
+
+fn kind(ty: TagType) -> &'static str {
+    match ty {
+        TagType::Any => "any tag",
+        TagType::Position => "a position tag",
+        TagType::Begin(_) => "a Begin tag",
+        TagType::End(_) => "an End tag",
+        TagType::Range => "a range tag",
+    }
+}
+
+/// Gathers the tags matching a predicate, with their staff and voice.
+struct Tags<'a, F> {
+    filter: F,
+    found: Vec<(&'a Tag, (u8, usize))>,
+}
+
+impl<'a, F: Fn(&Tag) -> bool> Tags<'a, F> {
+    fn new(filter: F) -> Self {
+        Self {
+            filter,
+            found: Vec::new(),
+        }
+    }
+}
+
+impl<'a, F: Fn(&Tag) -> bool> Visitor<'a> for Tags<'a, F> {
+    fn on_tag(&mut self, tag: &'a Tag, cx: &VisitContext) {
+        if (self.filter)(tag) {
+            self.found.push((tag, (cx.staff, cx.voice)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::lint::{Config, Linter};
+
+    fn messages(source: &str) -> Result<Vec<String>> {
+        let score = Score::parse_lenient(source)?;
+
+        Ok(Linter::new(Config::default().disable("style"))
+            .lint(&score, source)
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect())
+    }
+
+    #[test]
+    fn measures() -> Result<()> {
+        // A pickup, a full measure, a short one, two measures of rest and
+        // another short one
+        let score = "[ \\meter<\"3/4\"> c1 \\bar d e f \\bar g a \\bar
+                       \\mrest<2> \\bar c/2 \\bar ]";
+
+        assert_eq!(
+            messages(score)?,
+            [
+                "1:40: warning: Measure 3 lasts 1/2 instead of 3/4 \
+                 [measure-length]",
+                "2:43: warning: Measure 6 lasts 1/2 instead of 3/4 \
+                 [measure-length]"
+            ]
+        );
+        assert!(messages("[ c d e \\bar f g ]")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn voices() -> Result<()> {
+        let score = "{ [ c1 d e f ], [ c0/2 d ], [ a0 ],
+                       [ \\staff<2> c0/1 ] }";
+
+        assert_eq!(
+            messages(score)?,
+            ["1:31: warning: Voice 3 of staff 1 lasts 1/4, voice 1 lasts 1 \
+              [voice-length]"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn clefs() -> Result<()> {
+        assert!(messages(
+            "[ \\clef<\"treble\"> \\clef<\"f4\"> \\clef<\"g-8\"> \
+             \\clef<type=\"perc\"> c ]"
+        )?
+        .is_empty());
+        assert_eq!(
+            messages("[ \\clef<\"trebel\"> c ]")?,
+            ["1:3: error: Unknown clef \"trebel\" [unknown-clef]"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unbalanced_tags() -> Result<()> {
+        let score = "{ [ \\slurBegin c \\slurEnd d \\slurEnd:2 ],
+                       [ \\crescBegin c \\slurBegin:2 d ] }";
+
+        assert_eq!(
+            messages(score)?,
+            [
+                "1:29: error: \\slurEnd:2 without \\slurBegin:2 \
+                 [unbalanced-tags]",
+                "2:26: error: \\crescendoBegin without \\crescendoEnd \
+                 [unbalanced-tags]",
+                "2:40: error: \\slurBegin:2 without \\slurEnd:2 \
+                 [unbalanced-tags]"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn tag_types() -> Result<()> {
+        assert_eq!(
+            messages("[ \\bar(c d) \\slur e ]")?,
+            [
+                "1:3: error: \\bar is a position tag, not a range tag \
+                 [tag-type]",
+                "1:13: error: \\slur is a range tag, not a position tag \
+                 [tag-type]"
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    duration::Duration,
+    lint::{Diagnostic, Rule, Severity},
+    location::Location,
+    note::Note,
+    rest::Rest,
+    score::Score,
+    tag::Tag,
+    tag_definitions::TagDefinitions,
+    tag_id::TagId,
+    visitor::{VisitContext, Visitor},
+    voice::Voice,
+};
+
+const GROUP: &str = "style";
+
+/// The rules about how scores are written, rather than what they mean.
+pub fn rules(defs: &TagDefinitions) -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(RedundantMarker),
+        Box::new(DeprecatedAlias { defs: defs.clone() }),
+    ]
+}
+
+/// Octaves and durations written again while a note would inherit them
+/// from the previous one.
+struct RedundantMarker;
+
+/// Tags written with one of their `alternatives` instead of their name.
+struct DeprecatedAlias {
+    defs: TagDefinitions,
+}
+
+impl Rule for RedundantMarker {
+    fn name(&self) -> &'static str {
+        "redundant-marker"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Octaves and durations the previous note already gives"
+    }
+
+    fn is_default(&self) -> bool {
+        false
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn check(&self, score: &Score, source: &str) -> Vec<Diagnostic> {
+        let mut markers = Markers {
+            source,
+            octave: None,
+            duration: None,
+            redundant: Vec::new(),
+        };
+        score.visit(&mut markers);
+
+        markers
+            .redundant
+            .into_iter()
+            .map(|(marker, location)| {
+                self.diagnostic(
+                    format!(
+                        "Redundant {marker} in \"{}\"",
+                        location.text(source)
+                    ),
+                    vec![location],
+                )
+            })
+            .collect()
+    }
+}
+
+/// Follows the octave and duration notes inherit in each voice, like the
+/// parser does.
+struct Markers<'s> {
+    source: &'s str,
+    /// What the next note inherits, once a note gave it.
+    octave: Option<i8>,
+    duration: Option<Duration>,
+    redundant: Vec<(&'static str, Location)>,
+}
+
+impl Markers<'_> {
+    /// The octave and duration written with a note or rest, if any.
+    fn written(&self, location: Location) -> (bool, bool) {
+        lazy_static! {
+            static ref MARKERS: Regex =
+                Regex::new(r"^(?:_|[a-z]+[#&]*)(-?\d+)?([*/])?").unwrap();
+        }
+
+        MARKERS
+            .captures(location.text(self.source))
+            .map(|c| (c.get(1).is_some(), c.get(2).is_some()))
+            .unwrap_or_default()
+    }
+}
+
+impl<'a> Visitor<'a> for Markers<'_> {
+    fn on_voice(&mut self, _voice: &'a Voice, _cx: &VisitContext) {
+        self.octave = None;
+        self.duration = None;
+    }
+
+    fn on_note(&mut self, note: &'a Note, _cx: &VisitContext) {
+        let (octave, duration) = self.written(note.location);
+
+        if octave && self.octave == Some(note.octave) {
+            self.redundant.push(("octave", note.location));
+        }
+        if duration && self.duration == Some(note.duration) {
+            self.redundant.push(("duration", note.location));
+        }
+
+        self.octave = Some(note.octave);
+        self.duration = Some(note.duration);
+    }
+
+    fn on_rest(&mut self, rest: &'a Rest, _cx: &VisitContext) {
+        let (_, duration) = self.written(rest.location);
+
+        // Rests use the duration of the previous note without changing it
+        if duration && self.duration == Some(rest.duration) {
+            self.redundant.push(("duration", rest.location));
+        }
+    }
+}
+
+impl Rule for DeprecatedAlias {
+    fn name(&self) -> &'static str {
+        "deprecated-alias"
+    }
+
+    fn group(&self) -> &'static str {
+        GROUP
+    }
+
+    fn description(&self) -> &'static str {
+        "Tags written with an alternative name"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn check(&self, score: &Score, source: &str) -> Vec<Diagnostic> {
+        let mut aliases = Aliases {
+            source,
+            defs: &self.defs,
+            found: Vec::new(),
+        };
+        score.visit(&mut aliases);
+
+        aliases
+            .found
+            .into_iter()
+            .map(|(alias, id, location)| {
+                self.diagnostic(
+                    format!("\\{alias} is an alias of \\{id}"),
+                    vec![location],
+                )
+            })
+            .collect()
+    }
+}
+
+/// Gathers the tags written with an alias, with the alias.
+struct Aliases<'s> {
+    source: &'s str,
+    defs: &'s TagDefinitions,
+    found: Vec<(&'s str, TagId, Location)>,
+}
+
+impl<'a> Visitor<'a> for Aliases<'_> {
+    fn on_tag(&mut self, tag: &'a Tag, _cx: &VisitContext) {
+        let text = tag.location.text(self.source);
+        let Some(name) = text.strip_prefix('\\') else {
+            return;
+        };
+
+        let end = name
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(name.len());
+        let name = &name[..end];
+        let name = name
+            .strip_suffix("Begin")
+            .or_else(|| name.strip_suffix("End"))
+            .filter(|_| TagId::from_str(name).is_err())
+            .unwrap_or(name);
+
+        if tag.id.to_string() != name
+            && self.defs.lookup(name).ok() == Some(tag.id)
+        {
+            self.found.push((name, tag.id, tag.location));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::lint::{Config, Linter};
+
+    fn messages(source: &str) -> Result<Vec<String>> {
+        let score = Score::parse(source)?;
+
+        Ok(Linter::new(Config::default().enable(GROUP))
+            .lint(&score, source)
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect())
+    }
+
+    #[test]
+    fn redundant_markers() -> Result<()> {
+        assert_eq!(
+            messages("[ c1/8 d1 e2/8 _/8 { c, d/8 } _/4 ]")?,
+            [
+                "1:8: info: Redundant octave in \"d1\" [redundant-marker]",
+                "1:11: info: Redundant duration in \"e2/8\" \
+                 [redundant-marker]",
+                "1:16: info: Redundant duration in \"_/8\" \
+                 [redundant-marker]",
+                "1:25: info: Redundant duration in \"d/8\" [redundant-marker]",
+            ]
+        );
+        // Markers of the first note of each voice are never redundant
+        assert!(messages("{ [ c1/4 ], [ c1/4 ] }")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn aliases() -> Result<()> {
+        assert_eq!(
+            messages("[ \\slurBegin c \\slEnd \\instr<\"Piano\"> | d ]")?,
+            [
+                "1:16: info: \\sl is an alias of \\slur [deprecated-alias]",
+                "1:23: info: \\instr is an alias of \\instrument \
+                 [deprecated-alias]"
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{
+    chord::Chord,
+    note::Note,
+    score::Score,
+    tag::{Tag, TagType},
+    tag_id::TagId,
+    visitor::{VisitContext, Visitor},
+};
+
+/// Where a syllable is in its word, like in MusicXML.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syllabic {
+    /// A whole word.
+    Single,
+    /// The first syllable of a word, followed by a hyphen.
+    Begin,
+    Middle,
+    /// The last syllable of a word.
+    End,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Syllable {
+    /// The text sung, with elisions as spaces.
+    pub text: String,
+    pub syllabic: Syllabic,
+    /// Number of following notes the syllable is held over, drawn as an
+    /// extender line.
+    pub extension: usize,
+}
+
+impl Syllable {
+    /// Whether a hyphen is drawn after the syllable.
+    pub fn is_hyphenated(&self) -> bool {
+        matches!(self.syllabic, Syllabic::Begin | Syllabic::Middle)
+    }
+}
+
+/// A syllable and the note it is sung on.
+#[derive(Clone, Debug)]
+pub struct Lyric<'a> {
+    /// The note, or the first note of a chord.
+    pub note: &'a Note,
+    pub staff: u8,
+    /// Index of the voice in its staff.
+    pub voice: usize,
+    pub syllable: Syllable,
+}
+
+/// Splits the text of a `\lyrics` tag into syllables.
+///
+/// Words are separated by spaces and syllables by hyphens, while `~` joins
+/// words sung on the same note. Each `_`, or hyphen standing alone, holds the
+/// previous syllable over one more note.
+pub fn syllables(text: &str) -> Vec<Syllable> {
+    // Text, whether a hyphen follows, and extension of each syllable
+    let mut parts: Vec<(String, bool, usize)> = Vec::new();
+    let mut current = String::new();
+
+    for (i, c) in text.char_indices() {
+        let after_hyphen = text[..i].ends_with('-');
+
+        match c {
+            '-' | '_' | ' ' | '\t' | '\n' | '\r' if !current.is_empty() => {
+                parts.push((std::mem::take(&mut current), false, 0));
+            },
+            _ => {},
+        }
+
+        match c {
+            '-' => {
+                if let Some(last) = parts.last_mut() {
+                    // A lone hyphen holds the syllable before it
+                    if last.1 && !after_hyphen {
+                        last.2 += 1;
+                    }
+                    last.1 = true;
+                }
+            },
+            '_' => {
+                if let Some(last) = parts.last_mut() {
+                    last.2 += 1;
+                }
+            },
+            '~' => current.push(' '),
+            c if c.is_whitespace() => {},
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        parts.push((current, false, 0));
+    }
+
+    let mut hyphenated = false;
+
+    parts
+        .into_iter()
+        .map(|(text, hyphen, extension)| {
+            let syllabic = match (hyphenated, hyphen) {
+                (false, false) => Syllabic::Single,
+                (false, true) => Syllabic::Begin,
+                (true, true) => Syllabic::Middle,
+                (true, false) => Syllabic::End,
+            };
+            hyphenated = hyphen;
+
+            Syllable {
+                text,
+                syllabic,
+                extension,
+            }
+        })
+        .collect()
+}
+
+/// Plain text of a sequence of syllables.
+pub fn text(syllables: &[Syllable]) -> String {
+    let mut text = String::new();
+
+    for syllable in syllables {
+        text.push_str(&syllable.text);
+
+        if !syllable.is_hyphenated() {
+            text.push(' ');
+        }
+    }
+
+    text.trim_end().to_string()
+}
+
+/// The syllables of all the `\lyrics` tags of a score, each on the note it
+/// is sung on.
+///
+/// Syllables go to the notes and chords enclosed by their tag, leaving out
+/// rests, grace notes and the notes continuing a tie, and skipping the notes
+/// they are held over.
+pub fn lyrics(score: &Score) -> Vec<Lyric<'_>> {
+    let mut aligner = Aligner::default();
+
+    score.visit(&mut aligner);
+
+    aligner.lyrics
+}
+
+#[derive(Default)]
+struct Aligner<'a> {
+    lyrics: Vec<Lyric<'a>>,
+    /// Syllables of the current `\lyrics` tag, last first.
+    pending: Vec<Syllable>,
+    /// Notes left for the extension of the last syllable.
+    held: usize,
+    /// Whether each open tie already went through a note.
+    ties: Vec<bool>,
+}
+
+impl<'a> Aligner<'a> {
+    fn sing(&mut self, note: &'a Note, cx: &VisitContext) {
+        let tied = self.ties.iter().any(|started| *started);
+        self.ties.iter_mut().for_each(|started| *started = true);
+
+        if tied || cx.grace().is_some() {
+            return;
+        }
+
+        if self.held > 0 {
+            self.held -= 1;
+            return;
+        }
+
+        if let Some(syllable) = self.pending.pop() {
+            self.held = syllable.extension;
+            self.lyrics.push(Lyric {
+                note,
+                staff: cx.staff,
+                voice: cx.voice,
+                syllable,
+            });
+        }
+    }
+
+    fn start(&mut self, tag: &Tag) {
+        let text = tag.get_str("text").or_else(|| tag.as_str());
+
+        self.pending = syllables(text.unwrap_or_default());
+        self.pending.reverse();
+        self.held = 0;
+    }
+}
+
+impl<'a> Visitor<'a> for Aligner<'a> {
+    fn on_chord(&mut self, chord: &'a Chord, cx: &VisitContext) {
+        if let Some(note) = chord.notes().next() {
+            self.sing(note, cx);
+        }
+    }
+
+    fn on_note(&mut self, note: &'a Note, cx: &VisitContext) {
+        if !cx.in_chord() {
+            self.sing(note, cx);
+        }
+    }
+
+    fn on_tag(&mut self, tag: &'a Tag, _cx: &VisitContext) {
+        match (tag.id, tag.ty) {
+            (TagId::Lyrics, TagType::Range | TagType::Begin(_)) => {
+                self.start(tag)
+            },
+            (TagId::Lyrics, TagType::End(_)) => self.pending.clear(),
+            (TagId::Tie, TagType::Range | TagType::Begin(_)) => {
+                self.ties.push(false)
+            },
+            (TagId::Tie, TagType::End(_)) => {
+                self.ties.pop();
+            },
+            _ => {},
+        }
+    }
+
+    fn on_tag_end(&mut self, tag: &'a Tag, _cx: &VisitContext) {
+        match (tag.id, tag.ty) {
+            (TagId::Lyrics, TagType::Range) => self.pending.clear(),
+            (TagId::Tie, TagType::Range) => {
+                self.ties.pop();
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    fn texts(syllables: &[Syllable]) -> Vec<&str> {
+        syllables.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    #[test]
+    fn split() {
+        let syllables = syllables("Dans un som-meil  que char-mait");
+
+        assert_eq!(
+            texts(&syllables),
+            ["Dans", "un", "som", "meil", "que", "char", "mait"]
+        );
+        assert_eq!(syllables[1].syllabic, Syllabic::Single);
+        assert_eq!(syllables[2].syllabic, Syllabic::Begin);
+        assert_eq!(syllables[3].syllabic, Syllabic::End);
+        assert_eq!(text(&syllables), "Dans un sommeil que charmait");
+    }
+
+    #[test]
+    fn elisions_and_extenders() {
+        let syllables = syllables("A-ve~o Ma-ri-a_ _ fin mi-ra- -  - ge");
+
+        assert_eq!(
+            texts(&syllables),
+            ["A", "ve o", "Ma", "ri", "a", "fin", "mi", "ra", "ge"]
+        );
+        assert_eq!(syllables[3].syllabic, Syllabic::Middle);
+        assert_eq!(syllables[4].extension, 2);
+        assert_eq!(syllables[7].extension, 2);
+        assert_eq!(syllables[8].syllabic, Syllabic::End);
+        assert_eq!(text(&syllables), "Ave o Maria fin mirage");
+    }
+
+    #[test]
+    fn align() -> Result<()> {
+        let score = Score::parse(
+            "[ \\lyrics<\"Ky-ri-e_ e-lei-son\">(c d _ \\tie(e e) \
+             \\grace(f/8) g/4 a b { c, e } c) ]",
+        )?;
+        let lyrics = lyrics(&score);
+
+        let sung = lyrics
+            .iter()
+            .map(|l| (l.syllable.text.as_str(), l.note.midi_pitch().unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            sung,
+            [
+                ("Ky", 60),
+                ("ri", 62),
+                ("e", 64),
+                ("e", 69),
+                ("lei", 71),
+                ("son", 60)
+            ]
+        );
+
+        Ok(())
+    }
+}
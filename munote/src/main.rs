@@ -1,52 +1,505 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "playback")]
+use std::{thread, time::Duration};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
 use colorize::AnsiColor;
+use rayon::prelude::*;
 
-use munote::score::Score;
+#[cfg(feature = "playback")]
+use munote::{
+    output::{self, MidiOutputSink, PortSelector},
+    scheduler::Scheduler,
+};
+use munote::{
+    context::Context,
+    lint::{
+        report::{Format, Report},
+        Config, Linter,
+    },
+    lyrics::{self, Lyric},
+    midi,
+    performance::PerformanceModel,
+    preprocess::Source,
+    query::Query,
+    score::Score,
+    synth::Synth,
+    tag_definitions::{TagDefinition, TagDefinitions},
+    tag_id::TagId,
+    wav,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(required = true)]
+    path: Option<String>,
+
+    /// Reads extra tag definitions from this YAML file
+    #[arg(long, global = true)]
+    tags: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Renders a score to a file
+    #[command(group = clap::ArgGroup::new("output").required(true).multiple(true))]
+    Render {
+        path: String,
+
+        /// Writes a Standard MIDI File
+        #[arg(long, group = "output")]
+        midi: Option<PathBuf>,
+
+        /// Writes a WAV file with the built-in synthesizer
+        #[arg(long, group = "output")]
+        wav: Option<PathBuf>,
+
+        /// Quarter notes per minute
+        #[arg(long, default_value_t = 120.0)]
+        tempo: f32,
+
+        /// Plays the chord symbols as a backing track
+        #[arg(long)]
+        backing: bool,
+
+        /// Sample rate of the WAV file
+        #[arg(long, default_value_t = 44100)]
+        sample_rate: u32,
+    },
+    /// Plays a score on a MIDI output port
+    #[cfg(feature = "playback")]
+    Play {
+        path: String,
+
+        /// Output port, by index or (part of its) name
+        #[arg(long)]
+        port: Option<PortSelector>,
+
+        /// Creates a virtual output port with this name instead
+        #[arg(long = "virtual", conflicts_with = "port")]
+        virtual_port: Option<String>,
+
+        /// Quarter notes per minute
+        #[arg(long, default_value_t = 120.0)]
+        tempo: f32,
+
+        /// Plays the chord symbols as a backing track
+        #[arg(long)]
+        backing: bool,
+    },
+    /// Lists the available MIDI output ports
+    #[cfg(feature = "playback")]
+    Ports,
+    /// Prints the events matching a query, like 'note staff=2 in=\slur'
+    Query { expr: String, path: String },
+    /// Prints the lyrics of each voice
+    Lyrics { path: String },
+    /// Checks scores, or the scores of directories, for mistakes
+    Lint {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Reads the rules from this file instead of the closest
+        /// munote.toml
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Output format: human, json or sarif
+        #[arg(long, default_value = "human")]
+        format: Format,
+    },
+    /// Describes the tags, or a tag by its name or one of its aliases
+    Tags { name: Option<String> },
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let defs = match &args.tags {
+        Some(path) => Some(TagDefinitions::load(path)?),
+        None => None,
+    };
+    let tags = defs.clone().unwrap_or_default();
+
+    match args.command {
+        Some(Command::Render {
+            path,
+            midi,
+            wav,
+            tempo,
+            backing,
+            sample_rate,
+        }) => {
+            let synth = Synth {
+                sample_rate,
+                ..Default::default()
+            };
+            let model = PerformanceModel {
+                tempo,
+                backing,
+                ..Default::default()
+            };
+
+            render(Path::new(&path), &tags, midi, wav, &model, &synth)
+        },
+        #[cfg(feature = "playback")]
+        Some(Command::Play {
+            path,
+            port,
+            virtual_port,
+            tempo,
+            backing,
+        }) => {
+            let port = virtual_port.map(PortSelector::Virtual).or(port);
+            let model = PerformanceModel {
+                tempo,
+                backing,
+                ..Default::default()
+            };
 
-    let path = Path::new(&args.path);
+            play(Path::new(&path), &tags, port.as_ref(), &model)
+        },
+        #[cfg(feature = "playback")]
+        Some(Command::Ports) => {
+            for (i, name) in output::ports()?.iter().enumerate() {
+                println!("{i}: {name}");
+            }
+
+            Ok(())
+        },
+        Some(Command::Query { expr, path }) => {
+            query(&expr, Path::new(&path), &tags)
+        },
+        Some(Command::Lyrics { path }) => {
+            print_lyrics(Path::new(&path), &tags)
+        },
+        Some(Command::Lint {
+            paths,
+            config,
+            format,
+        }) => lint(&paths, config.as_deref(), defs, format),
+        Some(Command::Tags { name }) => print_tags(name.as_deref(), &tags),
+        None => parse(Path::new(&args.path.unwrap_or_default()), &tags),
+    }
+}
 
+fn parse(path: &Path, defs: &TagDefinitions) -> Result<()> {
     if path.is_dir() {
-        let dir = fs::read_dir(path)?;
-        let count = dir.count();
+        let files = fs::read_dir(path)?
+            .map(|file| Ok(file?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        let count = files.len();
         println!("Found {} files", count);
 
-        let dir = fs::read_dir(path)?;
-        for (i, file) in dir.into_iter().enumerate() {
-            let i = i + 1;
-            parse_score(&format!("{i}/{count}"), &file.unwrap().path())?;
+        // Printed in order once all the files are parsed
+        let outputs = files
+            .par_iter()
+            .enumerate()
+            .map(|(i, file)| {
+                let mut out = String::new();
+                let index = format!("{}/{count}", i + 1);
+                let score = parse_score_to(&mut out, &index, file, defs);
+
+                (out, score)
+            })
+            .collect::<Vec<_>>();
+
+        for (out, score) in outputs {
+            print!("{out}");
+            score?;
         }
     } else {
-        parse_score("", path)?;
+        parse_score("", path, defs)?;
     }
 
     Ok(())
 }
 
-fn parse_score(index: &str, path: &Path) -> Result<Score> {
-    let display = path.display();
+fn render(
+    path: &Path,
+    defs: &TagDefinitions,
+    midi: Option<PathBuf>,
+    wav: Option<PathBuf>,
+    model: &PerformanceModel,
+    synth: &Synth,
+) -> Result<()> {
+    let score = parse_score("", path, defs)?;
+
+    let performance = model.perform(&score);
+
+    if let Some(out) = midi {
+        midi::save_smf(&performance, &out)?;
+        print_written("MIDI", &out);
+    }
+
+    if let Some(out) = wav {
+        let samples = synth.render(&performance);
+        wav::save_wav(&samples, synth.sample_rate, &out)?;
+        print_written("WAV", &out);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "playback")]
+fn play(
+    path: &Path,
+    defs: &TagDefinitions,
+    port: Option<&PortSelector>,
+    model: &PerformanceModel,
+) -> Result<()> {
+    let score = parse_score("", path, defs)?;
+
+    let performance = model.perform(&score);
+
+    // Still give something to listen to when there is nowhere to play,
+    // unless the user asked for a port
+    if port.is_none() && output::ports()?.is_empty() {
+        let out = unused_path(&path.with_extension("mid"));
+        midi::save_smf(&performance, &out)?;
+
+        println!("{}", "No MIDI output port available".red());
+        print_written("MIDI", &out);
+
+        return Ok(());
+    }
+
+    let sink = MidiOutputSink::connect(port)?;
+
+    let scheduler = Scheduler::new(&performance, sink);
+    scheduler.play();
+
+    while scheduler.is_playing() {
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    Ok(())
+}
+
+/// The path, or the first one with a number after the file name that does
+/// not exist yet, like `score-2.mid`.
+#[cfg(feature = "playback")]
+fn unused_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+
+    (1..)
+        .map(|i| match i {
+            1 => path.to_path_buf(),
+            i => path.with_file_name(format!("{stem}-{i}.{extension}")),
+        })
+        .find(|path| !path.exists())
+        .expect("Ran out of file names")
+}
+
+fn query(expr: &str, path: &Path, defs: &TagDefinitions) -> Result<()> {
+    let query = Query::parse(expr, defs)?;
+    let source = Source::load(path)?;
+    let score = source.parse_with(context(defs))?;
+
+    for m in query.select(&score) {
+        println!(
+            "{}: staff {}, voice {}, measure {}: {}",
+            source.origin(m.location.offset),
+            m.staff,
+            m.voice + 1,
+            m.measure,
+            m.location.text(&source.text),
+        );
+    }
+
+    Ok(())
+}
+
+fn print_lyrics(path: &Path, defs: &TagDefinitions) -> Result<()> {
+    let score = Source::load(path)?.parse_with(context(defs))?;
+    let lyrics = lyrics::lyrics(&score);
+
+    let same_voice =
+        |a: &Lyric, b: &Lyric| (a.staff, a.voice) == (b.staff, b.voice);
+
+    for voice in lyrics.chunk_by(same_voice) {
+        let syllables = voice
+            .iter()
+            .map(|l| l.syllable.clone())
+            .collect::<Vec<_>>();
+
+        println!(
+            "Staff {}, voice {}: {}",
+            voice[0].staff,
+            voice[0].voice + 1,
+            lyrics::text(&syllables)
+        );
+    }
+
+    Ok(())
+}
+
+/// Lints scores with the tags of `defs`, or else the ones of the config.
+fn lint(
+    paths: &[PathBuf],
+    config: Option<&Path>,
+    defs: Option<TagDefinitions>,
+    format: Format,
+) -> Result<()> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut scores = fs::read_dir(path)?
+                .map(|file| Ok(file?.path()))
+                .filter(|file| {
+                    file.as_ref().map_or(true, |f| {
+                        f.extension().is_some_and(|e| e == "gmn")
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            scores.sort();
+            files.extend(scores);
+        } else {
+            files.push(path.clone());
+        }
+    }
 
-    println!("Parsing \"{}\" ({})... \n", display, index);
+    let config = match config
+        .map(Path::to_path_buf)
+        .or_else(|| files.first().and_then(|f| Config::find(f)))
+    {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
+    let defs = match defs {
+        Some(defs) => defs,
+        None => config.defs()?,
+    };
+    let linter = Linter::with_defs(config, defs);
 
-    let content = fs::read_to_string(path)?;
-    let score = Score::parse(&content)?;
+    let sources = files
+        .iter()
+        .map(|file| Source::load(file))
+        .collect::<Result<Vec<_>>>()?;
+    let diagnostics = sources
+        .par_iter()
+        .map(|source| linter.lint_source(source))
+        .collect::<Vec<_>>();
 
+    let mut report = Report::default();
+    for (source, diagnostics) in sources.iter().zip(diagnostics) {
+        report.add(source, diagnostics);
+    }
+
+    print!("{}", report.format(format, &linter));
+
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn print_written(kind: &str, path: &Path) {
     println!(
         "{}",
-        format!("Score \"{display}\" parsed successfully!\n").green()
+        format!("{kind} file \"{}\" written successfully!\n", path.display())
+            .green()
     );
+}
+
+fn parse_score(
+    index: &str,
+    path: &Path,
+    defs: &TagDefinitions,
+) -> Result<Score> {
+    let mut out = String::new();
+    let score = parse_score_to(&mut out, index, path, defs);
+    print!("{out}");
+
+    score
+}
+
+/// Parses a score, writing its progress to `out`.
+fn parse_score_to(
+    out: &mut String,
+    index: &str,
+    path: &Path,
+    defs: &TagDefinitions,
+) -> Result<Score> {
+    let display = path.display();
+
+    out.push_str(&format!("Parsing \"{}\" ({})... \n\n", display, index));
+
+    let score = Source::load(path)?.parse_with(context(defs))?;
+
+    out.push_str(&format!(
+        "{}\n",
+        format!("Score \"{display}\" parsed successfully!\n").green()
+    ));
     // println!("{}", format!("{score:?}").b_black());
 
     Ok(score)
 }
+
+/// A parsing context with the tags of `defs`.
+fn context(defs: &TagDefinitions) -> Context {
+    Context {
+        defs: defs.clone(),
+        ..Default::default()
+    }
+}
+
+fn print_tags(name: Option<&str>, defs: &TagDefinitions) -> Result<()> {
+    let Some(name) = name else {
+        let descriptions = defs
+            .iter()
+            .map(|(id, def)| describe(id, def))
+            .collect::<Vec<_>>();
+        println!("{}", descriptions.join("\n\n"));
+
+        return Ok(());
+    };
+
+    let (id, def) = defs
+        .find(name)
+        .ok_or_else(|| anyhow!("Unknown tag {name}"))?;
+    println!("{}", describe(id, def));
+
+    Ok(())
+}
+
+fn describe(id: TagId, def: &TagDefinition) -> String {
+    let mut lines = vec![
+        format!("\\{id} ({} tag)", def.ty).bold(),
+        format!("  {}", def.description),
+    ];
+
+    if !def.alternatives.is_empty() {
+        let aliases = def
+            .alternatives
+            .iter()
+            .map(|a| format!("\\{a}"))
+            .collect::<Vec<_>>();
+        lines.push(format!("  Aliases: {}", aliases.join(", ")));
+    }
+
+    for param in &def.params {
+        let mut line = format!("  <{}>: {}", param.name, param.ty);
+        if param.optional {
+            line.push_str(", optional");
+        }
+        if let Some(default) = &param.default {
+            line.push_str(&format!(", default {default}"));
+        }
+
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
@@ -0,0 +1,208 @@
+use std::{fs::File, io::Write, path::Path};
+
+use anyhow::Result;
+
+use crate::{instruments::Instrument, performance::Performance};
+
+/// Ticks per quarter note of the exported files.
+const DIVISION: u16 = 480;
+/// Microseconds per quarter note, i.e. 120 BPM.
+const TEMPO: u32 = 500_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        pitch: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        pitch: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+}
+
+impl MidiMessage {
+    pub fn is_note_on(&self) -> bool {
+        matches!(self, Self::NoteOn { .. })
+    }
+
+    /// Messages at the same instant are sent in this order: programs are
+    /// set up before any note, and note offs come before note ons so that
+    /// repeated pitches are retriggered.
+    pub fn priority(&self) -> u8 {
+        match self {
+            Self::ProgramChange { .. } => 0,
+            Self::NoteOff { .. } => 1,
+            Self::NoteOn { .. } => 2,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        const NOTE_ON_MSG: u8 = 0x90;
+        const NOTE_OFF_MSG: u8 = 0x80;
+        const PROGRAM_CHANGE_MSG: u8 = 0xc0;
+
+        match *self {
+            Self::NoteOn {
+                channel,
+                pitch,
+                velocity,
+            } => vec![NOTE_ON_MSG | (channel & 0x0f), pitch, velocity],
+            Self::NoteOff { channel, pitch } => {
+                vec![NOTE_OFF_MSG | (channel & 0x0f), pitch, 0x40]
+            },
+            Self::ProgramChange { channel, program } => {
+                vec![PROGRAM_CHANGE_MSG | (channel & 0x0f), program]
+            },
+        }
+    }
+}
+
+/// General MIDI program for an instrument name, as found in `\instr`.
+pub fn program_for(instrument: &str) -> Option<u8> {
+    Instrument::find(instrument).map(|i| i.program)
+}
+
+/// Writes a performance as a type 0 Standard MIDI File. Times are already
+/// resolved to seconds, so the file uses a fixed tempo.
+pub fn write_smf(
+    performance: &Performance,
+    out: &mut impl Write,
+) -> Result<()> {
+    let ticks_per_second = DIVISION as f32 * 1_000_000.0 / TEMPO as f32;
+
+    let mut track = Vec::new();
+
+    // Tempo meta event
+    write_var_len(&mut track, 0);
+    track.extend([0xff, 0x51, 0x03]);
+    track.extend(&TEMPO.to_be_bytes()[1..]);
+
+    let mut last = 0;
+    for (time, message) in performance.messages() {
+        let tick = (time * ticks_per_second).round() as u32;
+
+        write_var_len(&mut track, tick - last);
+        track.extend(message.to_bytes());
+
+        last = tick;
+    }
+
+    // End of track
+    write_var_len(&mut track, 0);
+    track.extend([0xff, 0x2f, 0x00]);
+
+    out.write_all(b"MThd")?;
+    out.write_all(&6u32.to_be_bytes())?;
+    out.write_all(&0u16.to_be_bytes())?;
+    out.write_all(&1u16.to_be_bytes())?;
+    out.write_all(&DIVISION.to_be_bytes())?;
+
+    out.write_all(b"MTrk")?;
+    out.write_all(&(track.len() as u32).to_be_bytes())?;
+    out.write_all(&track)?;
+
+    Ok(())
+}
+
+pub fn save_smf(performance: &Performance, path: &Path) -> Result<()> {
+    let mut file = File::create(path)?;
+
+    write_smf(performance, &mut file)
+}
+
+fn write_var_len(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+
+    while value > 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+
+    out.extend(bytes.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{performance::PerformanceModel, score::Score};
+
+    #[test]
+    fn message_bytes() {
+        let on = MidiMessage::NoteOn {
+            channel: 1,
+            pitch: 60,
+            velocity: 100,
+        };
+        let off = MidiMessage::NoteOff {
+            channel: 1,
+            pitch: 60,
+        };
+
+        let program = MidiMessage::ProgramChange {
+            channel: 2,
+            program: 40,
+        };
+
+        assert_eq!(on.to_bytes(), [0x91, 60, 100]);
+        assert_eq!(off.to_bytes(), [0x81, 60, 0x40]);
+        assert_eq!(program.to_bytes(), [0xc2, 40]);
+    }
+
+    #[test]
+    fn programs() {
+        assert_eq!(program_for("Violin I"), Some(40));
+        assert_eq!(program_for("Violoncello"), Some(42));
+        assert_eq!(program_for("Contrabass"), Some(43));
+        assert_eq!(program_for("Alto Sax"), Some(65));
+        assert_eq!(program_for("Alto Flute"), Some(73));
+        assert_eq!(program_for("Baritone Saxophone"), Some(67));
+        assert_eq!(program_for("Soprano"), Some(52));
+        assert_eq!(program_for("Pizz."), None);
+    }
+
+    #[test]
+    fn var_len() {
+        let encode = |value| {
+            let mut out = Vec::new();
+            write_var_len(&mut out, value);
+            out
+        };
+
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(0x7f), vec![0x7f]);
+        assert_eq!(encode(0x80), vec![0x81, 0x00]);
+        assert_eq!(encode(0x3fff), vec![0xff, 0x7f]);
+    }
+
+    #[test]
+    fn smf() -> Result<()> {
+        let score = Score::parse("[ a1/4 ]")?;
+        let performance = PerformanceModel::default().perform(&score);
+
+        let mut out = Vec::new();
+        write_smf(&performance, &mut out)?;
+
+        assert_eq!(&out[0..4], b"MThd");
+        assert_eq!(&out[14..18], b"MTrk");
+        assert_eq!(
+            &out[22..],
+            &[
+                0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20, // tempo
+                0x00, 0x90, 69, 100, // note on
+                0x83, 0x30, 0x80, 69, 0x40, // note off after 432 ticks
+                0x00, 0xff, 0x2f, 0x00, // end of track
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -19,6 +19,16 @@ pub enum Accidentals {
 }
 
 impl Accidentals {
+    pub fn semitones(&self) -> i32 {
+        match self {
+            Self::Natural => 0,
+            Self::Sharp => 1,
+            Self::Flat => -1,
+            Self::DoubleSharp => 2,
+            Self::DoubleFlat => -2,
+        }
+    }
+
     pub fn parse(input: Span) -> IResult<Span, Self> {
         let (input, accs) = opt(is_a("&#"))(input)?;
 
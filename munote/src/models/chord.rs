@@ -1,30 +1,61 @@
 use nom::IResult;
 
 use crate::{
-    context::ContextPtr,
+    context::Context,
     duration::Duration,
     event::Event,
+    location::Location,
+    note::Note,
 };
 use crate::event::parse_delimited_events;
 use crate::models::Span;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Chord {
     pub symbols: Vec<Box<dyn Event>>,
     pub duration: Duration,
+    pub location: Location,
+}
+
+impl PartialEq for Chord {
+    fn eq(&self, other: &Self) -> bool {
+        self.symbols == other.symbols && self.duration == other.duration
+    }
 }
 
 impl Chord {
     pub fn new(symbols: Vec<Box<dyn Event>>, duration: Duration) -> Self {
-        Self { symbols, duration }
+        Self {
+            symbols,
+            duration,
+            location: Location::default(),
+        }
     }
 
-    pub fn parse(input: Span, context: ContextPtr) -> IResult<Span, Self> {
-        let (input, symbols) = parse_delimited_events(input, context.clone(), '{', '}')?;
+    pub fn notes(&self) -> impl Iterator<Item = &Note> {
+        self.symbols
+            .iter()
+            .filter_map(|s| s.as_any().downcast_ref::<Note>())
+    }
 
-        let ctx = context.borrow();
+    pub fn full_duration(&self) -> Duration {
+        self.notes()
+            .map(Note::full_duration)
+            .max()
+            .unwrap_or(self.duration)
+    }
 
-        Ok((input, Chord::new(symbols, ctx.duration)))
+    /// Parses a chord, whose notes carry octave and duration over like in a
+    /// sequence. Tags inside it, like a cross-staff `\staff`, only apply to
+    /// the chord.
+    pub fn parse<'a>(input: Span<'a>, context: &mut Context) -> IResult<Span<'a>, Self> {
+        let tags = context.tags.clone();
+
+        let (input, symbols) = parse_delimited_events(input, context, '{', '}')?;
+
+        context.tags = tags;
+
+        Ok((input, Chord::new(symbols, context.duration)))
     }
 }
 
@@ -33,7 +64,7 @@ mod tests {
     use anyhow::{anyhow, Result};
 
     use crate::{
-        note::{Diatonic, Note},
+        note::Diatonic,
         tag::Tag,
         tag_id::TagId,
         tag_param::TagParam,
@@ -42,10 +73,10 @@ mod tests {
     use super::*;
 
     fn parse_chord(input: &str) -> Result<Chord> {
-        let context = ContextPtr::default();
+        let mut context = Context::default();
 
         let (input, parsed) =
-            Chord::parse(Span::new(input), context).map_err(|e| anyhow!("{}", e))?;
+            Chord::parse(Span::new(input), &mut context).map_err(|e| anyhow!("{}", e))?;
 
         assert_eq!(*input.fragment(), "");
 
@@ -88,6 +119,16 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn full_duration() -> Result<()> {
+        let chord = parse_chord("{ a1/4., c2/8 }")?;
+
+        assert_eq!(chord.full_duration(), Duration::new(3, 8));
+        assert_eq!(chord.notes().count(), 2);
+
+        Ok(())
+    }
+
     #[test]
     fn same_duration() -> Result<()> {
         let chord = parse_chord("{ a1*2, b1 }")?;
@@ -137,6 +178,19 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn restore_tags() -> Result<()> {
+        let mut context = Context::default();
+
+        Chord::parse(Span::new("{ c, \\staff<2> g }"), &mut context)
+            .map_err(|e| anyhow!("{}", e))?;
+
+        assert!(context.get_tag(TagId::Staff).is_none());
+        assert_eq!(context.duration, Duration::new(1, 4));
+
+        Ok(())
+    }
+
     fn assert_same_symbols(
         lhs: Vec<Box<dyn Event>>,
         rhs: Vec<Box<dyn Event>>,
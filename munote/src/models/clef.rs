@@ -0,0 +1,233 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+
+use crate::{note::Note, tag::Tag, tag_id::TagId};
+
+/// A clef, from the type of a `\clef` tag: `\clef<"g">`, `\clef<"f4">`,
+/// `\clef<"c3">`, `\clef<"g-8">`, `\clef<"perc">` or `\clef<"none">`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clef {
+    pub sign: ClefSign,
+    /// Staff line the clef sits on, from 1 at the bottom.
+    pub line: u8,
+    /// Octaves the notes sound above their written pitch, like -1 for the
+    /// `g-8` clef of tenors.
+    pub octave: i8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClefSign {
+    G,
+    F,
+    C,
+    Percussion,
+    Tab,
+    None,
+}
+
+impl Clef {
+    pub fn new(sign: ClefSign, line: u8) -> Self {
+        Self {
+            sign,
+            line,
+            octave: 0,
+        }
+    }
+
+    pub fn with_octave(mut self, octave: i8) -> Self {
+        self.octave = octave;
+        self
+    }
+
+    /// The clef of a `\clef` tag, a treble clef without a type.
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        if tag.id != TagId::Clef {
+            return None;
+        }
+
+        match tag.get_str("type").or_else(|| tag.as_str()) {
+            Some(clef) => clef.parse().ok(),
+            None => Some(Self::default()),
+        }
+    }
+
+    /// Where a note is written on the staff, in steps above the bottom line:
+    /// 0 for the bottom line, 1 for the space above it, 8 for the top line.
+    ///
+    /// `octava` are the octaves the notes sound above their written pitch,
+    /// from the enclosing `\octava`. Percussion, tablature and hidden clefs
+    /// place the notes like a treble clef.
+    pub fn staff_position(&self, note: &Note, octava: i8) -> i32 {
+        // Diatonic pitch of the note on the line of the clef
+        let (pitch, line) = match self.sign {
+            ClefSign::G => (-1, self.line),
+            ClefSign::F => (-9, self.line),
+            ClefSign::C => (-5, self.line),
+            _ => (-1, 2),
+        };
+        let octaves = i32::from(self.octave) + i32::from(octava);
+
+        note.diatonic_pitch() - pitch - 7 * octaves + 2 * (i32::from(line) - 1)
+    }
+
+    fn default_line(sign: ClefSign) -> u8 {
+        match sign {
+            ClefSign::G => 2,
+            ClefSign::F => 4,
+            _ => 3,
+        }
+    }
+}
+
+impl Default for Clef {
+    fn default() -> Self {
+        Self::new(ClefSign::G, 2)
+    }
+}
+
+impl FromStr for Clef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid clef \"{s}\"");
+        let name = s.trim().to_lowercase();
+
+        let (name, octave) = match name.find(['+', '-']) {
+            Some(i) => {
+                let octave = match &name[i + 1..] {
+                    "8" => 1,
+                    "15" => 2,
+                    _ => return Err(invalid()),
+                };
+                let sign = if name[i..].starts_with('-') { -1 } else { 1 };
+
+                (&name[..i], sign * octave)
+            },
+            None => (name.as_str(), 0),
+        };
+
+        let clef = match name {
+            "treble" => Self::new(ClefSign::G, 2),
+            "french" => Self::new(ClefSign::G, 1),
+            "bass" | "basso" => Self::new(ClefSign::F, 4),
+            "varbaritone" => Self::new(ClefSign::F, 3),
+            "subbass" => Self::new(ClefSign::F, 5),
+            "soprano" => Self::new(ClefSign::C, 1),
+            "mezzosoprano" => Self::new(ClefSign::C, 2),
+            "alto" => Self::new(ClefSign::C, 3),
+            "tenor" => Self::new(ClefSign::C, 4),
+            "baritone" => Self::new(ClefSign::C, 5),
+            "gg" => Self::default().with_octave(-1),
+            "perc" => Self::new(ClefSign::Percussion, 3),
+            "tab" => Self::new(ClefSign::Tab, 3),
+            "none" => Self::new(ClefSign::None, 3),
+            _ => {
+                // A sign, optionally followed by its line
+                let sign = match name.chars().next() {
+                    Some('g') => ClefSign::G,
+                    Some('f') => ClefSign::F,
+                    Some('c') => ClefSign::C,
+                    _ => return Err(invalid()),
+                };
+                let line = match &name[1..] {
+                    "" => Self::default_line(sign),
+                    line => line
+                        .parse()
+                        .ok()
+                        .filter(|l| (1..=5).contains(l))
+                        .ok_or_else(invalid)?,
+                };
+
+                Self::new(sign, line)
+            },
+        };
+
+        Ok(clef.with_octave(clef.octave + octave))
+    }
+}
+
+impl fmt::Display for Clef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sign {
+            ClefSign::G => write!(f, "g{}", self.line)?,
+            ClefSign::F => write!(f, "f{}", self.line)?,
+            ClefSign::C => write!(f, "c{}", self.line)?,
+            ClefSign::Percussion => write!(f, "perc")?,
+            ClefSign::Tab => write!(f, "tab")?,
+            ClefSign::None => write!(f, "none")?,
+        }
+
+        match self.octave {
+            0 => Ok(()),
+            1 => write!(f, "+8"),
+            -1 => write!(f, "-8"),
+            octave if octave > 0 => write!(f, "+15"),
+            _ => write!(f, "-15"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{note::Diatonic, tag_param::TagParam};
+
+    #[test]
+    fn parse() -> Result<()> {
+        assert_eq!("g".parse::<Clef>()?, Clef::default());
+        assert_eq!("treble".parse::<Clef>()?, Clef::new(ClefSign::G, 2));
+        assert_eq!("f4".parse::<Clef>()?, Clef::new(ClefSign::F, 4));
+        assert_eq!("c3".parse::<Clef>()?, "alto".parse()?);
+        assert_eq!(
+            "g-8".parse::<Clef>()?,
+            Clef::new(ClefSign::G, 2).with_octave(-1)
+        );
+        assert_eq!("gg".parse::<Clef>()?, "g2-8".parse()?);
+        assert_eq!("bass+15".parse::<Clef>()?.to_string(), "f4+15");
+        assert_eq!("perc".parse::<Clef>()?.sign, ClefSign::Percussion);
+        assert_eq!("none".parse::<Clef>()?.sign, ClefSign::None);
+        assert!("g6".parse::<Clef>().is_err());
+        assert!("trebel".parse::<Clef>().is_err());
+        assert!("f-7".parse::<Clef>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_tag() {
+        let bass = Tag::from_id(TagId::Clef)
+            .with_param(TagParam::VarString("type".into(), "f".into()));
+
+        assert_eq!(Clef::from_tag(&bass), Some(Clef::new(ClefSign::F, 4)));
+        assert_eq!(
+            Clef::from_tag(&Tag::from_id(TagId::Clef)),
+            Some(Clef::default())
+        );
+        assert_eq!(Clef::from_tag(&Tag::from_id(TagId::Key)), None);
+    }
+
+    #[test]
+    fn staff_positions() -> Result<()> {
+        let position = |clef: &str, name, octave, octava| -> Result<i32> {
+            let note = Note::from_name(name).with_octave(octave);
+
+            Ok(clef.parse::<Clef>()?.staff_position(&note, octava))
+        };
+
+        // E4 on the bottom line and F5 on the top one
+        assert_eq!(position("g", Diatonic::E, 1, 0)?, 0);
+        assert_eq!(position("g", Diatonic::F, 2, 0)?, 8);
+        // G2 and A3
+        assert_eq!(position("f", Diatonic::G, -1, 0)?, 0);
+        assert_eq!(position("bass", Diatonic::A, 0, 0)?, 8);
+        // Middle C on the middle line, and on the top one
+        assert_eq!(position("c3", Diatonic::C, 1, 0)?, 4);
+        assert_eq!(position("c5", Diatonic::C, 1, 0)?, 8);
+        assert_eq!(position("g-8", Diatonic::C, 0, 0)?, -2);
+        assert_eq!(position("g", Diatonic::C, 3, 1)?, 5);
+        assert_eq!(position("perc", Diatonic::B, 1, 0)?, 4);
+
+        Ok(())
+    }
+}
@@ -15,6 +15,20 @@ pub fn all_comments(input: &str) -> Result<String> {
     inline_comments(&multiline_comments(input)?)
 }
 
+/// Replaces the comments with spaces, keeping the remaining text where it
+/// was.
+pub fn blank_comments(input: &str) -> Result<String> {
+    let re = Regex::new(r"(?s:\(\*.*?\*\))|%[^\r\n]*")?;
+
+    Ok(re
+        .replace_all(input, |c: &regex::Captures| {
+            c[0].chars()
+                .map(|c| if c == '\n' { c } else { ' ' })
+                .collect::<String>()
+        })
+        .to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
@@ -35,6 +49,15 @@ b % remove this
         Ok(())
     }
 
+    #[test]
+    fn blank() -> Result<()> {
+        let res = blank_comments("a (* b\nc *) d % e\nf")?;
+
+        assert_eq!(&res, "a     \n     d    \nf");
+
+        Ok(())
+    }
+
     #[test]
     fn remove_multiline_comments() -> Result<()> {
         let res = multiline_comments(
@@ -16,9 +16,10 @@ pub struct Duration {
     pub denom: u8,
 }
 
+/// A quarter, like GUIDO assumes until a duration is given.
 impl Default for Duration {
     fn default() -> Self {
-        Self { num: 1, denom: 1 }
+        Self { num: 1, denom: 4 }
     }
 }
 
@@ -97,6 +98,25 @@ impl Duration {
     }
 }
 
+/// A time in whole notes as a fraction, like `5/8`.
+pub fn fraction(time: f32) -> String {
+    const EPSILON: f32 = 1e-4;
+
+    let denom = (1..=192)
+        .find(|d| {
+            let num = time * *d as f32;
+            (num - num.round()).abs() < EPSILON * *d as f32
+        })
+        .unwrap_or(192);
+    let num = (time * denom as f32).round() as i32;
+
+    if denom == 1 {
+        num.to_string()
+    } else {
+        format!("{num}/{denom}")
+    }
+}
+
 fn duration_num(input: Span) -> IResult<Span, u8> {
     let (input, _) = ch('*')(input)?;
     u8(input)
@@ -158,6 +178,14 @@ mod tests {
         assert_duration(1 + Duration::new(1, 1), Duration::new(2, 1), 2.0);
     }
 
+    #[test]
+    fn fractions() {
+        assert_eq!(fraction(1.0), "1");
+        assert_eq!(fraction(0.625), "5/8");
+        assert_eq!(fraction(1.0 / 3.0), "1/3");
+        assert_eq!(fraction(0.0), "0");
+    }
+
     fn assert_duration(actual: Duration, e1: Duration, e2: f32) {
         assert_eq!(actual, e1);
         assert!((actual.as_f32() - e2).abs() < 0.001);
@@ -1,5 +1,9 @@
+use std::fmt;
+
 use nom::error::{ErrorKind, ParseError};
 
+use crate::{location::Location, models::Span};
+
 #[derive(Debug)]
 pub enum NoteError<I> {
     TagId,
@@ -15,3 +19,41 @@ impl<I> ParseError<I> for NoteError<I> {
         other
     }
 }
+
+/// Where the parsing of a score stopped.
+#[derive(Debug)]
+pub struct SyntaxError {
+    pub location: Location,
+    /// The start of the text that could not be parsed.
+    pub found: String,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(input: &Span) -> Self {
+        let found = input
+            .fragment()
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .take(20)
+            .collect();
+
+        Self {
+            location: Location::between(input, input),
+            found,
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.found.is_empty() {
+            write!(f, "{}: unexpected end of score", self.location)
+        } else {
+            write!(f, "{}: unexpected \"{}\"", self.location, self.found)
+        }
+    }
+}
+
+impl std::error::Error for SyntaxError {}
@@ -11,37 +11,50 @@ use nom::sequence::delimited;
 
 use crate::{
     chord::Chord,
-    context::ContextPtr,
+    context::Context,
+    location::Location,
     models::ws,
     note::Note,
     rest::Rest,
     tag::Tag,
 };
 use crate::models::Span;
-use crate::visitor::VisitorPtr;
+use crate::visitor::{Visitable, VisitContext, Visitor, VisitorMut};
 
-pub trait Event {
+pub trait Event: Send + Sync {
     fn as_any(&self) -> &dyn Any;
 
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
     fn type_name(&self) -> &'static str;
 
     fn as_debug(&self) -> &dyn fmt::Debug;
 
+    fn location(&self) -> Location;
+
+    fn set_location(&mut self, location: Location);
+
     fn equals(&self, _: &dyn Event) -> bool;
 
     fn clone_box(&self) -> Box<dyn Event>;
 
-    fn visit(&self, visitor: VisitorPtr);
+    fn visit<'a>(&'a self, visitor: &mut dyn Visitor<'a>, cx: &mut VisitContext);
+
+    fn visit_mut(&mut self, visitor: &mut dyn VisitorMut, cx: &mut VisitContext);
 }
 
 #[macro_export]
 macro_rules! impl_event_for {
-    ($t:ty, $m:ident) => {
+    ($t:ty) => {
         impl Event for $t {
             fn as_any(&self) -> &dyn Any {
                 self
             }
 
+            fn as_any_mut(&mut self) -> &mut dyn Any {
+                self
+            }
+
             fn type_name(&self) -> &'static str {
                 stringify!($t)
             }
@@ -50,6 +63,14 @@ macro_rules! impl_event_for {
                 self
             }
 
+            fn location(&self) -> Location {
+                self.location
+            }
+
+            fn set_location(&mut self, location: Location) {
+                self.location = location;
+            }
+
             fn equals(&self, other: &dyn Event) -> bool {
                 other
                     .as_any()
@@ -61,17 +82,29 @@ macro_rules! impl_event_for {
                 Box::new((*self).clone())
             }
 
-            fn visit(&self, mut visitor: VisitorPtr) {
-                visitor.borrow_mut().$m(self);
+            fn visit<'a>(
+                &'a self,
+                visitor: &mut dyn Visitor<'a>,
+                cx: &mut VisitContext,
+            ) {
+                self.accept(visitor, cx);
+            }
+
+            fn visit_mut(
+                &mut self,
+                visitor: &mut dyn VisitorMut,
+                cx: &mut VisitContext,
+            ) {
+                self.accept_mut(visitor, cx);
             }
         }
     };
 }
 
-impl_event_for!(Chord, on_chord);
-impl_event_for!(Note, on_note);
-impl_event_for!(Rest, on_rest);
-impl_event_for!(Tag, on_tag);
+impl_event_for!(Chord);
+impl_event_for!(Note);
+impl_event_for!(Rest);
+impl_event_for!(Tag);
 
 impl Clone for Box<dyn Event> {
     fn clone(&self) -> Self {
@@ -91,16 +124,16 @@ impl<'a, 'b> PartialEq<dyn Event + 'b> for dyn Event + 'a {
     }
 }
 
-pub fn parse_delimited_events(
-    input: Span,
-    context: ContextPtr,
+pub fn parse_delimited_events<'a>(
+    input: Span<'a>,
+    context: &mut Context,
     start_delimiter: char,
     end_delimiter: char,
 )
-    -> IResult<Span, Vec<Box<dyn Event>>> {
+    -> IResult<Span<'a>, Vec<Box<dyn Event>>> {
     let (input, events) = delimited(
         terminated(char(start_delimiter), ws),
-        |i| parse_events(i, context.clone()),
+        |i| parse_events(i, context),
         terminated(char(end_delimiter), ws))(input)?;
 
     // println!("Parsed delimited events: \"{events:?}\"");
@@ -109,16 +142,16 @@ pub fn parse_delimited_events(
     Ok((input, events))
 }
 
-fn parse_events(
-    input: Span,
-    context: ContextPtr,
-) -> IResult<Span, Vec<Box<dyn Event>>> {
+fn parse_events<'a>(
+    input: Span<'a>,
+    context: &mut Context,
+) -> IResult<Span<'a>, Vec<Box<dyn Event>>> {
     // println!("Checking symbols: \"{input}\"");
-    let (input, first) = parse_event(input, context.clone())?;
+    let (input, first) = parse_event(input, context)?;
 
     let (input, mut events) = many0(preceded(
         terminated(opt(char(',')), ws),
-        preceded(ws, |i| parse_event(i, context.clone())),
+        preceded(ws, |i| parse_event(i, context)),
     ))(input)?;
 
     events.insert(0, first);
@@ -128,14 +161,16 @@ fn parse_events(
     Ok((input, events))
 }
 
-fn parse_event(
-    input: Span,
-    context: ContextPtr,
-) -> IResult<Span, Box<dyn Event>> {
+fn parse_event<'a>(
+    input: Span<'a>,
+    context: &mut Context,
+) -> IResult<Span<'a>, Box<dyn Event>> {
     // println!("Checking symbol: \"{input}\"");
     let (_, next) = peek(one_of("abcdefghilmrst{_|\\"))(input)?;
 
-    let (input, symbol) = match next {
+    let start = input;
+
+    let (input, mut symbol) = match next {
         '\\' | '|' => {
             let (input, tag) = Tag::parse(input, context)?;
             let b: Box<dyn Event> = Box::new(tag);
@@ -162,5 +197,7 @@ fn parse_event(
     // Skip remaining whitespaces
     let (input, _) = ws(input)?;
 
+    symbol.set_location(Location::between(&start, &input));
+
     Ok((input, symbol))
 }
@@ -0,0 +1,54 @@
+use std::str::FromStr;
+
+use parse_display::FromStr;
+
+use crate::{tag::Tag, tag_id::TagId};
+
+/// How the notes of a `\grace` range are played, from its `style`
+/// parameter.
+///
+/// GUIDO itself has no way to tell an acciaccatura from an appoggiatura, so
+/// the parameter is a munote extension that other GUIDO tools ignore, and
+/// grace notes without it are acciaccaturas.
+///
+/// Grace notes take no time in the score: they steal it from the note that
+/// follows them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromStr)]
+#[display(style = "lowercase")]
+pub enum Grace {
+    /// Crushed in, as quickly as possible, like a slashed grace note.
+    #[default]
+    Acciaccatura,
+    /// Leaning on the next note, for half of its duration.
+    Appoggiatura,
+}
+
+impl Grace {
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        if tag.id != TagId::Grace {
+            return None;
+        }
+
+        let style = tag.get_str("style").or_else(|| tag.as_str());
+
+        Some(style.and_then(|s| Self::from_str(s).ok()).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag_param::TagParam;
+
+    #[test]
+    fn from_tag() {
+        let grace = Tag::from_id(TagId::Grace);
+        let leaning = Tag::from_id(TagId::Grace).with_param(
+            TagParam::VarString("style".into(), "appoggiatura".into()),
+        );
+
+        assert_eq!(Grace::from_tag(&grace), Some(Grace::Acciaccatura));
+        assert_eq!(Grace::from_tag(&leaning), Some(Grace::Appoggiatura));
+        assert_eq!(Grace::from_tag(&Tag::from_id(TagId::Cue)), None);
+    }
+}
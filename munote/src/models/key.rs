@@ -0,0 +1,152 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{harmony::Spelling, tag::Tag, tag_id::TagId};
+
+/// A key, from the signature of a `\key` tag: `\key<-2>` for B flat major,
+/// or `\key<"D">` and `\key<"c#">` for D major and C sharp minor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Key {
+    /// Sharps in the signature, or flats when negative.
+    pub fifths: i8,
+    pub minor: bool,
+}
+
+impl Key {
+    pub fn major(fifths: i8) -> Self {
+        Self {
+            fifths,
+            minor: false,
+        }
+    }
+
+    pub fn minor(fifths: i8) -> Self {
+        Self {
+            fifths,
+            minor: true,
+        }
+    }
+
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        if tag.id != TagId::Key {
+            return None;
+        }
+
+        if let Some(fifths) = tag.get_number("key").or_else(|| tag.as_number())
+        {
+            return Some(Self::major(fifths as i8));
+        }
+
+        tag.get_str("key")
+            .or_else(|| tag.as_str())
+            .and_then(|s| s.parse().ok())
+    }
+
+    /// Pitch class of the tonic.
+    pub fn tonic(&self) -> u8 {
+        let tonic = i32::from(self.fifths) * 7 + if self.minor { 9 } else { 0 };
+
+        tonic.rem_euclid(12) as u8
+    }
+
+    /// Semitones of the degrees of the scale above the tonic, with the
+    /// natural minor scale for minor keys.
+    pub fn scale(&self) -> [u8; 7] {
+        if self.minor {
+            [0, 2, 3, 5, 7, 8, 10]
+        } else {
+            [0, 2, 4, 5, 7, 9, 11]
+        }
+    }
+
+    /// Name of a pitch class, with the accidentals of the signature.
+    pub fn spell(&self, pitch_class: u8) -> Spelling {
+        Spelling::from_pitch_class(pitch_class, self.fifths < 0)
+    }
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        if let Ok(fifths) = s.parse::<i8>() {
+            return Ok(Self::major(fifths));
+        }
+
+        let minor = s.starts_with(|c: char| c.is_ascii_lowercase());
+        // Flats are also written `&` in GUIDO
+        let name = match s.char_indices().nth(1) {
+            Some((i, _)) => {
+                s[..i].to_ascii_uppercase() + &s[i..].replace('&', "b")
+            },
+            None => s.to_ascii_uppercase(),
+        };
+        let (tonic, rest) = Spelling::parse(&name)
+            .ok_or_else(|| anyhow!("Invalid key \"{s}\""))?;
+
+        if !rest.is_empty() {
+            bail!("Invalid key \"{s}\"");
+        }
+
+        let fifths = match tonic.step {
+            'F' => -1,
+            'C' => 0,
+            'G' => 1,
+            'D' => 2,
+            'A' => 3,
+            'E' => 4,
+            _ => 5,
+        } + 7 * tonic.alter
+            - if minor { 3 } else { 0 };
+
+        Ok(Self { fifths, minor })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = if self.minor { "minor" } else { "major" };
+
+        write!(f, "{} {mode}", self.spell(self.tonic()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag_param::TagParam;
+
+    #[test]
+    fn parse() -> Result<()> {
+        assert_eq!("D".parse::<Key>()?, Key::major(2));
+        assert_eq!("c#".parse::<Key>()?, Key::minor(4));
+        assert_eq!("E&".parse::<Key>()?, Key::major(-3));
+        assert_eq!("bb".parse::<Key>()?, Key::minor(-5));
+        assert_eq!("-2".parse::<Key>()?, Key::major(-2));
+        assert!("H".parse::<Key>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_tag() {
+        let flats = Tag::from_id(TagId::Key).with_param(TagParam::Number(-3.0));
+        let minor = Tag::from_id(TagId::Key)
+            .with_param(TagParam::String("g".to_string()));
+
+        assert_eq!(Key::from_tag(&flats), Some(Key::major(-3)));
+        assert_eq!(Key::from_tag(&minor), Some(Key::minor(-2)));
+        assert_eq!(Key::from_tag(&Tag::from_id(TagId::Meter)), None);
+    }
+
+    #[test]
+    fn tonic() {
+        assert_eq!(Key::major(-2).tonic(), 10);
+        assert_eq!(Key::minor(-2).tonic(), 7);
+        assert_eq!(Key::minor(3).to_string(), "F# minor");
+        assert_eq!(Key::major(-6).to_string(), "Gb major");
+    }
+}
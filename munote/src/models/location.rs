@@ -0,0 +1,79 @@
+use std::fmt;
+
+use crate::models::Span;
+
+/// Where an event was written in the source.
+///
+/// Events leave their locations out of their equality, so that parsed events
+/// can be compared with the ones built in code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    /// Byte offset from the start of the input.
+    pub offset: usize,
+    pub line: u32,
+    pub column: usize,
+    /// Length in bytes, without the trailing whitespaces.
+    pub length: usize,
+}
+
+impl Location {
+    /// Location of what was consumed from `start` to reach `end`.
+    pub fn between(start: &Span, end: &Span) -> Self {
+        let length = end.location_offset() - start.location_offset();
+        let consumed = &start.fragment()[..length];
+
+        Self {
+            offset: start.location_offset(),
+            line: start.location_line(),
+            column: start.get_utf8_column(),
+            length: consumed.trim_end().len(),
+        }
+    }
+
+    /// The text of the event in the source it was parsed from.
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        source
+            .get(self.offset..self.offset + self.length)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::bytes::complete::tag;
+
+    use super::*;
+    use crate::note::{Diatonic, Note};
+
+    #[test]
+    fn between() {
+        let input = Span::new("[\n  c/4  d ]");
+        let (start, _) = tag::<_, _, ()>("[\n  ")(input).unwrap();
+        let (end, _) = tag::<_, _, ()>("c/4  ")(start).unwrap();
+
+        let location = Location::between(&start, &end);
+
+        assert_eq!(location.to_string(), "2:3");
+        assert_eq!(location.text("[\n  c/4  d ]"), "c/4");
+    }
+
+    #[test]
+    fn equality() {
+        let mut note = Note::from_name(Diatonic::C);
+        note.location = Location {
+            offset: 2,
+            line: 1,
+            column: 3,
+            length: 3,
+        };
+
+        assert_ne!(note.location, Location::default());
+        assert_eq!(note, Note::from_name(Diatonic::C));
+    }
+}
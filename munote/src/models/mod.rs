@@ -26,8 +26,12 @@ pub mod tag_validator;
 pub mod tag_definitions;
 pub mod symbols;
 pub mod display_event;
+pub mod location;
+pub mod grace;
+pub mod key;
+pub mod clef;
 
-type Span<'a> = LocatedSpan<&'a str>;
+pub(crate) type Span<'a> = LocatedSpan<&'a str>;
 
 fn string(input: Span) -> IResult<Span, Span> {
     recognize(preceded(alpha1, alphanumeric0))(input)
@@ -11,20 +11,32 @@ use parse_display::FromStr;
 
 use crate::{
     accidentals::Accidentals,
-    context::ContextPtr,
+    context::Context,
     dots::Dots,
     duration::Duration,
+    location::Location,
     models::ws,
 };
 use crate::models::Span;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Note {
     pub name: NoteName,
     pub octave: i8,
     pub accidentals: Accidentals,
     pub duration: Duration,
     pub dots: Dots,
+    pub location: Location,
+}
+
+impl PartialEq for Note {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.octave == other.octave
+            && self.accidentals == other.accidentals
+            && self.duration == other.duration
+            && self.dots == other.dots
+    }
 }
 
 impl Note {
@@ -41,6 +53,7 @@ impl Note {
             octave,
             duration,
             dots,
+            location: Location::default(),
         }
     }
 
@@ -77,6 +90,22 @@ impl Note {
         self.name.chromatic_pitch() + 12 * (self.octave - 1) as i32
     }
 
+    /// Whether this is an `empty` event, which takes its time without being
+    /// drawn nor played.
+    pub fn is_empty(&self) -> bool {
+        self.name == NoteName::Empty
+    }
+
+    pub fn midi_pitch(&self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let pitch = self.chromatic_pitch() + self.accidentals.semitones() + 69;
+
+        u8::try_from(pitch).ok().filter(|p| *p < 128)
+    }
+
     pub fn has_stem(&self) -> bool {
         self.duration != Duration::new(1, 1)
     }
@@ -101,7 +130,7 @@ impl Note {
         self.duration * (1 + self.dots.duration())
     }
 
-    pub fn parse(input: Span, mut context: ContextPtr) -> IResult<Span, Self> {
+    pub fn parse<'a>(input: Span<'a>, context: &mut Context) -> IResult<Span<'a>, Self> {
         let (input, name) = alt((
             map(tag("empty"), |_| NoteName::Empty),
             map(Chromatic::parse, |c| NoteName::from(c)),
@@ -117,7 +146,6 @@ impl Note {
         // Eat remaining whitespaces
         let (input, _) = ws(input)?;
 
-        let mut context = context.borrow_mut();
         let octave = maybe_octave.unwrap_or(context.octave);
         let duration = maybe_duration.unwrap_or(context.duration);
 
@@ -321,19 +349,15 @@ pub enum StemDirection {
 mod tests {
     use anyhow::{anyhow, Result};
 
-    use crate::{
-        accidentals::Accidentals,
-        context::Context,
-    };
-    use crate::ptr::Ptr;
+    use crate::accidentals::Accidentals;
 
     use super::*;
 
     fn parse_note(input: &str) -> Result<Note> {
-        let context = ContextPtr::default();
+        let mut context = Context::default();
 
         let (input, parsed) =
-            Note::parse(Span::new(input), context).map_err(|e| anyhow!("{}", e))?;
+            Note::parse(Span::new(input), &mut context).map_err(|e| anyhow!("{}", e))?;
 
         assert_eq!(*input.fragment(), "");
 
@@ -393,7 +417,8 @@ mod tests {
 
     #[test]
     fn stem_and_beams() -> Result<()> {
-        assert_stem_beams(&parse_note("c")?, false, 0);
+        assert_stem_beams(&parse_note("c")?, true, 0);
+        assert_stem_beams(&parse_note("c/1")?, false, 0);
         assert_stem_beams(&parse_note("c/2")?, true, 0);
         assert_stem_beams(&parse_note("c/4")?, true, 0);
         assert_stem_beams(&parse_note("c/8")?, true, 1);
@@ -405,10 +430,11 @@ mod tests {
 
     #[test]
     fn parse_dots() -> Result<()> {
-        assert_dots(&parse_note("c")?, Dots::None, Duration::new(1, 1), 1.0);
-        assert_dots(&parse_note("c.")?, Dots::Single, Duration::new(3, 2), 1.5);
-        assert_dots(&parse_note("c..")?, Dots::Double, Duration::new(7, 4), 1.75);
-        assert_dots(&parse_note("c...")?, Dots::Triple, Duration::new(15, 8), 1.875);
+        assert_dots(&parse_note("c")?, Dots::None, Duration::new(1, 4), 0.25);
+        assert_dots(&parse_note("c/1.")?, Dots::Single, Duration::new(3, 2), 1.5);
+        assert_dots(&parse_note("c/1..")?, Dots::Double, Duration::new(7, 4), 1.75);
+        assert_dots(&parse_note("c/1...")?, Dots::Triple, Duration::new(15, 8), 1.875);
+        assert_dots(&parse_note("c.")?, Dots::Single, Duration::new(3, 8), 0.375);
         assert_dots(&parse_note("c/8.")?, Dots::Single, Duration::new(3, 16), 0.1875);
 
         Ok(())
@@ -416,12 +442,12 @@ mod tests {
 
     #[test]
     fn same_octave() -> Result<()> {
-        let context = Ptr::new(Context {
+        let mut context = Context {
             octave: 2,
             ..Default::default()
-        });
+        };
 
-        let (_, note) = Note::parse("c".into(), context)?;
+        let (_, note) = Note::parse("c".into(), &mut context)?;
         assert_note(&note, Diatonic::C, 2);
 
         Ok(())
@@ -430,12 +456,12 @@ mod tests {
     #[test]
     fn same_duration() -> Result<()> {
         let duration = Duration::new(2, 1);
-        let context = Ptr::new(Context {
+        let mut context = Context {
             duration,
             ..Default::default()
-        });
+        };
 
-        let (_, note) = Note::parse(Span::new("c"), context)?;
+        let (_, note) = Note::parse(Span::new("c"), &mut context)?;
 
         assert_eq!(note.duration, duration);
         Ok(())
@@ -457,6 +483,21 @@ mod tests {
         assert_eq!(Note::from_name(Diatonic::C).with_octave(0).chromatic_pitch(), -12 - 9);
     }
 
+    #[test]
+    fn midi_pitch() {
+        assert_eq!(Note::from_name(Diatonic::A).midi_pitch(), Some(69));
+        assert_eq!(Note::from_name(Diatonic::C).midi_pitch(), Some(60));
+        assert_eq!(
+            Note::from_name(Diatonic::C)
+                .with_accidentals(Accidentals::Sharp)
+                .midi_pitch(),
+            Some(61)
+        );
+        assert_eq!(Note::from_name(Chromatic::Cis).with_octave(2).midi_pitch(), Some(73));
+        assert_eq!(Note::from_name(NoteName::Empty).midi_pitch(), None);
+        assert_eq!(Note::from_name(Diatonic::C).with_octave(-6).midi_pitch(), None);
+    }
+
     fn assert_note(note: &Note, name: impl Into<NoteName>, octave: i8) {
         assert_eq!(note.name, name.into());
         assert_eq!(note.octave, octave);
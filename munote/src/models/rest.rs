@@ -1,17 +1,25 @@
 use nom::{bytes::complete::tag, combinator::opt, IResult};
 
 use crate::{
-    context::ContextPtr,
+    context::Context,
     dots::Dots,
     duration::Duration,
+    location::Location,
     models::ws,
 };
 use crate::models::Span;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Rest {
     pub duration: Duration,
     pub dots: Dots,
+    pub location: Location,
+}
+
+impl PartialEq for Rest {
+    fn eq(&self, other: &Self) -> bool {
+        self.duration == other.duration && self.dots == other.dots
+    }
 }
 
 impl Default for Rest {
@@ -19,20 +27,25 @@ impl Default for Rest {
         Self {
             duration: Duration::default(),
             dots: Dots::default(),
+            location: Location::default(),
         }
     }
 }
 
 impl Rest {
     pub fn new(duration: Duration, dots: Dots) -> Self {
-        Self { duration, dots }
+        Self {
+            duration,
+            dots,
+            location: Location::default(),
+        }
     }
 
     pub fn full_duration(&self) -> Duration {
         self.duration * (1 + self.dots.duration())
     }
 
-    pub fn parse(input: Span, context: ContextPtr) -> IResult<Span, Self> {
+    pub fn parse<'a>(input: Span<'a>, context: &mut Context) -> IResult<Span<'a>, Self> {
         let (input, _) = tag("_")(input)?;
         let (input, maybe_duration) = opt(Duration::parse)(input)?;
         let (input, dots) = Dots::parse(input)?;
@@ -43,7 +56,7 @@ impl Rest {
         Ok((
             input,
             Rest::new(
-                maybe_duration.unwrap_or(context.borrow().duration),
+                maybe_duration.unwrap_or(context.duration),
                 dots,
             ),
         ))
@@ -54,16 +67,14 @@ impl Rest {
 mod tests {
     use anyhow::{anyhow, Result};
 
-    use crate::context::{Context};
-    use crate::ptr::Ptr;
 
     use super::*;
 
     fn parse_rest(input: &str) -> Result<Rest> {
-        let context = ContextPtr::default();
+        let mut context = Context::default();
 
         let (input, parsed) =
-            Rest::parse(Span::new(input), context).map_err(|e| anyhow!("{}", e))?;
+            Rest::parse(Span::new(input), &mut context).map_err(|e| anyhow!("{}", e))?;
 
         assert_eq!(*input.fragment(), "");
 
@@ -73,7 +84,7 @@ mod tests {
     #[test]
     fn parse() -> Result<()> {
         let rest = parse_rest("_")?;
-        assert_eq!(rest.duration, Duration::new(1, 1));
+        assert_eq!(rest.duration, Duration::new(1, 4));
         assert_eq!(rest.dots, Dots::None);
 
         Ok(())
@@ -98,12 +109,12 @@ mod tests {
     #[test]
     fn same_duration() -> Result<()> {
         let duration = Duration::new(2, 1);
-        let context = Ptr::new(Context {
+        let mut context = Context {
             duration,
             ..Default::default()
-        });
+        };
 
-        let (_, rest) = Rest::parse(Span::new("_"), context)?;
+        let (_, rest) = Rest::parse(Span::new("_"), &mut context)?;
 
         assert_eq!(rest.duration, duration);
         Ok(())
@@ -1,6 +1,4 @@
-use std::collections::HashMap;
-
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use nom::{
     character::complete::{char, one_of},
     combinator::peek,
@@ -10,81 +8,256 @@ use nom::{
 };
 
 use crate::{
-    comment::all_comments,
-    context::ContextPtr,
+    chord::Chord,
+    comment::blank_comments,
+    context::Context,
+    error::SyntaxError,
     models::ws,
+    note::Note,
+    rest::Rest,
+    tag::Tag,
+    tag_id::TagId,
     voice::Voice,
 };
 use crate::models::Span;
-use crate::visitor::VisitorPtr;
+use crate::visitor::{fold_events, Fold, VisitContext, Visitor, VisitorMut};
 
-#[derive(Debug)]
+/// The staffs of a score, kept in display order.
+///
+/// Parsing sorts them by their `\staff` number, while the voices of each
+/// staff keep the order they were written in.
+#[derive(Debug, Default)]
 pub struct Score {
-    pub staffs: HashMap<u8, Staff>,
+    pub staffs: Vec<Staff>,
 }
 
 #[derive(Debug, Default)]
 pub struct Staff {
+    /// Number given by `\staff`.
+    pub id: u8,
+    /// Instrument name, from `\instr`.
+    pub name: Option<String>,
+    /// Clef, key and meter the staff starts with.
+    pub clef: Option<String>,
+    pub key: Option<String>,
+    pub meter: Option<String>,
+    /// Style given by `\staffFormat`, e.g. "1-line".
+    pub style: Option<String>,
+    /// Number of lines, when the style gives one.
+    pub lines: Option<u8>,
     pub voices: Vec<Voice>,
 }
 
 impl Staff {
+    pub fn new(id: u8) -> Self {
+        Self {
+            id,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a voice, completing the metadata still missing from its tags.
     pub fn add_voice(&mut self, voice: Voice) {
+        let mut started = false;
+
+        for event in &voice.events {
+            let any = event.as_any();
+
+            if any.is::<Note>() || any.is::<Chord>() || any.is::<Rest>() {
+                started = true;
+            }
+
+            if let Some(tag) = any.downcast_ref::<Tag>() {
+                self.read_tag(tag, started);
+            }
+        }
+
         self.voices.push(voice);
     }
+
+    fn read_tag(&mut self, tag: &Tag, started: bool) {
+        let value = || {
+            tag.as_str()
+                .map(str::to_string)
+                .or_else(|| tag.as_number().map(|n| n.to_string()))
+        };
+
+        match tag.id {
+            TagId::Instrument if self.name.is_none() => self.name = value(),
+            TagId::Clef if !started && self.clef.is_none() => {
+                self.clef = value()
+            },
+            TagId::Key if !started && self.key.is_none() => {
+                self.key = value()
+            },
+            TagId::Meter if !started && self.meter.is_none() => {
+                self.meter = value()
+            },
+            TagId::StaffFormat if !started && self.style.is_none() => {
+                let style = tag.get_str("style").or_else(|| tag.as_str());
+
+                self.style = style.map(str::to_string);
+                self.lines = style.and_then(lines);
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Number of lines of a staff style, like "5-lines" or "1-line".
+fn lines(style: &str) -> Option<u8> {
+    style.split('-').next()?.parse().ok()
 }
 
 impl Score {
     pub fn new(voices: Vec<Voice>) -> Self {
-        let mut staffs = HashMap::new();
+        let mut staffs: Vec<Staff> = Vec::new();
 
         for voice in voices {
             let id = voice.staff;
 
-            if !staffs.contains_key(&id) {
-                staffs.insert(id, Staff::default());
-            }
-
-            let staff = staffs.get_mut(&id).unwrap();
+            let i = match staffs.binary_search_by_key(&id, |s| s.id) {
+                Ok(i) => i,
+                Err(i) => {
+                    staffs.insert(i, Staff::new(id));
+                    i
+                },
+            };
 
-            staff.add_voice(voice);
+            staffs[i].add_voice(voice);
         }
 
         Self { staffs }
     }
 
-    pub fn visit(&self, mut visitor: VisitorPtr) {
-        for (_, staff) in &self.staffs {
-            visitor.borrow_mut().on_staff_begin();
+    pub fn staff(&self, id: u8) -> Option<&Staff> {
+        self.staffs.iter().find(|s| s.id == id)
+    }
+
+    pub fn staff_mut(&mut self, id: u8) -> Option<&mut Staff> {
+        self.staffs.iter_mut().find(|s| s.id == id)
+    }
+
+    /// Appends a staff after the existing ones.
+    pub fn add_staff(&mut self, staff: Staff) -> Result<()> {
+        if self.staff(staff.id).is_some() {
+            bail!("Staff {} already exists", staff.id);
+        }
+
+        self.staffs.push(staff);
+
+        Ok(())
+    }
+
+    pub fn remove_staff(&mut self, id: u8) -> Option<Staff> {
+        let i = self.position(id)?;
+
+        Some(self.staffs.remove(i))
+    }
+
+    /// Moves a staff to the given position in display order.
+    pub fn move_staff(&mut self, id: u8, index: usize) -> Result<()> {
+        let Some(i) = self.position(id) else {
+            bail!("Staff {id} not found");
+        };
+
+        if index >= self.staffs.len() {
+            bail!("Invalid staff position {index}");
+        }
+
+        let staff = self.staffs.remove(i);
+        self.staffs.insert(index, staff);
+
+        Ok(())
+    }
+
+    /// Restores the order of the `\staff` numbers.
+    pub fn sort_staffs(&mut self) {
+        self.staffs.sort_by_key(|s| s.id);
+    }
+
+    fn position(&self, id: u8) -> Option<usize> {
+        self.staffs.iter().position(|s| s.id == id)
+    }
+
+    pub fn visit<'a>(&'a self, visitor: &mut dyn Visitor<'a>) {
+        for staff in &self.staffs {
+            visitor.on_staff_begin(&VisitContext::new(staff.id, 0));
 
-            for voice in &staff.voices {
-                voice.visit(visitor.clone())
+            for (i, voice) in staff.voices.iter().enumerate() {
+                voice.visit(visitor, &mut VisitContext::new(staff.id, i))
             }
 
-            visitor.borrow_mut().on_staff_end();
+            visitor.on_staff_end(&VisitContext::new(staff.id, 0));
+        }
+    }
+
+    pub fn visit_mut(&mut self, visitor: &mut dyn VisitorMut) {
+        for staff in &mut self.staffs {
+            visitor.on_staff_begin(&VisitContext::new(staff.id, 0));
+
+            for (i, voice) in staff.voices.iter_mut().enumerate() {
+                voice.visit_mut(visitor, &mut VisitContext::new(staff.id, i))
+            }
+
+            visitor.on_staff_end(&VisitContext::new(staff.id, 0));
+        }
+    }
+
+    /// Rewrites the events of every voice.
+    pub fn fold(&mut self, folder: &mut dyn Fold) {
+        for staff in &mut self.staffs {
+            for (i, voice) in staff.voices.iter_mut().enumerate() {
+                let mut cx = VisitContext::new(staff.id, i);
+                let events = std::mem::take(&mut voice.events);
+
+                voice.events = fold_events(events, folder, &mut cx);
+            }
         }
     }
 
     pub fn parse(input: &str) -> Result<Self> {
-        let input = all_comments(input)?;
+        Self::parse_with(input, Context::default())
+    }
+
+    /// Parses a score keeping the tags used with the wrong type, which
+    /// linters report.
+    pub fn parse_lenient(input: &str) -> Result<Self> {
+        Self::parse_with(
+            input,
+            Context {
+                lenient: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Parses a score in a context, like one with extra tag definitions.
+    pub fn parse_with(input: &str, mut context: Context) -> Result<Self> {
+        let input = blank_comments(input)?;
 
         let (_, score) = preceded(
             ws,
-            |s| parse_internal(s, ContextPtr::default()),
+            |s| parse_internal(s, &mut context),
         )(Span::new(input.as_str()))
-            .map_err(|e| anyhow!("{e}"))?;
+            .map_err(|e| match e {
+                nom::Err::Error(e) | nom::Err::Failure(e) => {
+                    SyntaxError::new(&e.input).into()
+                },
+                nom::Err::Incomplete(_) => anyhow!("Incomplete score"),
+            })?;
 
         Ok(score)
     }
 }
 
-fn parse_internal(input: Span, context: ContextPtr) -> IResult<Span, Score> {
+fn parse_internal<'a>(input: Span<'a>, context: &mut Context) -> IResult<Span<'a>, Score> {
     let (_, next) = peek(one_of("[{"))(input)?;
 
     let (input, voices) = match next {
         '{' => delimited(
             terminated(char('{'), ws),
-            |s| parse_voices(s, context.clone()),
+            |s| parse_voices(s, context),
             terminated(char('}'), ws),
         )(input)?,
         _ => {
@@ -96,12 +269,12 @@ fn parse_internal(input: Span, context: ContextPtr) -> IResult<Span, Score> {
     Ok((input, Score::new(voices)))
 }
 
-fn parse_voices(input: Span, context: ContextPtr) -> IResult<Span, Vec<Voice>> {
-    let (input, first) = Voice::parse(input, context.clone())?;
+fn parse_voices<'a>(input: Span<'a>, context: &mut Context) -> IResult<Span<'a>, Vec<Voice>> {
+    let (input, first) = Voice::parse(input, context)?;
 
     let (input, mut voices) =
         many0(preceded(terminated(char(','), ws), |i| {
-            Voice::parse(i, context.clone())
+            Voice::parse(i, context)
         }))(input)?;
 
     voices.insert(0, first);
@@ -114,6 +287,7 @@ mod tests {
     use anyhow::{anyhow, Result};
 
     use super::*;
+    use crate::note::Diatonic;
 
     fn parse_score(input: &str) -> Result<Score> {
         let score =
@@ -127,7 +301,7 @@ mod tests {
         let score = parse_score("[ a1 ]")?;
         assert_eq!(score.staffs.len(), 1);
 
-        let staff = &score.staffs[&1];
+        let staff = score.staff(1).unwrap();
         assert_eq!(staff.voices.len(), 1);
 
         Ok(())
@@ -145,7 +319,7 @@ mod tests {
 
         assert_eq!(score.staffs.len(), 1);
 
-        let staff = &score.staffs[&1];
+        let staff = score.staff(1).unwrap();
 
         assert_eq!(staff.voices.len(), 2);
 
@@ -168,6 +342,96 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn ordered_staffs() -> Result<()> {
+        let score = parse_score(
+            "{ [ \\staff<3> c ], [ \\staff<1> d ], [ \\staff<3> e ], [ \\staff<2> f ] }",
+        )?;
+
+        let ids = score.staffs.iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        let staff = score.staff(3).unwrap();
+        assert_eq!(staff.voices.len(), 2);
+        assert!(staff.voices[0].events[1].equals(&Note::from_name(Diatonic::C)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn voice_scope() -> Result<()> {
+        let score = parse_score(
+            "{ [ \\staff<2> c3/8 ], [ d ], [ { e, \\staff<2> g } ] }",
+        )?;
+
+        assert_eq!(score.staff(2).unwrap().voices.len(), 1);
+
+        let staff = score.staff(1).unwrap();
+        assert_eq!(staff.voices.len(), 2);
+        assert!(staff.voices[0].events[0].equals(&Note::from_name(Diatonic::D)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn staff_metadata() -> Result<()> {
+        let score = parse_score(
+            "{ [ \\instr<\"Flute\"> \\clef<\"g\"> \\key<-2> \\meter<\"3/4\"> c \\clef<\"f\"> ],
+               [ \\staff<2> \\staffFormat<style=\"1-line\"> \\meter<\"3/4\"> _ ] }",
+        )?;
+
+        let staff = score.staff(1).unwrap();
+        assert_eq!(staff.name.as_deref(), Some("Flute"));
+        assert_eq!(staff.clef.as_deref(), Some("g"));
+        assert_eq!(staff.key.as_deref(), Some("-2"));
+        assert_eq!(staff.meter.as_deref(), Some("3/4"));
+        assert_eq!(staff.style, None);
+
+        let staff = score.staff(2).unwrap();
+        assert_eq!(staff.name, None);
+        assert_eq!(staff.style.as_deref(), Some("1-line"));
+        assert_eq!(staff.lines, Some(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn edit_staffs() -> Result<()> {
+        let mut score = parse_score("{ [ \\staff<1> c ], [ \\staff<2> d ] }")?;
+
+        assert!(score.add_staff(Staff::new(2)).is_err());
+        score.add_staff(Staff::new(5))?;
+        score.move_staff(5, 0)?;
+
+        let ids = |score: &Score| {
+            score.staffs.iter().map(|s| s.id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&score), vec![5, 1, 2]);
+
+        assert!(score.remove_staff(1).is_some());
+        assert!(score.remove_staff(1).is_none());
+        assert!(score.move_staff(2, 2).is_err());
+
+        score.sort_staffs();
+        assert_eq!(ids(&score), vec![2, 5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn thread_safe() -> Result<()> {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let score = std::thread::spawn(|| parse_score("{ [ c d ], [ e ] }"))
+            .join()
+            .unwrap()?;
+
+        assert_send_sync(&score);
+        assert_eq!(score.staffs[0].voices.len(), 2);
+
+        Ok(())
+    }
+
     #[test]
     fn invalid_score() {
         let res = parse_score("{ [ \\unknown ] }");
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::str::FromStr;
 
 use nom::{branch::alt, bytes::complete::tag, character::complete::{alpha1, char}, combinator::opt, error_position, IResult, multi::many0, sequence::{delimited, preceded, terminated}};
@@ -7,8 +8,9 @@ use nom::error::ErrorKind;
 use serde::Deserialize;
 
 use crate::{
-    context::ContextPtr,
+    context::Context,
     event::Event,
+    location::Location,
     models::ws,
     tag_id::TagId,
     tag_param::TagParam,
@@ -35,12 +37,35 @@ impl PartialEq for TagType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl fmt::Display for TagType {
+    /// The kind of tag, `Begin` and `End` tags being parts of range tags.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TagType::Any => "any",
+            TagType::Position => "position",
+            TagType::Begin(_) | TagType::End(_) | TagType::Range => "range",
+        };
+
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Tag {
     pub id: TagId,
     pub ty: TagType,
     pub params: Vec<TagParam>,
     pub events: Vec<Box<dyn Event>>,
+    pub location: Location,
+}
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.ty == other.ty
+            && self.params == other.params
+            && self.events == other.events
+    }
 }
 
 impl Tag {
@@ -55,6 +80,7 @@ impl Tag {
             ty,
             params,
             events,
+            location: Location::default(),
         }
     }
 
@@ -77,7 +103,7 @@ impl Tag {
         self
     }
 
-    pub fn parse(input: Span, mut context: ContextPtr) -> IResult<Span, Self> {
+    pub fn parse<'a>(input: Span<'a>, context: &mut Context) -> IResult<Span<'a>, Self> {
         // println!("\n\n\nParsing \"{input}\"\n\n");
         let (input, maybe_id) =
             alt((terminated(preceded(char('\\'), alpha1), ws), tag("|"))
@@ -94,11 +120,9 @@ impl Tag {
         // println!("\n\n\nParsing \"{input}\" for {id:?}{maybe_params:?}");
 
         let (input, maybe_events) = opt(
-            |s| parse_delimited_events(s, context.clone(), '(', ')'),
+            |s| parse_delimited_events(s, context, '(', ')'),
         )(input)?;
 
-        let mut ctx = context.borrow_mut();
-
         // TODO: revisit this, its awful like this
         let mut ty = TagType::Position;
         let maybe_id = if maybe_id.ends_with("Begin") {
@@ -123,7 +147,7 @@ impl Tag {
             maybe_id.to_string()
         };
 
-        let id = ctx.lookup_tag(&maybe_id)
+        let id = context.lookup_tag(&maybe_id)
             .map_err(|e| {
                 println!("{e:?}");
                 Err::Error(error_position!(input, ErrorKind::Fail))
@@ -141,13 +165,13 @@ impl Tag {
             maybe_events.unwrap_or_default(),
         );
 
-        if let Err(e) = ctx.validate(&tag) {
+        if let Err(e) = context.validate(&tag) {
             println!("Could not validate \"{:?}\": {e}", tag.id);
 
             return Err(Err::Error(error_position!(input, ErrorKind::Fail)));
         }
 
-        ctx.add_tag(tag.clone());
+        context.add_tag(tag.clone());
 
         Ok((input, tag))
     }
@@ -167,6 +191,61 @@ impl Tag {
             None
         }
     }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if !self.has_params() {
+            return None;
+        }
+
+        if let TagParam::String(s) = &self.params[0] {
+            Some(s)
+        } else {
+            None
+        }
+    }
+
+    /// Value of a named string parameter, like `style="1-line"`.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.params.iter().find_map(|p| match p {
+            TagParam::VarString(n, s) if n == name => Some(s.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Value of a named number parameter, like `count=4`.
+    pub fn get_number(&self, name: &str) -> Option<f32> {
+        self.params.iter().find_map(|p| match p {
+            TagParam::VarNumber(n, v) if n == name => Some(*v),
+            _ => None,
+        })
+    }
+
+    /// Length in half spaces of the first parameter, or of the named one,
+    /// half spaces being the unit of plain numbers.
+    pub fn length(&self, name: &str) -> Option<f32> {
+        self.params.iter().enumerate().find_map(|(i, p)| match p {
+            TagParam::Number(v) if i == 0 => Some(*v),
+            TagParam::NumberUnit(v, unit) if i == 0 => {
+                Some(unit.half_spaces(*v))
+            },
+            TagParam::VarNumber(n, v) if n == name => Some(*v),
+            TagParam::VarNumberUnit(n, v, unit) if n == name => {
+                Some(unit.half_spaces(*v))
+            },
+            _ => None,
+        })
+    }
+
+    /// Number of full measures taken by a `\mrest<count>`.
+    pub fn rest_measures(&self) -> Option<u32> {
+        if self.id != TagId::Mrest {
+            return None;
+        }
+
+        let count = self.as_number().or_else(|| self.get_number("count"))?;
+
+        Some(count.max(0.0) as u32)
+    }
 }
 
 fn parse_suffix(input: Span) -> IResult<Span, u8> {
@@ -201,10 +280,10 @@ mod tests {
     use super::*;
 
     fn parse_tag(input: &str) -> Result<Tag> {
-        let context = ContextPtr::default();
+        let mut context = Context::default();
 
         let (input, parsed) =
-            Tag::parse(Span::new(input), context).map_err(|e| anyhow!("{}", e))?;
+            Tag::parse(Span::new(input), &mut context).map_err(|e| anyhow!("{}", e))?;
 
         assert_eq!(*input.fragment(), "");
 
@@ -297,6 +376,16 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn as_str() -> Result<()> {
+        let tag = parse_tag("\\intens<\"pp\">")?;
+
+        assert_eq!(tag.as_str().unwrap(), "pp");
+        assert!(parse_tag("\\staff<1>")?.as_str().is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn compound() -> Result<()> {
         let tag = parse_tag("\\accidental<size=1.4>(d&)")?;
@@ -380,4 +469,16 @@ mod tests {
     fn assert_tag_id(tag: Tag, expected: TagId) {
         assert_eq!(tag.id, expected)
     }
+
+    #[test]
+    fn lengths_and_counts() -> Result<()> {
+        assert_eq!(parse_tag("\\space<4>")?.length("dd"), Some(4.0));
+        assert_eq!(parse_tag("\\space<dd=2hs>")?.length("dd"), Some(2.0));
+        assert_eq!(parse_tag("\\space<7mm>")?.length("dd"), Some(8.0));
+        assert_eq!(parse_tag("\\mrest<4>")?.rest_measures(), Some(4));
+        assert_eq!(parse_tag("\\mrest<count=2>")?.rest_measures(), Some(2));
+        assert_eq!(parse_tag("\\space<4>")?.rest_measures(), None);
+
+        Ok(())
+    }
 }
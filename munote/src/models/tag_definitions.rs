@@ -1,14 +1,19 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::{anyhow, bail, Result};
 use lazy_static::lazy_static;
-use serde::Deserialize;
+use parse_display::Display;
+use serde::{Deserialize, Deserializer};
 
 use crate::tag::TagType;
 use crate::tag_id::TagId;
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Display, Deserialize)]
+#[display(style = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub enum TagParamType {
     Boolean,
@@ -19,17 +24,23 @@ pub enum TagParamType {
     Unit,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct TagParamDefinition {
     pub name: String,
     #[serde(rename = "type")]
     pub ty: TagParamType,
-    // default: TagParamDefaultValue,
+    /// The value used when the parameter is not given, as written in the
+    /// definitions.
+    #[serde(default, deserialize_with = "scalar")]
+    pub default: Option<String>,
     pub optional: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct TagDefinition {
+    /// What the tag does, in a sentence.
+    #[serde(default)]
+    pub description: String,
     #[serde(rename = "type")]
     pub ty: TagType,
     #[serde(default = "Vec::new")]
@@ -38,32 +49,114 @@ pub struct TagDefinition {
     pub params: Vec<TagParamDefinition>,
 }
 
+/// The definitions of the tags, the built-in ones and the ones added to
+/// them, like house-style tags:
+///
+/// ```yaml
+/// rehearsalLetter:
+///   description: "Boxed letter of a section"
+///   type: "position"
+///   params:
+///     - { name: letter, type: "string", optional: false }
+/// ```
+#[derive(Clone)]
 pub struct TagDefinitions {
-    defs: &'static HashMap<TagId, TagDefinition>,
-    lookup: &'static HashMap<String, TagId>,
+    defs: Arc<HashMap<TagId, TagDefinition>>,
+    lookup: Arc<HashMap<String, TagId>>,
 }
 
 impl Default for TagDefinitions {
     fn default() -> Self {
         lazy_static! {
-            static ref TAG_DEFS: HashMap<TagId, TagDefinition> = load_defs(include_str!("../../assets/tag_defs.yaml"))
-                .expect("Could not load tag definitions");
-            static ref TAG_LOOKUP: HashMap<String, TagId> = build_lookup(&TAG_DEFS)
-                .expect("Could not create tag lookup");
+            static ref TAG_DEFS: Arc<HashMap<TagId, TagDefinition>> =
+                Arc::new(
+                    load_defs(include_str!("../../assets/tag_defs.yaml"))
+                        .expect("Could not load tag definitions"),
+                );
+            static ref TAG_LOOKUP: Arc<HashMap<String, TagId>> = Arc::new(
+                build_lookup(&TAG_DEFS).expect("Could not create tag lookup"),
+            );
         }
 
         Self {
-            defs: &TAG_DEFS,
-            lookup: &TAG_LOOKUP,
+            defs: TAG_DEFS.clone(),
+            lookup: TAG_LOOKUP.clone(),
         }
     }
 }
 
 impl TagDefinitions {
+    /// The built-in definitions with the ones of a YAML file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let input = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read \"{}\": {e}", path.display()))?;
+
+        let mut defs = Self::default();
+        defs.extend(&input)
+            .map_err(|e| anyhow!("Invalid \"{}\": {e}", path.display()))?;
+
+        Ok(defs)
+    }
+
+    /// Adds the definitions of tags written in YAML, as the built-in ones.
+    ///
+    /// Nothing is added unless all the definitions are valid.
+    pub fn extend(&mut self, input: &str) -> Result<()> {
+        let extra: HashMap<String, TagDefinition> =
+            serde_yaml::from_str(input)?;
+
+        let mut defs = (*self.defs).clone();
+        let mut lookup = (*self.lookup).clone();
+
+        for (name, def) in extra {
+            let names = def.alternatives.iter().chain([&name]);
+            for name in names {
+                if lookup.contains_key(name) || TagId::from_str(name).is_ok() {
+                    bail!("Tag {name} already defined");
+                }
+            }
+
+            let id = TagId::custom(&name);
+            lookup.insert(name, id);
+            for alt in &def.alternatives {
+                lookup.insert(alt.clone(), id);
+            }
+
+            defs.insert(id, def);
+        }
+
+        self.defs = Arc::new(defs);
+        self.lookup = Arc::new(lookup);
+
+        Ok(())
+    }
+
+    /// All the definitions, by name.
+    pub fn iter(&self) -> impl Iterator<Item = (TagId, &TagDefinition)> {
+        let mut defs = self.defs.iter().collect::<Vec<_>>();
+        defs.sort_by_key(|(id, _)| id.to_string());
+
+        defs.into_iter().map(|(id, def)| (*id, def))
+    }
+
     pub fn get(&self, id: TagId) -> Option<&TagDefinition> {
         self.defs.get(&id)
     }
 
+    /// The definition of a tag by its name, one of its aliases or the name
+    /// of one of its `Begin` and `End` tags, with or without a backslash.
+    pub fn find(&self, name: &str) -> Option<(TagId, &TagDefinition)> {
+        let name = name.strip_prefix('\\').unwrap_or(name);
+        let id = self.lookup(name).ok().or_else(|| {
+            let id = name
+                .strip_suffix("Begin")
+                .or_else(|| name.strip_suffix("End"))?;
+            self.lookup(id).ok()
+        })?;
+
+        Some((id, self.get(id)?))
+    }
+
     pub fn lookup(&self, name: &str) -> Result<TagId> {
         if let Some(alt) = self.lookup.get(name) {
             return Ok(*alt);
@@ -74,7 +167,24 @@ impl TagDefinitions {
     }
 }
 
-fn load_defs(input: &str) -> Result<HashMap<TagId, TagDefinition>> {
+/// A scalar value of the definitions as text, like `above` or `0hs`, empty
+/// strings meaning no value.
+fn scalar<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let value = serde_yaml::Value::deserialize(deserializer)?;
+
+    Ok(match value {
+        serde_yaml::Value::Null => None,
+        serde_yaml::Value::String(s) if s.is_empty() => None,
+        serde_yaml::Value::String(s) => Some(s),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+fn load_defs(input: &'static str) -> Result<HashMap<TagId, TagDefinition>> {
     let defs = serde_yaml::from_str(input)?;
 
     Ok(defs)
@@ -95,3 +205,97 @@ fn build_lookup(defs: &HashMap<TagId, TagDefinition>) -> Result<HashMap<String,
 
     Ok(lookup)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn described() {
+        let defs = TagDefinitions::default();
+
+        for (id, def) in defs.iter() {
+            assert!(!def.description.is_empty(), "{id} has no description");
+        }
+    }
+
+    #[test]
+    fn find() {
+        let defs = TagDefinitions::default();
+        let id = |name| defs.find(name).map(|(id, _)| id);
+
+        assert_eq!(id("slur"), Some(TagId::Slur));
+        assert_eq!(id("\\sl"), Some(TagId::Slur));
+        assert_eq!(id("crescBegin"), Some(TagId::Crescendo));
+        assert_eq!(id("repeatEnd"), Some(TagId::RepeatEnd));
+        assert_eq!(id("slurred"), None);
+    }
+
+    /// Every tag of the reference in `docs/GUIDO-Music Notation Format.pdf`,
+    /// which only specifies Basic GUIDO: its Advanced and Extended parts are
+    /// announced for later reports. The tags of Advanced GUIDO come from the
+    /// scores using them instead, and Extended GUIDO (generic pitch classes,
+    /// tuning systems, hierarchical scores) is not supported.
+    #[test]
+    fn reference_tags() {
+        let defs = TagDefinitions::default();
+        let names = [
+            "accel", "accelBegin", "accelEnd", "accent", "bar", "beam",
+            "beamsOff", "clef", "composer", "cresc", "cue", "dim", "dimBegin",
+            "dimEnd", "doubleBar", "fermata", "grace", "i", "instr", "intens",
+            "key", "label", "marcato", "mark", "meter", "mord", "oct",
+            "repeatBegin", "repeatEnd", "rit", "sl", "slur", "stacc", "staff",
+            "stemsAuto", "stemsDown", "stemsUp", "tactus", "tempo", "ten",
+            "text", "tie", "title", "trem", "trill", "turn",
+        ];
+
+        for name in names {
+            assert!(defs.find(name).is_some(), "\\{name} is not defined");
+        }
+    }
+
+    /// Advanced tags found in GUIDO scores.
+    #[test]
+    fn guido_tags() {
+        let defs = TagDefinitions::default();
+        let names = [
+            "i", "trem", "ritBegin", "cresc", "dim", "headsReverse", "ped",
+            "lyricsAutoPos", "fing", "staffOff", "staffOn", "set", "shareStem",
+            "symbol", "tactus", "t", "label",
+        ];
+
+        for name in names {
+            assert!(defs.find(name).is_some(), "\\{name} is not defined");
+        }
+    }
+
+    #[test]
+    fn extend() -> Result<()> {
+        let mut defs = TagDefinitions::default();
+        defs.extend(
+            r#"
+cueText:
+  description: "Text of a cue"
+  type: "position"
+  alternatives: [ "ct" ]
+  params:
+    - { name: text, type: "string", optional: false }
+"#,
+        )?;
+
+        let id = TagId::custom("cueText");
+        assert_eq!(defs.lookup("ct")?, id);
+        assert_eq!(defs.get(id).map(|d| d.params.len()), Some(1));
+        assert!(TagDefinitions::default().get(id).is_none());
+
+        // Aliases cannot be taken twice, and no other tag of the file is
+        // added then
+        let slurry = "slurry:\n  type: any\n  alternatives: [ sl ]\n\
+                      cueNote:\n  type: any";
+        assert!(defs.extend(slurry).is_err());
+        assert!(defs.lookup("slurry").is_err());
+        assert!(defs.lookup("cueNote").is_err());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
-use parse_display::FromStr;
+use std::{collections::HashSet, sync::Mutex};
 
+use lazy_static::lazy_static;
+use parse_display::{Display, FromStr};
 use serde::Deserialize;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, FromStr, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, Display, FromStr, Deserialize,
+)]
 #[display(style = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub enum TagId {
@@ -29,6 +33,7 @@ pub enum TagId {
     BarFormat,
     DoubleBar,
     EndBar,
+    Tactus,
 
     // Beaming
     Beam,
@@ -63,6 +68,7 @@ pub enum TagId {
     Auto,
     Space,
     Special,
+    Symbol,
 
     // Notes
     Cluster,
@@ -86,6 +92,7 @@ pub enum TagId {
     StemsUp,
     Tie,
     Tuplet,
+    ShareStem,
 
     // Ornaments
     Arpeggio,
@@ -118,8 +125,57 @@ pub enum TagId {
     Footer,
     Harmony,
     Instrument,
+    Label,
     Lyrics,
+    LyricsAutoPos,
     Mark,
     Text,
     Title,
+
+    /// A tag of the extra definitions, by name.
+    #[display("{0}")]
+    #[from_str(ignore)]
+    #[serde(skip)]
+    Custom(&'static str),
+}
+
+impl TagId {
+    /// The ID of a tag of the extra definitions.
+    ///
+    /// Names are kept for the whole run, as the IDs of the built-in tags:
+    /// each distinct name is leaked once, so the memory taken is bounded by
+    /// the number of distinct custom tags, however often definitions are
+    /// loaded.
+    pub fn custom(name: &str) -> Self {
+        lazy_static! {
+            static ref NAMES: Mutex<HashSet<&'static str>> = Default::default();
+        }
+
+        let mut names = NAMES.lock().unwrap();
+        let name = match names.get(name) {
+            Some(name) => *name,
+            None => {
+                let name: &'static str =
+                    Box::leak(name.to_string().into_boxed_str());
+                names.insert(name);
+                name
+            },
+        };
+
+        TagId::Custom(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn custom() {
+        assert_eq!(TagId::custom("cueText"), TagId::custom("cueText"));
+        assert_eq!(TagId::custom("cueText").to_string(), "cueText");
+        assert!(TagId::from_str("cueText").is_err());
+    }
 }
@@ -3,6 +3,9 @@ use parse_display::Display;
 use strum::EnumIter;
 use crate::models::Span;
 
+/// Half of the 1.75 mm between the lines of a 7 mm staff.
+const HALF_SPACE_MM: f32 = 0.875;
+
 #[derive(Clone, Copy, Debug, PartialEq, Display, EnumIter)]
 #[display(style = "camelCase")]
 pub enum Unit {
@@ -27,4 +30,20 @@ impl Unit {
             value(Unit::Hs, tag("hs")),
         ))(input)
     }
+
+    /// Converts a length to half spaces, the distance between a staff line
+    /// and the next space, for staffs 7 mm high.
+    pub fn half_spaces(self, value: f32) -> f32 {
+        let mm = match self {
+            Unit::M => 1000.0,
+            Unit::Cm => 10.0,
+            Unit::Mm => 1.0,
+            Unit::In => 25.4,
+            Unit::Pt => 25.4 / 72.0,
+            Unit::Pc => 25.4 / 6.0,
+            Unit::Hs => return value,
+        };
+
+        value * mm / HALF_SPACE_MM
+    }
 }
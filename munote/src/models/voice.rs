@@ -1,14 +1,14 @@
 use nom::IResult;
 
 use crate::{
-    context::ContextPtr,
+    context::Context,
     event::Event,
 };
 use crate::event::parse_delimited_events;
 use crate::models::Span;
 use crate::tag::Tag;
 use crate::tag_id::TagId;
-use crate::visitor::VisitorPtr;
+use crate::visitor::{VisitContext, Visitor, VisitorMut};
 
 #[derive(Debug)]
 pub struct Voice {
@@ -22,19 +22,40 @@ impl Voice {
         Self { staff, events }
     }
 
-    pub fn visit(&self, mut visitor: VisitorPtr) {
-        visitor.borrow_mut().on_voice(self);
+    pub fn visit<'a>(
+        &'a self,
+        visitor: &mut dyn Visitor<'a>,
+        cx: &mut VisitContext,
+    ) {
+        visitor.on_voice(self, cx);
 
         for event in &self.events {
-            event.visit(visitor.clone());
+            event.visit(visitor, cx);
         }
+
+        visitor.on_voice_end(self, cx);
     }
 
-    pub fn parse<'a>(input: Span, context: ContextPtr) -> IResult<Span, Self> {
-        let (input, events) = parse_delimited_events(input, context.clone(), '[', ']')?;
+    pub fn visit_mut(
+        &mut self,
+        visitor: &mut dyn VisitorMut,
+        cx: &mut VisitContext,
+    ) {
+        visitor.on_voice(self, cx);
+
+        for event in &mut self.events {
+            event.visit_mut(visitor, cx);
+        }
+
+        visitor.on_voice_end(self, cx);
+    }
 
-        let ctx = context.borrow();
-        let staff = ctx.get_tag(TagId::Staff).and_then(Tag::as_number);
+    pub fn parse<'a>(input: Span<'a>, context: &mut Context) -> IResult<Span<'a>, Self> {
+        context.begin_voice();
+
+        let (input, events) = parse_delimited_events(input, context, '[', ']')?;
+
+        let staff = context.get_tag(TagId::Staff).and_then(Tag::as_number);
 
         Ok((input, Voice::new(staff.unwrap_or(1.0) as u8, events)))
     }
@@ -54,10 +75,10 @@ mod tests {
     use super::*;
 
     fn parse_voice(input: &str) -> Result<Voice> {
-        let context = ContextPtr::default();
+        let mut context = Context::default();
 
         let (input, parsed) =
-            Voice::parse(Span::new(input), context).map_err(|e| anyhow!("{}", e))?;
+            Voice::parse(Span::new(input), &mut context).map_err(|e| anyhow!("{}", e))?;
 
         assert_eq!(*input.fragment(), "");
 
@@ -103,6 +124,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn carry_over_chords() -> Result<()> {
+        let voice = parse_voice("[ { c2/8, e } g ]")?;
+
+        assert!(voice.events[1].equals(
+            &Note::from_name(Diatonic::G).with_octave(2).with_duration(1, 8)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn staff_from_voice() -> Result<()> {
+        assert_eq!(parse_voice("[ \\staff<2> c ]")?.staff, 2);
+        assert_eq!(parse_voice("[ { c, \\staff<2> e } ]")?.staff, 1);
+
+        Ok(())
+    }
+
     #[test]
     fn parse_tag() -> Result<()> {
         let voice = parse_voice("[ \\meter<\"2/4\"> a1 ]")?;
@@ -0,0 +1,199 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use midir::{MidiOutput, MidiOutputConnection, MidiOutputPort};
+
+use crate::{midi::MidiMessage, scheduler::MidiSink};
+
+const CLIENT_NAME: &str = "Munote";
+
+/// How to choose the MIDI output port.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PortSelector {
+    Index(usize),
+    Name(String),
+    /// A new port other applications can connect to (ALSA and CoreMIDI).
+    Virtual(String),
+}
+
+impl FromStr for PortSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            bail!("Empty port name");
+        }
+
+        Ok(s.parse()
+            .map(Self::Index)
+            .unwrap_or_else(|_| Self::Name(s.to_string())))
+    }
+}
+
+impl fmt::Display for PortSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(i) => write!(f, "#{i}"),
+            Self::Name(name) => write!(f, "\"{name}\""),
+            Self::Virtual(name) => write!(f, "virtual \"{name}\""),
+        }
+    }
+}
+
+/// Names of the available output ports, by index.
+pub fn ports() -> Result<Vec<String>> {
+    let midi_out = MidiOutput::new(CLIENT_NAME)?;
+
+    midi_out
+        .ports()
+        .iter()
+        .map(|p| midi_out.port_name(p).map_err(|e| anyhow!("{e}")))
+        .collect()
+}
+
+pub struct MidiOutputSink {
+    connection: MidiOutputConnection,
+}
+
+impl MidiOutputSink {
+    /// Connects to the selected port, or to the first one available.
+    pub fn connect(selector: Option<&PortSelector>) -> Result<Self> {
+        let midi_out = MidiOutput::new(CLIENT_NAME)?;
+
+        let connection = match selector {
+            Some(PortSelector::Virtual(name)) => {
+                connect_virtual(midi_out, name)?
+            },
+            selector => {
+                let port = find_port(&midi_out, selector)?;
+
+                midi_out
+                    .connect(&port, "Munote Output")
+                    .map_err(|e| anyhow!("Cannot connect to MIDI port: {e}"))?
+            },
+        };
+
+        Ok(Self { connection })
+    }
+}
+
+impl MidiSink for MidiOutputSink {
+    fn send(&mut self, message: MidiMessage) {
+        // Dropped messages are not worth stopping the playback for
+        let _ = self.connection.send(&message.to_bytes());
+    }
+}
+
+fn find_port(
+    midi_out: &MidiOutput,
+    selector: Option<&PortSelector>,
+) -> Result<MidiOutputPort> {
+    let ports = midi_out.ports();
+
+    if ports.is_empty() {
+        bail!("No MIDI output port available");
+    }
+
+    let port = match selector {
+        None => ports.first(),
+        Some(PortSelector::Index(i)) => ports.get(*i),
+        Some(PortSelector::Name(name)) => {
+            let names = ports
+                .iter()
+                .map(|p| midi_out.port_name(p).unwrap_or_default())
+                .collect::<Vec<_>>();
+
+            port_named(&names, name)?.and_then(|i| ports.get(i))
+        },
+        Some(PortSelector::Virtual(_)) => None,
+    };
+
+    match (port, selector) {
+        (Some(port), _) => Ok(port.clone()),
+        (None, Some(selector)) => {
+            bail!("MIDI output port {selector} not found")
+        },
+        (None, None) => bail!("No MIDI output port available"),
+    }
+}
+
+/// Index of the port called `name`, exactly or whatever the case, or else
+/// of the only port whose name contains it.
+fn port_named(names: &[String], name: &str) -> Result<Option<usize>> {
+    let lowercase = name.to_lowercase();
+
+    let exact = names
+        .iter()
+        .position(|n| n == name)
+        .or_else(|| names.iter().position(|n| n.to_lowercase() == lowercase));
+    if exact.is_some() {
+        return Ok(exact);
+    }
+
+    let mut matches = names
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| n.to_lowercase().contains(&lowercase));
+
+    match (matches.next(), matches.next()) {
+        (Some((i, _)), None) => Ok(Some(i)),
+        (Some((_, first)), Some((_, second))) => bail!(
+            "Ambiguous MIDI output port \"{name}\", matching \"{first}\" \
+             and \"{second}\""
+        ),
+        (None, _) => Ok(None),
+    }
+}
+
+#[cfg(unix)]
+fn connect_virtual(
+    midi_out: MidiOutput,
+    name: &str,
+) -> Result<MidiOutputConnection> {
+    use midir::os::unix::VirtualOutput;
+
+    midi_out
+        .create_virtual(name)
+        .map_err(|e| anyhow!("Cannot create virtual MIDI port: {e}"))
+}
+
+#[cfg(not(unix))]
+fn connect_virtual(_: MidiOutput, _: &str) -> Result<MidiOutputConnection> {
+    bail!("Virtual MIDI ports are not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_selector() {
+        assert_eq!(
+            "2".parse::<PortSelector>().unwrap(),
+            PortSelector::Index(2)
+        );
+        assert_eq!(
+            "FluidSynth".parse::<PortSelector>().unwrap(),
+            PortSelector::Name("FluidSynth".into())
+        );
+        assert!("".parse::<PortSelector>().is_err());
+    }
+
+    #[test]
+    fn port_names() -> Result<()> {
+        let names = ["Midi Through:0", "FLUID Synth (qsynth)", "FLUID Synth"]
+            .map(String::from);
+
+        assert_eq!(port_named(&names, "FLUID Synth")?, Some(2));
+        assert_eq!(port_named(&names, "fluid synth")?, Some(2));
+        assert_eq!(port_named(&names, "through")?, Some(0));
+        assert_eq!(port_named(&names, "qsynth")?, Some(1));
+        assert_eq!(port_named(&names, "Timidity")?, None);
+        assert!(port_named(&names, "fluid")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Ambiguous"));
+
+        Ok(())
+    }
+}
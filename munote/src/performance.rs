@@ -0,0 +1,1253 @@
+use std::{collections::HashMap, str::FromStr};
+
+use parse_display::FromStr;
+
+use crate::{
+    chord::Chord,
+    event::Event,
+    grace::Grace,
+    harmony::Harmony,
+    midi::{program_for, MidiMessage},
+    note::Note,
+    rest::Rest,
+    score::Score,
+    tag::{Tag, TagType},
+    tag_id::TagId,
+    tempo::{Metronome, Tempo},
+    visitor::meter_length,
+    voice::Voice,
+};
+
+/// A dynamic marking, as found in `\intens<"...">`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromStr)]
+#[display(style = "lowercase")]
+pub enum Dynamic {
+    Pppp,
+    Ppp,
+    Pp,
+    P,
+    Mp,
+    Mf,
+    F,
+    Ff,
+    Fff,
+    Ffff,
+    Sf,
+    Sfz,
+    Fz,
+    Rf,
+    Rfz,
+}
+
+impl Dynamic {
+    pub fn velocity(&self) -> u8 {
+        match self {
+            Self::Pppp => 8,
+            Self::Ppp => 20,
+            Self::Pp => 33,
+            Self::P => 49,
+            Self::Mp => 64,
+            Self::Mf => 80,
+            Self::F => 96,
+            Self::Ff => 112,
+            Self::Fff => 120,
+            Self::Ffff => 127,
+            Self::Sf | Self::Fz | Self::Rf => 112,
+            Self::Sfz | Self::Rfz => 120,
+        }
+    }
+
+    /// Sforzando-like markings only affect the next event.
+    pub fn is_accent(&self) -> bool {
+        matches!(self, Self::Sf | Self::Sfz | Self::Fz | Self::Rf | Self::Rfz)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Articulation {
+    Accent,
+    Fermata,
+    Marcato,
+    Staccato,
+    Tenuto,
+}
+
+impl Articulation {
+    pub fn from_id(id: TagId) -> Option<Self> {
+        match id {
+            TagId::Accent => Some(Self::Accent),
+            TagId::Fermata => Some(Self::Fermata),
+            TagId::Marcato => Some(Self::Marcato),
+            TagId::Staccato => Some(Self::Staccato),
+            TagId::Tenuto => Some(Self::Tenuto),
+            _ => None,
+        }
+    }
+}
+
+/// Maps the symbolic score to sounding notes: velocities from dynamics,
+/// note lengths from articulations and wall-clock times from tempo changes.
+#[derive(Clone, Debug)]
+pub struct PerformanceModel {
+    /// Quarter notes per minute until the first metronome mark.
+    pub tempo: f32,
+    /// Velocity used until the first dynamic marking.
+    pub velocity: u8,
+    /// Fraction of the written duration that actually sounds.
+    pub gate: f32,
+    pub staccato_gate: f32,
+    pub tenuto_gate: f32,
+    pub marcato_gate: f32,
+    pub accent_boost: u8,
+    pub marcato_boost: u8,
+    /// Velocity change of a hairpin not followed by a dynamic marking.
+    pub hairpin_step: u8,
+    /// Length multiplier of a note under a fermata.
+    pub fermata: f32,
+    /// Tempo multiplier reached at the end of an accelerando not giving the
+    /// tempo it reaches.
+    pub tempo_change: f32,
+    /// Length of an acciaccatura, in whole notes.
+    pub acciaccatura: f32,
+    /// Leaves out the notes of cue passages.
+    pub mute_cues: bool,
+    /// Plays the chord symbols of `\harmony` tags on a channel of their own.
+    pub backing: bool,
+}
+
+impl Default for PerformanceModel {
+    fn default() -> Self {
+        Self {
+            tempo: 120.0,
+            velocity: 0x64,
+            gate: 0.9,
+            staccato_gate: 0.5,
+            tenuto_gate: 1.0,
+            marcato_gate: 0.75,
+            accent_boost: 20,
+            marcato_boost: 30,
+            hairpin_step: 24,
+            fermata: 2.0,
+            tempo_change: 1.25,
+            acciaccatura: 1.0 / 32.0,
+            mute_cues: false,
+            backing: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerformedNote {
+    pub staff: u8,
+    pub voice: usize,
+    pub channel: u8,
+    pub pitch: u8,
+    pub velocity: u8,
+    /// Onset in seconds.
+    pub start: f32,
+    /// Release in seconds.
+    pub end: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Performance {
+    pub notes: Vec<PerformedNote>,
+    /// General MIDI program of each channel, from `\instr` tags.
+    pub programs: Vec<(u8, u8)>,
+}
+
+impl Performance {
+    pub fn duration(&self) -> f32 {
+        self.notes.iter().map(|n| n.end).fold(0.0, f32::max)
+    }
+
+    pub fn program_changes(&self) -> Vec<MidiMessage> {
+        self.programs
+            .iter()
+            .map(|(channel, program)| MidiMessage::ProgramChange {
+                channel: *channel,
+                program: *program,
+            })
+            .collect()
+    }
+
+    /// All the messages of the performance, ordered by time.
+    pub fn messages(&self) -> Vec<(f32, MidiMessage)> {
+        let mut messages = Vec::with_capacity(self.notes.len() * 2);
+
+        messages.extend(self.program_changes().into_iter().map(|m| (0.0, m)));
+
+        for note in &self.notes {
+            messages.push((
+                note.start,
+                MidiMessage::NoteOn {
+                    channel: note.channel,
+                    pitch: note.pitch,
+                    velocity: note.velocity,
+                },
+            ));
+            messages.push((
+                note.end,
+                MidiMessage::NoteOff {
+                    channel: note.channel,
+                    pitch: note.pitch,
+                },
+            ));
+        }
+
+        messages.sort_by(|(t1, m1), (t2, m2)| {
+            t1.total_cmp(t2)
+                .then_with(|| m1.priority().cmp(&m2.priority()))
+        });
+
+        messages
+    }
+}
+
+impl PerformanceModel {
+    pub fn perform(&self, score: &Score) -> Performance {
+        let timelines = self.timelines(score);
+        let time_map = self.merge_tempos(timelines.iter().map(|(_, t)| t));
+
+        // One channel per voice, leaving out the percussion one
+        let mut channels = (0..16).filter(|c| *c != 9).cycle();
+        let timelines = timelines.iter().zip(&mut channels).collect::<Vec<_>>();
+
+        let programs = timelines
+            .iter()
+            .filter_map(|((_, timeline), channel)| {
+                let program =
+                    timeline.instrument.as_deref().and_then(program_for)?;
+                Some((*channel, program))
+            })
+            .collect();
+
+        let mut notes = timelines
+            .iter()
+            .flat_map(|(((staff, voice), timeline), channel)| {
+                let notes = timeline.notes.iter();
+
+                notes.filter(|n| !(self.mute_cues && n.cue)).map(|note| {
+                    PerformedNote {
+                        staff: *staff,
+                        voice: *voice,
+                        channel: *channel,
+                        pitch: note.pitch,
+                        velocity: self.velocity_for(timeline, note),
+                        start: time_map.seconds(note.start),
+                        end: self.release(&time_map, note),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if self.backing {
+            let channel = channels.next().unwrap_or_default();
+            let time_map = &time_map;
+
+            for (((staff, voice), timeline), _) in &timelines {
+                notes.extend(timeline.chords().flat_map(|(start, end, h)| {
+                    h.voicing().into_iter().map(move |pitch| PerformedNote {
+                        staff: *staff,
+                        voice: *voice,
+                        channel,
+                        pitch,
+                        velocity: self.velocity,
+                        start: time_map.seconds(start),
+                        end: time_map.seconds(end),
+                    })
+                }));
+            }
+        }
+
+        notes.sort_by(|a, b| {
+            a.start
+                .total_cmp(&b.start)
+                .then(a.staff.cmp(&b.staff))
+                .then(a.voice.cmp(&b.voice))
+                .then(a.pitch.cmp(&b.pitch))
+        });
+
+        Performance { notes, programs }
+    }
+
+    /// When the events of a score are played, with its tempo marks,
+    /// accelerandos and ritardandos.
+    pub fn time_map(&self, score: &Score) -> TimeMap {
+        let timelines = self.timelines(score);
+
+        self.merge_tempos(timelines.iter().map(|(_, t)| t))
+    }
+
+    fn timelines(&self, score: &Score) -> Vec<((u8, usize), Timeline)> {
+        let mut timelines = Vec::new();
+
+        for staff in &score.staffs {
+            for (i, voice) in staff.voices.iter().enumerate() {
+                timelines.push(((staff.id, i), self.timeline(voice)));
+            }
+        }
+
+        timelines
+    }
+
+    fn timeline(&self, voice: &Voice) -> Timeline {
+        let mut walker = Walker {
+            acciaccatura: self.acciaccatura,
+            ..Default::default()
+        };
+        walker.walk(&voice.events);
+        // Grace notes ending the voice keep their written durations
+        walker.steal(None);
+        walker.timeline.end = walker.time;
+
+        let mut timeline = walker.timeline;
+        self.resolve_hairpins(&mut timeline);
+
+        timeline
+    }
+
+    /// A hairpin not ending on a dynamic marking reaches a level a step
+    /// above or below the one it started from, which then holds.
+    fn resolve_hairpins(&self, timeline: &mut Timeline) {
+        timeline.marks.sort_by(|a, b| a.0.total_cmp(&b.0));
+        timeline
+            .hairpins
+            .sort_by(|a, b| a.start.total_cmp(&b.start));
+
+        for hairpin in &timeline.hairpins {
+            if timeline.mark_at(hairpin.end).is_some() {
+                continue;
+            }
+
+            let from =
+                timeline.level_at(hairpin.start).unwrap_or(self.velocity);
+            let to = if hairpin.crescendo {
+                from.saturating_add(self.hairpin_step).min(127)
+            } else {
+                from.saturating_sub(self.hairpin_step).max(1)
+            };
+
+            let i = timeline.marks.partition_point(|(t, _)| *t <= hairpin.end);
+            timeline.marks.insert(i, (hairpin.end, to));
+        }
+    }
+
+    fn merge_tempos<'a>(
+        &self,
+        timelines: impl Iterator<Item = &'a Timeline> + Clone,
+    ) -> TimeMap {
+        let mut changes = timelines
+            .clone()
+            .flat_map(|t| t.tempo_changes.iter().copied())
+            .collect::<Vec<_>>();
+        // Tempo marks before the changes starting with them
+        changes.sort_by(|a, b| {
+            a.start.total_cmp(&b.start).then(a.end.total_cmp(&b.end))
+        });
+
+        let mut map = TimeMap::new(self.tempo);
+        let mut tempo = self.tempo;
+
+        for change in changes {
+            let is_mark = change.end == change.start;
+            let repeated = map.ramps.last().is_some_and(|r| {
+                (r.start, r.end) == (change.start, change.end)
+            });
+
+            // The same change is often repeated in every voice
+            if change.start < map.end()
+                || repeated
+                || (is_mark && change.target.is_none())
+                || change.end < change.start
+            {
+                continue;
+            }
+
+            let to = match change.target {
+                Some(target) => target.quarters_per_minute(tempo),
+                None if change.accelerando => tempo * self.tempo_change,
+                None => tempo / self.tempo_change,
+            };
+
+            map.ramps.push(Ramp {
+                start: change.start,
+                end: change.end,
+                from: tempo,
+                to,
+            });
+            tempo = to;
+        }
+
+        let mut holds = timelines
+            .flat_map(|t| &t.notes)
+            .filter(|n| n.has(Articulation::Fermata))
+            .map(|n| (n.end, (n.end - n.start) * (self.fermata - 1.0)))
+            .collect::<Vec<_>>();
+        holds.sort_by(|a, b| a.0.total_cmp(&b.0));
+        holds.dedup_by(|a, b| a.0 == b.0);
+
+        map.holds = holds
+            .into_iter()
+            .map(|(at, length)| (at, length * 240.0 / map.tempo_at(at)))
+            .collect();
+
+        map
+    }
+
+    fn velocity_for(&self, timeline: &Timeline, note: &TimedNote) -> u8 {
+        let mut velocity = note
+            .accent
+            .unwrap_or_else(|| self.level_at(timeline, note.start));
+
+        if note.has(Articulation::Accent) {
+            velocity = velocity.saturating_add(self.accent_boost);
+        }
+
+        if note.has(Articulation::Marcato) {
+            velocity = velocity.saturating_add(self.marcato_boost);
+        }
+
+        velocity.min(127)
+    }
+
+    fn level_at(&self, timeline: &Timeline, time: f32) -> u8 {
+        let level = |time| timeline.level_at(time).unwrap_or(self.velocity);
+
+        let hairpin = timeline
+            .hairpins
+            .iter()
+            .find(|h| h.start <= time && time < h.end);
+
+        let Some(hairpin) = hairpin else {
+            return level(time);
+        };
+
+        let from = level(hairpin.start) as f32;
+        let to = level(hairpin.end) as f32;
+        let progress = (time - hairpin.start) / (hairpin.end - hairpin.start);
+
+        (from + (to - from) * progress).round().clamp(1.0, 127.0) as u8
+    }
+
+    fn release(&self, time_map: &TimeMap, note: &TimedNote) -> f32 {
+        let gate = if note.has(Articulation::Staccato) {
+            self.staccato_gate
+        } else if note.has(Articulation::Marcato) {
+            self.marcato_gate
+        } else if note.has(Articulation::Tenuto)
+            || note.has(Articulation::Fermata)
+        {
+            self.tenuto_gate
+        } else {
+            self.gate
+        };
+
+        let start = time_map.seconds(note.start);
+        let end = time_map.seconds(note.end);
+
+        start + (end - start) * gate
+    }
+}
+
+/// Converts score time (in whole notes) to seconds and back.
+///
+/// Tempo marks change the tempo at once, like ramps starting and ending at
+/// the same time.
+#[derive(Clone, Debug)]
+pub struct TimeMap {
+    tempo: f32,
+    ramps: Vec<Ramp>,
+    holds: Vec<(f32, f32)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Ramp {
+    start: f32,
+    end: f32,
+    from: f32,
+    to: f32,
+}
+
+impl TimeMap {
+    pub fn new(tempo: f32) -> Self {
+        Self {
+            tempo,
+            ramps: Vec::new(),
+            holds: Vec::new(),
+        }
+    }
+
+    /// Tempo in quarter notes per minute at the given score time.
+    pub fn tempo_at(&self, time: f32) -> f32 {
+        let mut tempo = self.tempo;
+
+        for ramp in &self.ramps {
+            if time < ramp.start {
+                break;
+            }
+
+            if time < ramp.end {
+                let progress = (time - ramp.start) / (ramp.end - ramp.start);
+                return ramp.from + (ramp.to - ramp.from) * progress;
+            }
+
+            tempo = ramp.to;
+        }
+
+        tempo
+    }
+
+    pub fn seconds(&self, time: f32) -> f32 {
+        let mut seconds = 0.0;
+        let mut position = 0.0;
+        let mut tempo = self.tempo;
+
+        for ramp in &self.ramps {
+            if time <= ramp.start {
+                break;
+            }
+
+            seconds += (ramp.start - position) * 240.0 / tempo;
+
+            let until = time.min(ramp.end);
+            let length = ramp.end - ramp.start;
+            let slope = (ramp.to - ramp.from) / length.max(f32::EPSILON);
+
+            seconds += if length <= 0.0 {
+                0.0
+            } else if slope.abs() < f32::EPSILON {
+                (until - ramp.start) * 240.0 / ramp.from
+            } else {
+                let reached = ramp.from + slope * (until - ramp.start);
+                240.0 / slope * ln(reached / ramp.from)
+            };
+
+            position = until;
+            tempo = ramp.to;
+        }
+
+        seconds += (time - position).max(0.0) * 240.0 / tempo;
+
+        let held = self
+            .holds
+            .iter()
+            .take_while(|(at, _)| *at <= time)
+            .map(|(_, length)| length)
+            .sum::<f32>();
+
+        seconds + held
+    }
+
+    /// Score time reached after some seconds, the time of a fermata while
+    /// it is held.
+    pub fn time(&self, seconds: f32) -> f32 {
+        if seconds <= 0.0 {
+            return 0.0;
+        }
+
+        let mut high = 1.0;
+        while self.seconds(high) < seconds {
+            high *= 2.0;
+        }
+
+        // Seconds only grow with the score time
+        let mut low = 0.0;
+        for _ in 0..48 {
+            let middle = (low + high) / 2.0;
+            if self.seconds(middle) < seconds {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+
+        high
+    }
+
+    fn end(&self) -> f32 {
+        self.ramps.last().map_or(0.0, |r| r.end)
+    }
+}
+
+/// Platform-independent natural logarithm, so that performances render to
+/// the same samples on every machine, like the sine table of the synth.
+fn ln(x: f32) -> f32 {
+    const LN_2: f64 = std::f64::consts::LN_2;
+
+    // x = m * 2^e with m in [1, 2)
+    let bits = f64::from(x).to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mantissa = f64::from_bits((bits & !(0x7ff << 52)) | (1023 << 52));
+
+    // ln(m) = 2 atanh(z), with z in [0, 1/3)
+    let z = (mantissa - 1.0) / (mantissa + 1.0);
+    let mut power = z;
+    let mut sum = 0.0;
+    for n in 0..30 {
+        sum += power / f64::from(2 * n + 1);
+        power *= z * z;
+    }
+
+    (exponent as f64 * LN_2 + 2.0 * sum) as f32
+}
+
+#[derive(Debug, Default)]
+struct Timeline {
+    instrument: Option<String>,
+    notes: Vec<TimedNote>,
+    marks: Vec<(f32, u8)>,
+    hairpins: Vec<Hairpin>,
+    tempo_changes: Vec<TempoChange>,
+    /// Chord symbols, from their start.
+    harmonies: Vec<(f32, Harmony)>,
+    end: f32,
+}
+
+impl Timeline {
+    /// Start, end and chord of each chord symbol, lasting until the next
+    /// one or the end of the voice.
+    fn chords(&self) -> impl Iterator<Item = (f32, f32, &Harmony)> {
+        self.harmonies
+            .iter()
+            .enumerate()
+            .map(|(i, (start, harmony))| {
+                let end = self.harmonies.get(i + 1).map_or(self.end, |h| h.0);
+                (*start, end, harmony)
+            })
+    }
+
+    fn mark_at(&self, time: f32) -> Option<u8> {
+        self.marks
+            .iter()
+            .find(|(t, _)| (*t - time).abs() < f32::EPSILON)
+            .map(|(_, v)| *v)
+    }
+
+    fn level_at(&self, time: f32) -> Option<u8> {
+        self.marks
+            .iter()
+            .take_while(|(t, _)| *t <= time)
+            .last()
+            .map(|(_, v)| *v)
+    }
+}
+
+#[derive(Debug)]
+struct TimedNote {
+    pitch: u8,
+    start: f32,
+    end: f32,
+    articulations: Vec<Articulation>,
+    accent: Option<u8>,
+    cue: bool,
+}
+
+impl TimedNote {
+    fn has(&self, articulation: Articulation) -> bool {
+        self.articulations.contains(&articulation)
+    }
+}
+
+#[derive(Debug)]
+struct Hairpin {
+    start: f32,
+    end: f32,
+    crescendo: bool,
+}
+
+/// An accelerando, a ritardando or a tempo mark, which ends where it starts.
+#[derive(Clone, Copy, Debug)]
+struct TempoChange {
+    start: f32,
+    end: f32,
+    accelerando: bool,
+    /// The tempo reached.
+    target: Option<Metronome>,
+}
+
+/// Walks the events of a voice, keeping track of the score time and of the
+/// tags that are currently applied.
+#[derive(Default)]
+struct Walker {
+    time: f32,
+    timeline: Timeline,
+    active: Vec<Articulation>,
+    pending: Vec<Articulation>,
+    accent: Option<u8>,
+    open: HashMap<(TagId, u8), (f32, Option<Articulation>)>,
+    /// Tempos reached by the open `\accelBegin` and `\ritBegin` tags.
+    targets: HashMap<(TagId, u8), Metronome>,
+    /// Length of the measures given by the last `\meter`.
+    meter: Option<f32>,
+    acciaccatura: f32,
+    /// Style of the grace notes being read.
+    grace: Option<Grace>,
+    /// Grace notes waiting for the event they steal their time from, at
+    /// their written times.
+    graces: Vec<TimedNote>,
+    grace_style: Grace,
+    /// Time stolen from the current event by the grace notes before it.
+    delay: f32,
+    cues: usize,
+}
+
+impl Walker {
+    fn walk(&mut self, events: &[Box<dyn Event>]) {
+        for event in events {
+            let any = event.as_any();
+
+            if let Some(note) = any.downcast_ref::<Note>() {
+                let duration = note.full_duration().as_f32();
+                self.steal(Some(duration));
+                self.add_note(note, self.time + duration);
+                self.advance(self.time + duration);
+            } else if let Some(chord) = any.downcast_ref::<Chord>() {
+                self.steal(Some(chord.full_duration().as_f32()));
+                self.on_chord(chord);
+            } else if let Some(rest) = any.downcast_ref::<Rest>() {
+                let duration = rest.full_duration().as_f32();
+                self.steal(Some(duration));
+                self.advance(self.time + duration);
+            } else if let Some(tag) = any.downcast_ref::<Tag>() {
+                self.on_tag(tag);
+            }
+        }
+    }
+
+    fn on_chord(&mut self, chord: &Chord) {
+        let end = self.time + chord.full_duration().as_f32();
+
+        for symbol in &chord.symbols {
+            let any = symbol.as_any();
+
+            if let Some(note) = any.downcast_ref::<Note>() {
+                let end = self.time + note.full_duration().as_f32();
+                self.add_note(note, end);
+            } else if let Some(tag) = any.downcast_ref::<Tag>() {
+                // Range tags inside chords only decorate single notes
+                let time = self.time;
+                self.on_tag(tag);
+                self.time = time;
+            }
+        }
+
+        self.advance(end);
+    }
+
+    fn on_tag(&mut self, tag: &Tag) {
+        let articulation = Articulation::from_id(tag.id);
+
+        match tag.ty {
+            TagType::Range => {
+                let start = self.time;
+                let grace = self.grace;
+
+                if let Some(style) = Grace::from_tag(tag) {
+                    self.grace = Some(style);
+                }
+                if tag.id == TagId::Cue {
+                    self.cues += 1;
+                }
+
+                self.active.extend(articulation);
+                self.walk(&tag.events);
+                if articulation.is_some() {
+                    self.active.pop();
+                }
+
+                if tag.id == TagId::Cue {
+                    self.cues -= 1;
+                }
+                if let Some(style) = Grace::from_tag(tag) {
+                    // Grace notes take no time in the score
+                    self.grace = grace;
+                    self.grace_style = style;
+                    self.time = start;
+                }
+
+                self.close(tag.id, start, Metronome::from_tag(tag));
+                self.rest_measures(tag, start);
+            },
+            TagType::Begin(suffix) => {
+                self.active.extend(articulation);
+                self.open
+                    .insert((tag.id, suffix), (self.time, articulation));
+                if let Some(target) = Metronome::from_tag(tag) {
+                    self.targets.insert((tag.id, suffix), target);
+                }
+            },
+            TagType::End(suffix) => {
+                if let Some((start, articulation)) =
+                    self.open.remove(&(tag.id, suffix))
+                {
+                    if let Some(articulation) = articulation {
+                        if let Some(i) =
+                            self.active.iter().rposition(|a| *a == articulation)
+                        {
+                            self.active.remove(i);
+                        }
+                    }
+
+                    let begun = self.targets.remove(&(tag.id, suffix));
+                    let target = Metronome::from_tag(tag).or(begun);
+                    self.close(tag.id, start, target);
+                }
+            },
+            _ => {
+                self.pending.extend(articulation);
+
+                match tag.id {
+                    TagId::Intensity => self.on_intensity(tag),
+                    TagId::Instrument => {
+                        self.timeline.instrument =
+                            tag.as_str().map(String::from)
+                    },
+                    TagId::Meter => {
+                        self.meter = tag.as_str().and_then(meter_length)
+                    },
+                    TagId::Mrest => self.rest_measures(tag, self.time),
+                    TagId::Tempo => {
+                        let mark = Tempo::from_tag(tag).and_then(|t| t.mark);
+                        self.timeline.tempo_changes.push(TempoChange {
+                            start: self.time,
+                            end: self.time,
+                            accelerando: false,
+                            target: mark,
+                        });
+                    },
+                    TagId::Harmony => {
+                        if let Some(harmony) = Harmony::from_tag(tag) {
+                            self.timeline.harmonies.push((self.time, harmony));
+                        }
+                    },
+                    _ => {},
+                }
+            },
+        }
+    }
+
+    fn on_intensity(&mut self, tag: &Tag) {
+        let Some(dynamic) =
+            tag.as_str().and_then(|s| Dynamic::from_str(s).ok())
+        else {
+            return;
+        };
+
+        if dynamic.is_accent() {
+            self.accent = Some(dynamic.velocity());
+        } else {
+            self.timeline.marks.push((self.time, dynamic.velocity()));
+        }
+    }
+
+    fn close(&mut self, id: TagId, start: f32, target: Option<Metronome>) {
+        let end = self.time;
+
+        match id {
+            TagId::Crescendo | TagId::Decrescendo => {
+                self.timeline.hairpins.push(Hairpin {
+                    start,
+                    end,
+                    crescendo: id == TagId::Crescendo,
+                })
+            },
+            TagId::Accelerando | TagId::Ritardando => {
+                self.timeline.tempo_changes.push(TempoChange {
+                    start,
+                    end,
+                    accelerando: id == TagId::Accelerando,
+                    target,
+                })
+            },
+            _ => {},
+        }
+    }
+
+    /// Silences the measures of a `\mrest`, whatever it encloses.
+    fn rest_measures(&mut self, tag: &Tag, start: f32) {
+        if let Some(measures) = tag.rest_measures() {
+            let length = self.meter.filter(|l| *l > 0.0).unwrap_or(1.0);
+            self.advance(start + measures as f32 * length);
+        }
+    }
+
+    fn add_note(&mut self, note: &Note, end: f32) {
+        let Some(pitch) = note.midi_pitch() else {
+            return;
+        };
+
+        let mut articulations = self.active.clone();
+        articulations.extend(&self.pending);
+
+        let note = TimedNote {
+            pitch,
+            start: self.time + self.delay,
+            end,
+            articulations,
+            accent: self.accent,
+            cue: self.cues > 0,
+        };
+
+        if self.grace.is_some() {
+            self.graces.push(note);
+        } else {
+            self.timeline.notes.push(note);
+        }
+    }
+
+    /// Plays the pending grace notes at the start of the next event, whose
+    /// sounding length is shortened by as much. Without a next event, grace
+    /// notes keep their written durations.
+    fn steal(&mut self, length: Option<f32>) {
+        if self.grace.is_some() || self.graces.is_empty() {
+            return;
+        }
+
+        let graces = std::mem::take(&mut self.graces);
+        let first = graces[0].start;
+        let last = graces.iter().map(|n| n.end).fold(first, f32::max);
+        let written = last - first;
+
+        let stolen = match (length, self.grace_style) {
+            (None, _) => written,
+            (Some(length), Grace::Acciaccatura) => {
+                (graces.len() as f32 * self.acciaccatura).min(length / 2.0)
+            },
+            (Some(length), Grace::Appoggiatura) => length / 2.0,
+        };
+        let scale = if written > 0.0 { stolen / written } else { 0.0 };
+
+        for mut note in graces {
+            note.start = self.time + (note.start - first) * scale;
+            note.end = self.time + (note.end - first) * scale;
+            self.timeline.notes.push(note);
+        }
+
+        self.delay = stolen;
+    }
+
+    fn advance(&mut self, end: f32) {
+        self.time = end;
+        self.delay = 0.0;
+        self.pending.clear();
+        self.accent = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    fn perform(input: &str) -> Result<Performance> {
+        let score = Score::parse(input)?;
+
+        Ok(PerformanceModel::default().perform(&score))
+    }
+
+    fn assert_approx(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 0.001, "{actual:?} != {expected:?}");
+        }
+    }
+
+    fn velocities(performance: &Performance) -> Vec<u8> {
+        performance.notes.iter().map(|n| n.velocity).collect()
+    }
+
+    #[test]
+    fn parse_dynamics() {
+        assert_eq!(Dynamic::from_str("pp").unwrap(), Dynamic::Pp);
+        assert_eq!(Dynamic::from_str("sfz").unwrap(), Dynamic::Sfz);
+        assert!(Dynamic::from_str("loud").is_err());
+    }
+
+    #[test]
+    fn default_performance() -> Result<()> {
+        let performance = perform("[ a1/4 c2 ]")?;
+
+        assert_eq!(performance.notes.len(), 2);
+        assert_eq!(performance.notes[0].pitch, 69);
+        assert_eq!(performance.notes[0].velocity, 0x64);
+        assert_eq!(performance.notes[0].start, 0.0);
+        assert_approx(&[performance.notes[0].end], &[0.45]);
+        assert_eq!(performance.notes[1].start, 0.5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn intensity() -> Result<()> {
+        let performance =
+            perform("[ \\i<\"pp\"> c/4 d \\i<\"ff\"> e \\i<\"sfz\"> f g ]")?;
+
+        assert_eq!(velocities(&performance), vec![33, 33, 112, 120, 112]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn crescendo_to_next_mark() -> Result<()> {
+        let performance =
+            perform("[ \\i<\"p\"> \\cresc(c/4 d e f) \\i<\"f\"> g ]")?;
+
+        assert_eq!(velocities(&performance), vec![49, 61, 73, 84, 96]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrescendo_begin_end() -> Result<()> {
+        let performance =
+            perform("[ \\i<\"f\"> \\decrescBegin c/4 d \\decrescEnd e ]")?;
+
+        assert_eq!(velocities(&performance), vec![96, 84, 72]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn articulations() -> Result<()> {
+        let performance = perform(
+            "[ \\stacc(c/4) \\ten(d) \\accent(e) \\marcato(f) \\accent(\\stacc(g)) ]",
+        )?;
+
+        let lengths = performance
+            .notes
+            .iter()
+            .map(|n| n.end - n.start)
+            .collect::<Vec<_>>();
+
+        assert_approx(&lengths, &[0.25, 0.5, 0.45, 0.375, 0.25]);
+        assert_eq!(velocities(&performance), vec![100, 100, 120, 127, 120]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fermata() -> Result<()> {
+        let performance = perform("[ \\fermata(c/4) d ]")?;
+
+        assert_eq!(performance.notes[0].end, 1.0);
+        assert_eq!(performance.notes[1].start, 1.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tempo_changes() -> Result<()> {
+        let performance = perform("[ c/4 \\accel(d e) f \\rit(g a) b ]")?;
+
+        let starts = performance
+            .notes
+            .iter()
+            .map(|n| n.start)
+            .collect::<Vec<_>>();
+
+        // Quarter notes get shorter during the accelerando, longer during
+        // the ritardando.
+        let lengths =
+            starts.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+        assert_eq!(lengths[0], 0.5);
+        assert!(lengths[1] < 0.5 && lengths[2] < lengths[1]);
+        assert!((lengths[3] - 0.4).abs() < 0.001);
+        assert!(lengths[4] > 0.4 && lengths[5] > lengths[4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tempo_marks() -> Result<()> {
+        let performance = perform(
+            "[ c/4 \\tempo<\"Adagio\", \"1/4=60\"> d e \
+             \\tempo<\"[1/2] = 60\"> f g ]",
+        )?;
+        let starts = performance
+            .notes
+            .iter()
+            .map(|n| n.start)
+            .collect::<Vec<_>>();
+
+        assert_approx(&starts, &[0.0, 0.5, 1.5, 2.5, 3.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tempo_targets() -> Result<()> {
+        let score = Score::parse(
+            "[ \\tempo<\"1/4=60\"> \\accelBegin c d \\accelEnd<\"1/4=120\"> e
+               \\rit<\"1/4=120\", \"[1/8] = [1/4]\">(f g) a ]",
+        )?;
+        let map = PerformanceModel::default().time_map(&score);
+
+        assert_eq!(map.tempo_at(0.0), 60.0);
+        assert!((map.tempo_at(0.5) - 120.0).abs() < 0.001);
+        assert!((map.tempo_at(1.25) - 60.0).abs() < 0.001);
+
+        for time in [0.0, 0.3, 0.5, 1.0, 1.4] {
+            assert!((map.time(map.seconds(time)) - time).abs() < 0.001);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn chords_and_voices() -> Result<()> {
+        let performance = perform("{ [ { c/2, e, g } ], [ c0/4 d ] }")?;
+
+        let onsets = performance
+            .notes
+            .iter()
+            .map(|n| (n.start, n.pitch))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            onsets,
+            vec![(0.0, 60), (0.0, 64), (0.0, 67), (0.0, 48), (0.5, 50)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn messages() -> Result<()> {
+        let performance = perform("[ c/4 c ]")?;
+        let messages = performance.messages();
+
+        assert_eq!(messages.len(), 4);
+        assert!(messages.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert!(messages[0].1.is_note_on());
+        assert!(!messages[1].1.is_note_on());
+
+        Ok(())
+    }
+
+    #[test]
+    fn channels_and_programs() -> Result<()> {
+        let performance = perform(
+            "{ [ \\instr<\"Violin\"> c/4 ], [ \\instr<\"Cello\"> c0/4 ], [ c-1/4 ] }",
+        )?;
+
+        let channels = performance
+            .notes
+            .iter()
+            .map(|n| (n.pitch, n.channel))
+            .collect::<Vec<_>>();
+
+        assert_eq!(channels, vec![(60, 0), (48, 1), (36, 2)]);
+        assert_eq!(performance.programs, vec![(0, 40), (1, 42)]);
+        assert_eq!(
+            performance.messages()[0].1,
+            MidiMessage::ProgramChange {
+                channel: 0,
+                program: 40
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_and_multi_measure_rests() -> Result<()> {
+        let starts = |input| -> Result<Vec<f32>> {
+            let performance = perform(input)?;
+            Ok(performance.notes.iter().map(|n| n.start).collect())
+        };
+
+        // A whole note lasts 2 seconds
+        assert_eq!(starts("[ empty/2 c ]")?, vec![1.0]);
+        assert_eq!(starts("[ \\meter<\"3/4\"> \\mrest<2> c ]")?, vec![3.0]);
+        assert_eq!(starts("[ \\mrest<count=1>(_/1) c ]")?, vec![2.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn grace_notes() -> Result<()> {
+        let times = |input| -> Result<Vec<(u8, f32, f32)>> {
+            let performance = perform(input)?;
+            Ok(performance
+                .notes
+                .iter()
+                .map(|n| (n.pitch, n.start, n.end))
+                .collect())
+        };
+
+        // A whole note lasts 2 seconds, an acciaccatura 1/16 second
+        let crushed = times("[ c/4 \\grace(d/8) e/4 ]")?;
+        assert_approx(&[crushed[1].1, crushed[2].1], &[0.5, 0.5625]);
+        assert_eq!(crushed[1].0, 62);
+
+        let leaning = times("[ \\grace<style=\"appoggiatura\">(d/8 e) f/2 ]")?;
+        assert_approx(
+            &leaning.iter().map(|n| n.1).collect::<Vec<_>>(),
+            &[0.0, 0.25, 0.5],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn cue_notes() -> Result<()> {
+        let input = "[ c/4 \\cue<\"flute\">(d e) f ]";
+        let score = Score::parse(input)?;
+        let muted = PerformanceModel {
+            mute_cues: true,
+            ..Default::default()
+        };
+
+        assert_eq!(perform(input)?.notes.len(), 4);
+        assert_eq!(muted.perform(&score).notes.len(), 2);
+        assert_eq!(muted.perform(&score).notes[1].start, 1.5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn backing() -> Result<()> {
+        let score =
+            Score::parse("[ \\harmony<\"C/E\"> c/2 \\harmony<\"G7\"> d ]")?;
+        let model = PerformanceModel {
+            backing: true,
+            ..Default::default()
+        };
+        let performance = model.perform(&score);
+
+        let chords = performance
+            .notes
+            .iter()
+            .filter(|n| n.channel == 1)
+            .map(|n| (n.pitch, n.start))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            chords,
+            vec![
+                (40, 0.0),
+                (48, 0.0),
+                (52, 0.0),
+                (55, 0.0),
+                (43, 1.0),
+                (55, 1.0),
+                (59, 1.0),
+                (62, 1.0),
+                (65, 1.0)
+            ]
+        );
+        assert_eq!(PerformanceModel::default().perform(&score).notes.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn logarithm() {
+        for x in [0.5f32, 0.9, 1.0, 1.25, 2.0, 3.0, 100.0] {
+            assert!((ln(x) - x.ln()).abs() < 1e-6, "ln({x})");
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    comment::blank_comments, context::Context, error::SyntaxError, score::Score,
+};
+
+/// A score with its includes and variables expanded, ready to be parsed.
+///
+/// Files are included with `\include<"header.gmn">`, relative to the file
+/// including them. Variables are defined with `$name = [ ... ]`, and every
+/// `$name` written after the definition is replaced by what is between the
+/// brackets. Definitions may use other variables, and are seen by the files
+/// including the file defining them.
+///
+/// The expanded text remembers where each of its parts comes from, so that
+/// errors point to the original files.
+#[derive(Debug)]
+pub struct Source {
+    pub text: String,
+    path: Arc<Path>,
+    segments: Vec<Segment>,
+}
+
+/// A part of the expanded text copied from a file.
+#[derive(Debug)]
+struct Segment {
+    offset: usize,
+    file: Arc<Path>,
+    line: u32,
+    column: usize,
+}
+
+/// A place in one of the original files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Origin<'a> {
+    pub file: &'a Path,
+    pub line: u32,
+    pub column: usize,
+}
+
+impl fmt::Display for Origin<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+impl Source {
+    /// Reads and expands a score file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read \"{}\": {e}", path.display()))?;
+
+        Self::expand(&text, path)
+    }
+
+    /// Expands a score, as if it was read from `path`.
+    pub fn expand(text: &str, path: &Path) -> Result<Self> {
+        let path: Arc<Path> = path.into();
+        let mut preprocessor = Preprocessor {
+            variables: HashMap::new(),
+            includes: vec![canonical(&path)],
+            expanding: Vec::new(),
+            source: Self {
+                text: String::new(),
+                path: path.clone(),
+                segments: Vec::new(),
+            },
+        };
+
+        preprocessor.expand(&blank_comments(text)?, &path, 1, 1)?;
+
+        Ok(preprocessor.source)
+    }
+
+    /// Path of the file the score was read from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the text at the given offset was written.
+    pub fn origin(&self, offset: usize) -> Origin<'_> {
+        let i = self.segments.partition_point(|s| s.offset <= offset);
+
+        let Some(segment) = i.checked_sub(1).map(|i| &self.segments[i]) else {
+            return Origin {
+                file: &self.path,
+                line: 1,
+                column: 1,
+            };
+        };
+
+        let before = self.text.get(segment.offset..offset).unwrap_or_default();
+        let (line, column) = advance(segment.line, segment.column, before);
+
+        Origin {
+            file: &segment.file,
+            line,
+            column,
+        }
+    }
+
+    /// Parses the expanded score, with errors pointing to the original
+    /// files.
+    pub fn parse(&self) -> Result<Score> {
+        Score::parse(&self.text).map_err(|e| self.error(e))
+    }
+
+    /// Parses the expanded score like [`Score::parse_lenient`].
+    pub fn parse_lenient(&self) -> Result<Score> {
+        Score::parse_lenient(&self.text).map_err(|e| self.error(e))
+    }
+
+    /// Parses the expanded score like [`Score::parse_with`].
+    pub fn parse_with(&self, context: Context) -> Result<Score> {
+        Score::parse_with(&self.text, context).map_err(|e| self.error(e))
+    }
+
+    /// Makes a parsing error point to the original files.
+    fn error(&self, e: anyhow::Error) -> anyhow::Error {
+        match e.downcast_ref::<SyntaxError>() {
+            Some(error) if error.found.is_empty() => {
+                anyhow!("{}: unexpected end of score", self.path.display())
+            },
+            Some(error) => anyhow!(
+                "{}: unexpected \"{}\"",
+                self.origin(error.location.offset),
+                error.found
+            ),
+            None => e,
+        }
+    }
+}
+
+struct Preprocessor {
+    variables: HashMap<String, Variable>,
+    /// Files being included, to detect cycles.
+    includes: Vec<PathBuf>,
+    /// Variables being expanded, to detect cycles.
+    expanding: Vec<String>,
+    source: Source,
+}
+
+struct Variable {
+    body: String,
+    file: Arc<Path>,
+    line: u32,
+    column: usize,
+}
+
+impl Preprocessor {
+    /// Expands some text of a file, starting at the given line and column.
+    fn expand(
+        &mut self,
+        text: &str,
+        file: &Arc<Path>,
+        line: u32,
+        column: usize,
+    ) -> Result<()> {
+        let error = |at: usize, message: String| {
+            let (line, column) = advance(line, column, &text[..at]);
+            anyhow!("{}:{line}:{column}: {message}", file.display())
+        };
+
+        // Start of the text still to copy
+        let mut start = 0;
+        let mut i = 0;
+
+        while let Some(c) = text[i..].chars().next() {
+            let rest = &text[i..];
+
+            if c == '"' {
+                // Strings are copied as they are
+                i += rest[1..].find('"').map_or(rest.len(), |end| end + 2);
+            } else if let Some(include) = include(rest) {
+                let (length, name) = include
+                    .ok_or_else(|| error(i, "Invalid include".into()))?;
+
+                self.copy(text, start..i, file, line, column);
+
+                let (line, column) = advance(line, column, &text[..i]);
+                let parent = file.parent().unwrap_or(Path::new(""));
+
+                self.include(&parent.join(name)).map_err(|e| {
+                    anyhow!("{}:{line}:{column}: {e}", file.display())
+                })?;
+
+                i += length;
+                start = i;
+            } else if c == '$' {
+                let name = identifier(&rest[1..]);
+                if name.is_empty() {
+                    return Err(error(i, "Expected a variable name".into()));
+                }
+
+                self.copy(text, start..i, file, line, column);
+
+                let after = &rest[1 + name.len()..];
+                if after.trim_start().starts_with('=') {
+                    let length = self
+                        .define(
+                            name,
+                            text,
+                            i + rest.len() - after.len(),
+                            file,
+                            line,
+                            column,
+                        )
+                        .map_err(|e| error(i, e.to_string()))?;
+                    i += 1 + name.len() + length;
+                } else {
+                    self.substitute(name)
+                        .map_err(|e| error(i, e.to_string()))?;
+                    i += 1 + name.len();
+                }
+
+                start = i;
+            } else {
+                i += c.len_utf8();
+            }
+        }
+
+        self.copy(text, start..text.len(), file, line, column);
+
+        Ok(())
+    }
+
+    /// Copies a part of the text expanded from `line` and `column`.
+    fn copy(
+        &mut self,
+        text: &str,
+        range: std::ops::Range<usize>,
+        file: &Arc<Path>,
+        line: u32,
+        column: usize,
+    ) {
+        if range.is_empty() {
+            return;
+        }
+
+        let (line, column) = advance(line, column, &text[..range.start]);
+
+        self.source.segments.push(Segment {
+            offset: self.source.text.len(),
+            file: file.clone(),
+            line,
+            column,
+        });
+        self.source.text.push_str(&text[range]);
+    }
+
+    fn include(&mut self, path: &Path) -> Result<()> {
+        let canonical = canonical(path);
+
+        if let Some(i) = self.includes.iter().position(|p| *p == canonical) {
+            let cycle = self.includes[i..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+
+            bail!("Include cycle: {}", cycle.join(" -> "));
+        }
+
+        let text = fs::read_to_string(path).map_err(|e| {
+            anyhow!("Cannot include \"{}\": {e}", path.display())
+        })?;
+
+        self.includes.push(canonical);
+        self.expand(&blank_comments(&text)?, &path.into(), 1, 1)?;
+        self.includes.pop();
+
+        Ok(())
+    }
+
+    /// Defines a variable from `$name = [ ... ]`, whose `=` is found after
+    /// `at`, returning the length of the definition after the name.
+    fn define(
+        &mut self,
+        name: &str,
+        text: &str,
+        at: usize,
+        file: &Arc<Path>,
+        line: u32,
+        column: usize,
+    ) -> Result<usize> {
+        let rest = &text[at..];
+        let value = rest.trim_start()[1..].trim_start();
+
+        if !value.starts_with('[') {
+            bail!("Expected \"[\" after \"${name} =\"");
+        }
+
+        let body_start = at + rest.len() - value.len() + 1;
+        let body_length = closing_bracket(&text[body_start..])
+            .ok_or_else(|| anyhow!("Missing \"]\" after \"${name} =\""))?;
+        let (line, column) = advance(line, column, &text[..body_start]);
+
+        self.variables.insert(
+            name.to_string(),
+            Variable {
+                body: text[body_start..body_start + body_length].to_string(),
+                file: file.clone(),
+                line,
+                column,
+            },
+        );
+
+        Ok(body_start + body_length + 1 - at)
+    }
+
+    fn substitute(&mut self, name: &str) -> Result<()> {
+        let Some(variable) = self.variables.get(name) else {
+            bail!("Undefined variable \"${name}\"");
+        };
+
+        if self.expanding.iter().any(|n| n == name) {
+            let mut cycle = self.expanding.clone();
+            cycle.push(name.to_string());
+
+            bail!("Variable cycle: ${}", cycle.join(" -> $"));
+        }
+
+        let (body, file) = (variable.body.clone(), variable.file.clone());
+        let (line, column) = (variable.line, variable.column);
+
+        self.expanding.push(name.to_string());
+        self.expand(&body, &file, line, column)?;
+        self.expanding.pop();
+
+        Ok(())
+    }
+}
+
+/// The length and file of an `\include<"file">` directive, if the text
+/// starts with one.
+fn include(text: &str) -> Option<Option<(usize, &str)>> {
+    let rest = text.strip_prefix("\\include")?;
+    if rest.starts_with(|c: char| c.is_alphanumeric()) {
+        return None;
+    }
+
+    let parse = || {
+        let rest = rest.trim_start().strip_prefix('<')?;
+        let rest = rest.trim_start().strip_prefix('"')?;
+        let (name, rest) = rest.split_once('"')?;
+        let rest = rest.trim_start().strip_prefix('>')?;
+
+        Some((text.len() - rest.len(), name))
+    };
+
+    Some(parse())
+}
+
+fn identifier(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+
+    &text[..end]
+}
+
+/// Offset of the `]` closing a bracket opened just before the text.
+fn closing_bracket(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string && depth == 0 => return Some(i),
+            ']' if !in_string => depth -= 1,
+            _ => {},
+        }
+    }
+
+    None
+}
+
+/// Line and column reached after the given text.
+fn advance(line: u32, column: usize, text: &str) -> (u32, usize) {
+    match text.rfind('\n') {
+        Some(i) => (
+            line + text.matches('\n').count() as u32,
+            text[i + 1..].chars().count() + 1,
+        ),
+        None => (line, column + text.chars().count()),
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ops::Deref,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    /// A directory removed with everything in it once dropped.
+    struct TempDir(PathBuf);
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes files in a new directory, unique to the calling test.
+    fn files(files: &[(&str, &str)]) -> Result<TempDir> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = TempDir(std::env::temp_dir().join(format!(
+            "munote-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        )));
+        fs::create_dir_all(dir.join("parts"))?;
+
+        for (file, content) in files {
+            fs::write(dir.join(file), content)?;
+        }
+
+        Ok(dir)
+    }
+
+    #[test]
+    fn variables() -> Result<()> {
+        let source = Source::expand(
+            "$notes = [ c d ]\n$more = [ $notes e ]\n[ $more $notes ]",
+            Path::new("score.gmn"),
+        )?;
+
+        assert_eq!(
+            source.text.split_whitespace().collect::<Vec<_>>(),
+            ["[", "c", "d", "e", "c", "d", "]"]
+        );
+        assert_eq!(source.parse()?.staffs[0].voices[0].events.len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn includes() -> Result<()> {
+        let dir = files(&[
+            ("score.gmn", "\\include<\"parts/header.gmn\">\n[ $title c ]"),
+            ("parts/header.gmn", "% Header\n$title = [ \\title<\"$1\"> ]"),
+        ])?;
+
+        let source = Source::load(&dir.join("score.gmn"))?;
+        let score = source.parse()?;
+
+        assert_eq!(score.staffs[0].voices[0].events.len(), 2);
+        assert!(source.text.contains("\\title<\"$1\">"));
+
+        let origin = source.origin(source.text.find("\\title").unwrap());
+        assert_eq!(origin.file, dir.join("parts/header.gmn"));
+        assert_eq!((origin.line, origin.column), (2, 12));
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors() -> Result<()> {
+        let dir = files(&[
+            ("a.gmn", "[ c ]\n\\include<\"b.gmn\">"),
+            ("b.gmn", "\\include<\"a.gmn\">"),
+            ("bad.gmn", "$notes = [ c d ]\n[ c $notes\n  # ]"),
+        ])?;
+
+        let error = Source::load(&dir.join("a.gmn")).unwrap_err();
+        assert!(error.to_string().contains("Include cycle"));
+        assert!(error.to_string().contains("a.gmn:2:1"));
+
+        let error = Source::load(&dir.join("bad.gmn"))?.parse().unwrap_err();
+        assert!(error.to_string().ends_with("bad.gmn:3:3: unexpected \"#\""));
+
+        let path = Path::new("score.gmn");
+        let error = Source::expand("[ c\n $notes ]", path).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "score.gmn:2:2: Undefined variable \"$notes\""
+        );
+
+        let error =
+            Source::expand("$a = [ $b ] $b = [ $a ] [ $a ]", path).unwrap_err();
+        assert!(error.to_string().contains("Variable cycle: $a -> $b -> $a"));
+
+        Ok(())
+    }
+}
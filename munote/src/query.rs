@@ -0,0 +1,422 @@
+use std::{
+    fmt,
+    ops::{Range, RangeInclusive},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    chord::Chord,
+    context::Context,
+    event::Event,
+    location::Location,
+    models::Span,
+    note::Note,
+    rest::Rest,
+    score::Score,
+    tag::Tag,
+    tag_definitions::TagDefinitions,
+    tag_id::TagId,
+    visitor::{VisitContext, Visitor},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Note,
+    Rest,
+    Chord,
+    Tag,
+}
+
+/// Selects the events of a score matching all the given filters.
+///
+/// Queries are built in code, or parsed from expressions like
+/// `note staff=2 measure=5..8 in=\slur`, whose terms are:
+///
+/// - `note`, `rest`, `chord` or `tag`, for the kind of event;
+/// - `\name`, for the tags with that name;
+/// - `staff=N` and `voice=N`, voices counting from 1 in each staff;
+/// - `measure=N` or `measure=A..B`, inclusive;
+/// - `time=A..B`, onsets in whole notes, end excluded;
+/// - `pitch=A..B`, inclusive, with MIDI numbers or note names like `c#2`;
+/// - `in=\name`, for the events enclosed by a tag.
+///
+/// Bounds of ranges can be left out, like in `measure=5..`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pub kind: Option<Kind>,
+    pub tag: Option<TagId>,
+    pub staff: Option<u8>,
+    pub voice: Option<usize>,
+    pub measures: Option<RangeInclusive<u32>>,
+    pub time: Option<Range<f32>>,
+    pub pitch: Option<RangeInclusive<u8>>,
+    pub within: Option<TagId>,
+}
+
+/// An event selected by a query, and where it is.
+#[derive(Clone, Copy)]
+pub struct Match<'a> {
+    pub event: &'a dyn Event,
+    pub staff: u8,
+    /// Index of the voice in its staff.
+    pub voice: usize,
+    pub measure: u32,
+    pub onset: f32,
+    pub location: Location,
+}
+
+impl fmt::Debug for Match<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Match")
+            .field("event", self.event.as_debug())
+            .field("staff", &self.staff)
+            .field("voice", &self.voice)
+            .field("measure", &self.measure)
+            .field("onset", &self.onset)
+            .field("location", &self.location)
+            .finish()
+    }
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses an expression, naming the tags of `defs`.
+    pub fn parse(s: &str, defs: &TagDefinitions) -> Result<Self> {
+        let tag_id = |name: &str| {
+            let name = name.strip_prefix('\\').unwrap_or(name);
+            defs.lookup(name)
+        };
+
+        let mut query = Query::new();
+
+        for term in s.split_whitespace() {
+            if let Some((key, value)) = term.split_once('=') {
+                match key {
+                    "staff" => query.staff = Some(value.parse()?),
+                    "voice" => query.voice = Some(value.parse()?),
+                    "measure" | "measures" => {
+                        let (start, end) = parse_range(value, str::parse)?;
+                        query.measures =
+                            Some(start.unwrap_or(1)..=end.unwrap_or(u32::MAX));
+                    },
+                    "time" => {
+                        let (start, end) = parse_range(value, str::parse)?;
+                        query.time = Some(
+                            start.unwrap_or(0.0)..end.unwrap_or(f32::INFINITY),
+                        );
+                    },
+                    "pitch" => {
+                        let (start, end) = parse_range(value, parse_pitch)?;
+                        query.pitch =
+                            Some(start.unwrap_or(0)..=end.unwrap_or(127));
+                    },
+                    "in" => query.within = Some(tag_id(value)?),
+                    _ => bail!("Unknown query filter \"{key}\""),
+                }
+            } else if term.starts_with('\\') {
+                query.tag = Some(tag_id(term)?);
+            } else {
+                query.kind = Some(match term {
+                    "note" | "notes" => Kind::Note,
+                    "rest" | "rests" => Kind::Rest,
+                    "chord" | "chords" => Kind::Chord,
+                    "tag" | "tags" => Kind::Tag,
+                    _ => bail!("Unknown query term \"{term}\""),
+                });
+            }
+        }
+
+        Ok(query)
+    }
+
+    pub fn kind(mut self, kind: Kind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn tag(mut self, id: TagId) -> Self {
+        self.tag = Some(id);
+        self
+    }
+
+    pub fn staff(mut self, staff: u8) -> Self {
+        self.staff = Some(staff);
+        self
+    }
+
+    /// Voice from 1, in its staff.
+    pub fn voice(mut self, voice: usize) -> Self {
+        self.voice = Some(voice);
+        self
+    }
+
+    pub fn measures(mut self, measures: RangeInclusive<u32>) -> Self {
+        self.measures = Some(measures);
+        self
+    }
+
+    pub fn time(mut self, time: Range<f32>) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn pitch(mut self, pitch: RangeInclusive<u8>) -> Self {
+        self.pitch = Some(pitch);
+        self
+    }
+
+    pub fn within(mut self, id: TagId) -> Self {
+        self.within = Some(id);
+        self
+    }
+
+    /// The matching events, in the order of the staffs and voices.
+    pub fn select<'a>(&self, score: &'a Score) -> Vec<Match<'a>> {
+        let mut selector = Selector {
+            query: self,
+            matches: Vec::new(),
+        };
+
+        score.visit(&mut selector);
+
+        selector.matches
+    }
+
+    fn accepts(
+        &self,
+        kind: Kind,
+        pitches: &[Option<u8>],
+        cx: &VisitContext,
+    ) -> bool {
+        let in_range = |pitch: &Option<u8>| {
+            pitch.is_some_and(|p| self.pitch.as_ref().unwrap().contains(&p))
+        };
+
+        self.kind.is_none_or(|k| k == kind)
+            && self.staff.is_none_or(|s| s == cx.staff)
+            && self.voice.is_none_or(|v| v == cx.voice + 1)
+            && self
+                .measures
+                .as_ref()
+                .is_none_or(|m| m.contains(&cx.measure))
+            && self.time.as_ref().is_none_or(|t| t.contains(&cx.onset))
+            && (self.pitch.is_none() || pitches.iter().any(in_range))
+            && self.within.is_none_or(|id| cx.is_within(id))
+    }
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s, &TagDefinitions::default())
+    }
+}
+
+/// Bounds of a range like `A..B`, or of a single value.
+fn parse_range<T: Copy, E: Into<anyhow::Error>>(
+    value: &str,
+    parse: impl Fn(&str) -> Result<T, E>,
+) -> Result<(Option<T>, Option<T>)> {
+    let bound = |s: &str| -> Result<Option<T>> {
+        if s.is_empty() {
+            Ok(None)
+        } else {
+            parse(s).map(Some).map_err(Into::into)
+        }
+    };
+
+    match value.split_once("..") {
+        Some((start, end)) => Ok((bound(start)?, bound(end)?)),
+        None => {
+            let value = bound(value)?;
+            if value.is_none() {
+                bail!("Empty range");
+            }
+
+            Ok((value, value))
+        },
+    }
+}
+
+/// A MIDI pitch, or a note name.
+fn parse_pitch(value: &str) -> Result<u8> {
+    if let Ok(pitch) = value.parse() {
+        return Ok(pitch);
+    }
+
+    let (rest, note) =
+        Note::parse(Span::new(value), &mut Context::default())
+            .map_err(|e| anyhow!("Invalid pitch \"{value}\": {e}"))?;
+
+    if !rest.fragment().is_empty() {
+        bail!("Invalid pitch \"{value}\"");
+    }
+
+    note.midi_pitch()
+        .ok_or_else(|| anyhow!("Pitch \"{value}\" out of the MIDI range"))
+}
+
+struct Selector<'q, 'a> {
+    query: &'q Query,
+    matches: Vec<Match<'a>>,
+}
+
+impl<'a> Selector<'_, 'a> {
+    fn select(
+        &mut self,
+        event: &'a dyn Event,
+        kind: Kind,
+        pitches: &[Option<u8>],
+        cx: &VisitContext,
+    ) {
+        if self.query.accepts(kind, pitches, cx) {
+            self.matches.push(Match {
+                event,
+                staff: cx.staff,
+                voice: cx.voice,
+                measure: cx.measure,
+                onset: cx.onset,
+                location: event.location(),
+            });
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for Selector<'_, 'a> {
+    fn on_chord(&mut self, chord: &'a Chord, cx: &VisitContext) {
+        let pitches = chord.notes().map(Note::midi_pitch).collect::<Vec<_>>();
+
+        if self.query.tag.is_none() {
+            self.select(chord, Kind::Chord, &pitches, cx);
+        }
+    }
+
+    fn on_note(&mut self, note: &'a Note, cx: &VisitContext) {
+        if self.query.tag.is_none() {
+            self.select(note, Kind::Note, &[note.midi_pitch()], cx);
+        }
+    }
+
+    fn on_rest(&mut self, rest: &'a Rest, cx: &VisitContext) {
+        if self.query.tag.is_none() {
+            self.select(rest, Kind::Rest, &[], cx);
+        }
+    }
+
+    fn on_tag(&mut self, tag: &'a Tag, cx: &VisitContext) {
+        if self.query.tag.is_none_or(|id| id == tag.id) {
+            self.select(tag, Kind::Tag, &[], cx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCORE: &str =
+        "{ [ \\meter<\"2/4\"> c d \\slur(e f) | \\i<\"p\"> g _ ],
+  [ \\staff<2> \\slurBegin c0/2 \\slurEnd { e, g } a ] }";
+
+    fn texts(query: &Query) -> Result<Vec<String>> {
+        let score = Score::parse(SCORE)?;
+
+        Ok(query
+            .select(&score)
+            .iter()
+            .map(|m| m.location.text(SCORE).to_string())
+            .collect())
+    }
+
+    #[test]
+    fn kinds() -> Result<()> {
+        assert_eq!(texts(&Query::new().kind(Kind::Rest))?, vec!["_"]);
+        assert_eq!(texts(&Query::new().kind(Kind::Chord))?, vec!["{ e, g }"]);
+        assert_eq!(texts(&Query::new().kind(Kind::Note))?.len(), 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tags() -> Result<()> {
+        assert_eq!(
+            texts(&Query::new().tag(TagId::Intensity))?,
+            vec!["\\i<\"p\">"]
+        );
+        assert_eq!(
+            texts(&"\\slur".parse()?)?,
+            vec!["\\slur(e f)", "\\slurBegin", "\\slurEnd"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn filters() -> Result<()> {
+        assert_eq!(texts(&"note in=slur".parse()?)?, vec!["e", "f", "c0/2"]);
+        assert_eq!(
+            texts(&"note staff=2 time=0.5..".parse()?)?,
+            vec!["e", "g", "a"]
+        );
+        assert_eq!(texts(&"note measure=2..".parse()?)?, vec!["e", "f", "g"]);
+        assert_eq!(texts(&"note pitch=c1..64".parse()?)?, vec!["c", "d", "e"]);
+        assert_eq!(texts(&"chord pitch=g0".parse()?)?, vec!["{ e, g }"]);
+        assert_eq!(texts(&"voice=2".parse()?)?, Vec::<String>::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn custom_tags() -> Result<()> {
+        let mut defs = TagDefinitions::default();
+        defs.extend("cueText:\n  type: position\n  alternatives: [ ct ]")?;
+
+        let input = "[ c \\ct<\"solo\"> d \\slur(e f) ]";
+        let context = Context {
+            defs: defs.clone(),
+            ..Default::default()
+        };
+        let score = Score::parse_with(input, context)?;
+        let texts = |query: Query| {
+            query
+                .select(&score)
+                .iter()
+                .map(|m| m.location.text(input).to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            texts(Query::parse("\\cueText", &defs)?),
+            vec!["\\ct<\"solo\">"]
+        );
+        assert!("\\cueText".parse::<Query>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn locations() -> Result<()> {
+        let score = Score::parse(SCORE)?;
+        let matches = "rest".parse::<Query>()?.select(&score);
+
+        assert_eq!(matches[0].location.to_string(), "1:46");
+        assert_eq!(matches[0].measure, 3);
+        assert_eq!(matches[0].onset, 1.25);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("notes staff=x".parse::<Query>().is_err());
+        assert!("\\unknown".parse::<Query>().is_err());
+        assert!("pitch=h#9".parse::<Query>().is_err());
+        assert!("everything".parse::<Query>().is_err());
+    }
+}
@@ -0,0 +1,452 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{midi::MidiMessage, performance::Performance};
+
+/// Destination of the messages sent by a [`Scheduler`].
+pub trait MidiSink: Send {
+    fn send(&mut self, message: MidiMessage);
+}
+
+/// Plays a performance on a background thread.
+///
+/// The events of all staffs and voices are merged in a single time-ordered
+/// queue, and dispatched against a monotonic clock, so the caller is never
+/// blocked and can pause, seek or loop at any time.
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    transport: Mutex<Transport>,
+    wakeup: Condvar,
+}
+
+struct Transport {
+    sink: Box<dyn MidiSink>,
+    events: Vec<(f32, MidiMessage)>,
+    duration: f32,
+    next: usize,
+    /// Position in seconds when the clock was last started or stopped.
+    position: f32,
+    started: Option<Instant>,
+    range: Option<(f32, f32)>,
+    sounding: Vec<(u8, u8)>,
+    quit: bool,
+}
+
+impl Scheduler {
+    pub fn new(
+        performance: &Performance,
+        mut sink: impl MidiSink + 'static,
+    ) -> Self {
+        // Set up the instruments once, whatever position playback starts
+        // from, rather than again on every loop or seek to the start
+        for message in performance.program_changes() {
+            sink.send(message);
+        }
+
+        let events = performance
+            .messages()
+            .into_iter()
+            .filter(|(_, m)| !matches!(m, MidiMessage::ProgramChange { .. }))
+            .collect();
+
+        let transport = Transport {
+            sink: Box::new(sink),
+            events,
+            duration: performance.duration(),
+            next: 0,
+            position: 0.0,
+            started: None,
+            range: None,
+            sounding: Vec::new(),
+            quit: false,
+        };
+
+        let shared = Arc::new(Shared {
+            transport: Mutex::new(transport),
+            wakeup: Condvar::new(),
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            thread::spawn(move || run(&shared))
+        };
+
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn play(&self) {
+        let mut transport = self.lock();
+
+        if transport.started.is_none() {
+            if transport.position >= transport.duration {
+                transport.seek(0.0);
+            }
+
+            transport.started = Some(Instant::now());
+        }
+
+        self.shared.wakeup.notify_one();
+    }
+
+    pub fn pause(&self) {
+        let mut transport = self.lock();
+
+        transport.position = transport.position();
+        transport.started = None;
+        transport.silence();
+
+        self.shared.wakeup.notify_one();
+    }
+
+    pub fn stop(&self) {
+        self.pause();
+        self.seek(0.0);
+    }
+
+    /// Moves the playback to the given time in seconds.
+    pub fn seek(&self, seconds: f32) {
+        let mut transport = self.lock();
+
+        transport.seek(seconds);
+
+        self.shared.wakeup.notify_one();
+    }
+
+    /// Repeats the given range in seconds, or the whole performance.
+    pub fn set_loop(&self, range: Option<(f32, f32)>) {
+        let mut transport = self.lock();
+
+        transport.range = range.filter(|(start, end)| start < end);
+
+        self.shared.wakeup.notify_one();
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.lock().started.is_some()
+    }
+
+    /// Current position in seconds.
+    pub fn position(&self) -> f32 {
+        self.lock().position()
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.lock().duration
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Transport> {
+        self.shared.transport.lock().expect("Poisoned transport")
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        {
+            let mut transport = self.lock();
+            transport.quit = true;
+            transport.silence();
+        }
+
+        self.shared.wakeup.notify_one();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Transport {
+    fn position(&self) -> f32 {
+        match self.started {
+            Some(started) => self.position + started.elapsed().as_secs_f32(),
+            None => self.position,
+        }
+    }
+
+    fn seek(&mut self, seconds: f32) {
+        let seconds = seconds.clamp(0.0, self.duration);
+
+        self.silence();
+        self.next = self.events.partition_point(|(t, _)| *t < seconds);
+        self.position = seconds;
+
+        if self.started.is_some() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    fn silence(&mut self) {
+        for (channel, pitch) in self.sounding.drain(..) {
+            self.sink.send(MidiMessage::NoteOff { channel, pitch });
+        }
+    }
+
+    /// Sends all the events due by now, returning how long to wait for the
+    /// next one.
+    fn dispatch(&mut self) -> Option<Duration> {
+        self.started?;
+
+        let (loop_start, loop_end) = self.range.unwrap_or((0.0, self.duration));
+        let mut position = self.position();
+
+        if self.range.is_some() && position >= loop_end {
+            self.seek(loop_start);
+            position = loop_start;
+        }
+
+        while let Some((time, message)) = self.events.get(self.next).copied() {
+            if time > position {
+                break;
+            }
+
+            match message {
+                MidiMessage::NoteOn { channel, pitch, .. } => {
+                    self.sounding.push((channel, pitch))
+                },
+                MidiMessage::NoteOff { channel, pitch } => {
+                    if let Some(i) = self
+                        .sounding
+                        .iter()
+                        .position(|s| *s == (channel, pitch))
+                    {
+                        self.sounding.remove(i);
+                    }
+                },
+                MidiMessage::ProgramChange { .. } => {},
+            }
+
+            self.sink.send(message);
+            self.next += 1;
+        }
+
+        let until = match self.events.get(self.next) {
+            Some((time, _)) if self.range.is_none() || *time < loop_end => {
+                *time
+            },
+            _ if self.range.is_some() => loop_end,
+            _ => {
+                // Reached the end
+                self.position = self.duration;
+                self.started = None;
+                return None;
+            },
+        };
+
+        Some(Duration::from_secs_f32((until - position).max(0.0)))
+    }
+}
+
+fn run(shared: &Shared) {
+    let mut transport = shared.transport.lock().expect("Poisoned transport");
+
+    while !transport.quit {
+        transport = match transport.dispatch() {
+            Some(timeout) => {
+                shared
+                    .wakeup
+                    .wait_timeout(transport, timeout)
+                    .expect("Poisoned transport")
+                    .0
+            },
+            None => shared.wakeup.wait(transport).expect("Poisoned transport"),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{performance::PerformanceModel, score::Score};
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        messages: Arc<Mutex<Vec<MidiMessage>>>,
+    }
+
+    impl MidiSink for Recorder {
+        fn send(&mut self, message: MidiMessage) {
+            self.messages.lock().unwrap().push(message);
+        }
+    }
+
+    impl Recorder {
+        fn note_ons(&self) -> Vec<u8> {
+            self.messages
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|m| match m {
+                    MidiMessage::NoteOn { pitch, .. } => Some(*pitch),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    fn performance(input: &str) -> Result<Performance> {
+        let score = Score::parse(input)?;
+        let model = PerformanceModel {
+            tempo: 6000.0,
+            ..Default::default()
+        };
+
+        Ok(model.perform(&score))
+    }
+
+    /// Waits for the playback thread, with a deadline generous enough for
+    /// loaded machines.
+    fn wait_until(condition: impl Fn() -> bool) {
+        let start = Instant::now();
+
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(10), "Timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn plays_voices_together() -> Result<()> {
+        let recorder = Recorder::default();
+        let scheduler = Scheduler::new(
+            &performance("{ [ c/4 d ], [ { e0/4, g0 } a0 ] }")?,
+            recorder.clone(),
+        );
+
+        scheduler.play();
+        wait_until(|| !scheduler.is_playing());
+
+        assert_eq!(recorder.note_ons(), vec![60, 52, 55, 62, 57]);
+        assert_eq!(recorder.messages.lock().unwrap().len(), 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn programs_sent_once() -> Result<()> {
+        let recorder = Recorder::default();
+        let scheduler = Scheduler::new(
+            &performance("[ \\instr<\"Violin\"> c/4 d ]")?,
+            recorder.clone(),
+        );
+
+        scheduler.play();
+        wait_until(|| !scheduler.is_playing());
+        scheduler.seek(0.0);
+        scheduler.play();
+        wait_until(|| !scheduler.is_playing());
+
+        let messages = recorder.messages.lock().unwrap();
+        let programs = messages
+            .iter()
+            .filter(|m| matches!(m, MidiMessage::ProgramChange { .. }))
+            .count();
+
+        assert_eq!(
+            messages[0],
+            MidiMessage::ProgramChange {
+                channel: 0,
+                program: 40
+            }
+        );
+        assert_eq!(programs, 1);
+        assert_eq!(messages.len(), 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn paused_until_played() -> Result<()> {
+        let recorder = Recorder::default();
+        let scheduler =
+            Scheduler::new(&performance("[ c/4 ]")?, recorder.clone());
+
+        // Nothing may happen, so there is nothing to wait for
+        thread::sleep(Duration::from_millis(20));
+
+        assert!(!scheduler.is_playing());
+        assert_eq!(scheduler.position(), 0.0);
+        assert!(recorder.note_ons().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn seek_and_pause() -> Result<()> {
+        let recorder = Recorder::default();
+        let scheduler =
+            Scheduler::new(&performance("[ c/1 d e f ]")?, recorder.clone());
+
+        // Each whole note lasts 40ms
+        scheduler.seek(0.07);
+        scheduler.play();
+        wait_until(|| !scheduler.is_playing());
+
+        assert_eq!(recorder.note_ons(), vec![64, 65]);
+
+        scheduler.seek(0.05);
+        assert_eq!(scheduler.position(), 0.05);
+
+        scheduler.play();
+        scheduler.pause();
+        let position = scheduler.position();
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(scheduler.position(), position);
+
+        Ok(())
+    }
+
+    #[test]
+    fn silence_on_pause() -> Result<()> {
+        let recorder = Recorder::default();
+        // Long enough to still sound whenever the thread starts it
+        let model = PerformanceModel {
+            tempo: 60.0,
+            ..Default::default()
+        };
+        let long = model.perform(&Score::parse("[ c*8/1 ]")?);
+        let scheduler = Scheduler::new(&long, recorder.clone());
+
+        scheduler.play();
+        wait_until(|| !recorder.note_ons().is_empty());
+        scheduler.pause();
+
+        let messages = recorder.messages.lock().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(!messages[1].is_note_on());
+
+        Ok(())
+    }
+
+    #[test]
+    fn looping() -> Result<()> {
+        let recorder = Recorder::default();
+        let scheduler =
+            Scheduler::new(&performance("[ c/1 d e f ]")?, recorder.clone());
+
+        scheduler.set_loop(Some((0.0, 0.08)));
+        scheduler.play();
+        wait_until(|| recorder.note_ons().len() > 4);
+
+        assert!(scheduler.is_playing());
+        assert!(scheduler.position() < 0.08);
+
+        let notes = recorder.note_ons();
+        assert!(notes.len() > 4);
+        assert!(notes.iter().all(|p| *p == 60 || *p == 62));
+
+        Ok(())
+    }
+}
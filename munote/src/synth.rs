@@ -0,0 +1,279 @@
+use lazy_static::lazy_static;
+
+use crate::performance::{Performance, PerformedNote};
+
+const TABLE_SIZE: usize = 4096;
+/// Loudest sample of a mix, which is scaled down when notes add up beyond.
+const PEAK: f32 = 0.9;
+
+/// Linear attack, decay and release times in seconds, sustain as a level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            attack: 0.01,
+            decay: 0.1,
+            sustain: 0.7,
+            release: 0.2,
+        }
+    }
+}
+
+impl Envelope {
+    /// Level at `time` seconds after the onset of a note released after
+    /// `length` seconds.
+    pub fn level(&self, time: f32, length: f32) -> f32 {
+        if time < length {
+            return self.held_level(time);
+        }
+
+        let released = time - length;
+        if released >= self.release {
+            return 0.0;
+        }
+
+        self.held_level(length) * (1.0 - released / self.release)
+    }
+
+    fn held_level(&self, time: f32) -> f32 {
+        if time < self.attack {
+            time / self.attack
+        } else if time < self.attack + self.decay {
+            let progress = (time - self.attack) / self.decay;
+            1.0 - (1.0 - self.sustain) * progress
+        } else {
+            self.sustain
+        }
+    }
+}
+
+/// A small additive synthesizer rendering performances offline.
+///
+/// Everything is computed with plain arithmetic on lookup tables, so the
+/// same performance always renders to the same samples on every machine.
+#[derive(Clone, Debug)]
+pub struct Synth {
+    pub sample_rate: u32,
+    pub envelope: Envelope,
+    /// Relative amplitudes of the harmonics, starting from the fundamental.
+    pub partials: Vec<f32>,
+    pub gain: f32,
+}
+
+impl Default for Synth {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100,
+            envelope: Envelope::default(),
+            partials: vec![1.0, 0.5, 0.25, 0.125],
+            gain: 0.2,
+        }
+    }
+}
+
+impl Synth {
+    /// Renders a mono signal, mixing all the notes of the performance.
+    ///
+    /// Dense passages are scaled down as a whole to stay within [`PEAK`],
+    /// rather than clip.
+    pub fn render(&self, performance: &Performance) -> Vec<f32> {
+        let length = performance.duration() + self.envelope.release;
+        let mut samples = vec![0.0; self.samples(length)];
+
+        for note in &performance.notes {
+            self.render_note(note, &mut samples);
+        }
+
+        let peak = samples.iter().fold(0.0f32, |a, b| a.max(b.abs()));
+        if peak > PEAK {
+            let scale = PEAK / peak;
+            samples.iter_mut().for_each(|s| *s *= scale);
+        }
+
+        samples
+    }
+
+    fn render_note(&self, note: &PerformedNote, samples: &mut [f32]) {
+        let rate = self.sample_rate as f32;
+        let first = self.samples(note.start);
+        let length = note.end - note.start;
+        let count = self.samples(length + self.envelope.release);
+
+        let amplitude = self.gain * note.velocity as f32 / 127.0;
+        let frequency = frequency(note.pitch);
+
+        let increments = self
+            .partials
+            .iter()
+            .enumerate()
+            .map(|(i, amplitude)| {
+                let frequency = frequency * (i + 1) as f64;
+                let step = frequency / self.sample_rate as f64;
+                ((step * u32::MAX as f64) as u32, *amplitude)
+            })
+            // Harmonics above Nyquist would alias
+            .filter(|(step, _)| *step < u32::MAX / 2)
+            .collect::<Vec<_>>();
+
+        let mut phases = vec![0u32; increments.len()];
+
+        for (i, sample) in
+            samples.iter_mut().skip(first).take(count).enumerate()
+        {
+            let level = self.envelope.level(i as f32 / rate, length);
+
+            let mut value = 0.0;
+            for (phase, (step, partial)) in phases.iter_mut().zip(&increments) {
+                value += partial * sine(*phase);
+                *phase = phase.wrapping_add(*step);
+            }
+
+            *sample += value * level * amplitude;
+        }
+    }
+
+    fn samples(&self, seconds: f32) -> usize {
+        (seconds * self.sample_rate as f32).round() as usize
+    }
+}
+
+/// Equal temperament frequency of a MIDI pitch, with A4 at 440Hz.
+pub fn frequency(pitch: u8) -> f64 {
+    const SEMITONE: f64 = 1.059_463_094_359_295_3;
+
+    let mut frequency = 440.0;
+    let steps = pitch as i32 - 69;
+
+    for _ in 0..steps.abs() {
+        if steps > 0 {
+            frequency *= SEMITONE;
+        } else {
+            frequency /= SEMITONE;
+        }
+    }
+
+    frequency
+}
+
+/// Sine of a phase spanning the full `u32` range over one period.
+fn sine(phase: u32) -> f32 {
+    lazy_static! {
+        static ref TABLE: Vec<f32> = (0..=TABLE_SIZE)
+            .map(|i| taylor_sine(i as f64 / TABLE_SIZE as f64))
+            .collect();
+    }
+
+    let position = phase as f64 / (u32::MAX as f64 + 1.0) * TABLE_SIZE as f64;
+    let index = position as usize;
+    let fraction = (position - index as f64) as f32;
+
+    TABLE[index] + (TABLE[index + 1] - TABLE[index]) * fraction
+}
+
+/// Platform-independent sine of a fraction of a period.
+fn taylor_sine(fraction: f64) -> f32 {
+    // Bring the angle into [-pi, pi], where the series converges quickly
+    let x = (fraction - (fraction + 0.5).floor()) * 2.0 * std::f64::consts::PI;
+
+    let mut term = x;
+    let mut sum = x;
+    for n in 1..20 {
+        term *= -x * x / ((2 * n) * (2 * n + 1)) as f64;
+        sum += term;
+    }
+
+    sum as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{performance::PerformanceModel, score::Score};
+
+    fn render(input: &str) -> Result<Vec<f32>> {
+        let score = Score::parse(input)?;
+        let performance = PerformanceModel::default().perform(&score);
+
+        Ok(Synth::default().render(&performance))
+    }
+
+    #[test]
+    fn frequencies() {
+        assert_eq!(frequency(69), 440.0);
+        assert!((frequency(81) - 880.0).abs() < 1e-9);
+        assert!((frequency(60) - 261.625_565).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sine_table() {
+        assert_eq!(sine(0), 0.0);
+        assert!((sine(u32::MAX / 4) - 1.0).abs() < 1e-5);
+        assert!((sine(u32::MAX / 4 * 3) + 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn envelope() {
+        let envelope = Envelope::default();
+
+        assert_eq!(envelope.level(0.0, 1.0), 0.0);
+        assert_eq!(envelope.level(0.01, 1.0), 1.0);
+        assert!((envelope.level(0.5, 1.0) - 0.7).abs() < 1e-6);
+        assert!((envelope.level(1.1, 1.0) - 0.35).abs() < 1e-6);
+        assert_eq!(envelope.level(1.2, 1.0), 0.0);
+    }
+
+    #[test]
+    fn length() -> Result<()> {
+        // A quarter at 120 BPM plus the release
+        let samples = render("[ a1/4 ]")?;
+
+        assert_eq!(samples.len(), (0.65 * 44100.0) as usize);
+        assert!(samples.last().unwrap().abs() < 1e-3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn polyphony() -> Result<()> {
+        let single = render("[ c/4 ]")?;
+        let chord = render("[ { c/4, e, g } ]")?;
+
+        let peak = |s: &[f32]| s.iter().fold(0.0f32, |a, b| a.max(b.abs()));
+
+        assert_eq!(single.len(), chord.len());
+        assert!(peak(&chord) > peak(&single));
+
+        Ok(())
+    }
+
+    #[test]
+    fn no_clipping() -> Result<()> {
+        let chords = "\\i<\"ff\"> { c-1*4/1, g-1, c, e, g, c1, e1, g1 }";
+        let samples = render(&format!("{{ [ {chords} ], [ {chords} ] }}"))?;
+
+        let peak = samples.iter().fold(0.0f32, |a, b| a.max(b.abs()));
+
+        assert!(peak < 1.0, "Clipped at {peak}");
+        assert!(peak > PEAK / 2.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic() -> Result<()> {
+        let input = "{ [ \\i<\"p\"> \\cresc(c/8 d e f) g/4 ], [ c0/2 ] }";
+
+        assert_eq!(render(input)?, render(input)?);
+
+        Ok(())
+    }
+}
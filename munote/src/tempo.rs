@@ -0,0 +1,207 @@
+use std::{fmt, ops::Range};
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+use crate::{duration::Duration, tag::Tag, tag_id::TagId, tag_param::TagParam};
+
+/// A tempo marking, like the ones written with
+/// `\tempo<"Moderato [1/4] = 90">` or `\tempo<"Allegro", "1/4=120">`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tempo {
+    /// Text of the marking without its metronome mark, like `Moderato`.
+    pub text: String,
+    pub mark: Option<Metronome>,
+}
+
+/// How fast the beats go, as given by a metronome mark.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metronome {
+    /// So many beats per minute, like `[1/4.] = 60`.
+    Bpm { beat: Duration, bpm: f32 },
+    /// Beats lasting as long as other beats did before, like
+    /// `[3/8] = [1/4]`.
+    Equivalence { beat: Duration, previous: Duration },
+}
+
+impl Tempo {
+    /// The marking of a `\tempo` tag, whose metronome mark is either part of
+    /// its text or its second parameter.
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        if tag.id != TagId::Tempo {
+            return None;
+        }
+
+        let text = strings(tag).next()?;
+        let mark = Metronome::from_tag(tag);
+        let text = match Metronome::locate(text) {
+            Some((_, range)) => {
+                format!("{} {}", &text[..range.start], &text[range.end..])
+            },
+            None => text.to_string(),
+        };
+
+        Some(Self {
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            mark,
+        })
+    }
+}
+
+impl Metronome {
+    /// The last metronome mark of the string parameters of a tag, like the
+    /// tempo reached by `\accel<"[1/4] = 60", "[1/4] = 90">`.
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        strings(tag)
+            .filter_map(|s| Self::locate(s).map(|(mark, _)| mark))
+            .last()
+    }
+
+    /// Quarter notes per minute from the mark on, given the ones before.
+    pub fn quarters_per_minute(&self, previous: f32) -> f32 {
+        match self {
+            Self::Bpm { beat, bpm } => bpm * beat.as_f32() * 4.0,
+            Self::Equivalence {
+                beat,
+                previous: then,
+            } => previous * beat.as_f32() / then.as_f32(),
+        }
+    }
+
+    /// The first metronome mark of a text, with where it is written.
+    fn locate(text: &str) -> Option<(Self, Range<usize>)> {
+        lazy_static! {
+            static ref MARK: Regex = Regex::new(
+                r"\[?\s*(\d+)\s*/\s*(\d+)\s*(\.*)\s*\]?\s*=\s*(?:\[?\s*(\d+)\s*/\s*(\d+)\s*(\.*)\s*\]?|(\d+(?:\.\d+)?))"
+            )
+            .unwrap();
+        }
+
+        let captures = MARK.captures(text)?;
+        let beat = beat(&captures, 1)?;
+        let mark = match captures.get(7) {
+            Some(bpm) => Self::Bpm {
+                beat,
+                bpm: bpm.as_str().parse().ok()?,
+            },
+            None => Self::Equivalence {
+                beat,
+                previous: self::beat(&captures, 4)?,
+            },
+        };
+
+        Some((mark, captures.get(0)?.range()))
+    }
+}
+
+impl fmt::Display for Metronome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bpm { beat, bpm } => {
+                write!(f, "[{}/{}] = {bpm}", beat.num, beat.denom)
+            },
+            Self::Equivalence { beat, previous } => write!(
+                f,
+                "[{}/{}] = [{}/{}]",
+                beat.num, beat.denom, previous.num, previous.denom
+            ),
+        }
+    }
+}
+
+/// The positional and named string parameters of a tag.
+fn strings(tag: &Tag) -> impl Iterator<Item = &str> {
+    tag.params.iter().filter_map(|p| match p {
+        TagParam::String(s) | TagParam::VarString(_, s) => Some(s.as_str()),
+        _ => None,
+    })
+}
+
+/// The duration written from the `i`-th capture on, like `1/4.`.
+fn beat(captures: &Captures, i: usize) -> Option<Duration> {
+    let num = captures.get(i)?.as_str().parse::<u8>().ok()?;
+    let denom = captures.get(i + 1)?.as_str().parse::<u8>().ok()?;
+    let dots = captures.get(i + 2).map_or(0, |d| d.as_str().len() as u32);
+
+    // Each dot adds half of the previous value
+    let num = num.checked_mul(2u8.checked_pow(dots + 1)? - 1)?;
+    let denom = denom.checked_mul(2u8.checked_pow(dots)?)?;
+
+    (denom > 0).then(|| Duration::new(num, denom))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::score::Score;
+
+    use super::*;
+
+    fn tempo(input: &str) -> Result<Option<Tempo>> {
+        let score = Score::parse(&format!("[ {input} c ]"))?;
+        let event = &score.staffs[0].voices[0].events[0];
+
+        Ok(event
+            .as_any()
+            .downcast_ref::<Tag>()
+            .and_then(Tempo::from_tag))
+    }
+
+    fn bpm(num: u8, denom: u8, bpm: f32) -> Option<Metronome> {
+        Some(Metronome::Bpm {
+            beat: Duration::new(num, denom),
+            bpm,
+        })
+    }
+
+    #[test]
+    fn markings() -> Result<()> {
+        let marking = tempo("\\tempo<\"Moderato [1/4] = 90\", fsize=13pt>")?;
+        assert_eq!(
+            marking,
+            Some(Tempo {
+                text: "Moderato".to_string(),
+                mark: bpm(1, 4, 90.0),
+            })
+        );
+
+        let marking = tempo("\\tempo<\"Allegro\", \"1/4=120\">")?.unwrap();
+        assert_eq!(marking.text, "Allegro");
+        assert_eq!(marking.mark, bpm(1, 4, 120.0));
+
+        let marking = tempo("\\tempo<\"Adagio\">")?.unwrap();
+        assert_eq!(marking.mark, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn metronome_marks() -> Result<()> {
+        let mark = |input: &str| tempo(input).map(|t| t.and_then(|t| t.mark));
+
+        assert_eq!(mark("\\tempo<\"[1/4.] = 60\">")?, bpm(3, 8, 60.0));
+        assert_eq!(mark("\\tempo<\"[1/8..]=50\">")?, bpm(7, 32, 50.0));
+        assert_eq!(
+            mark("\\tempo<\"[3/8] = [1/4]\">")?,
+            Some(Metronome::Equivalence {
+                beat: Duration::new(3, 8),
+                previous: Duration::new(1, 4),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn quarters_per_minute() {
+        assert_eq!(bpm(3, 8, 100.0).unwrap().quarters_per_minute(60.0), 150.0);
+        assert_eq!(bpm(1, 2, 40.0).unwrap().quarters_per_minute(60.0), 80.0);
+
+        let faster = Metronome::Equivalence {
+            beat: Duration::new(3, 8),
+            previous: Duration::new(1, 4),
+        };
+        assert_eq!(faster.quarters_per_minute(60.0), 90.0);
+    }
+}
@@ -1,18 +1,703 @@
 use crate::chord::Chord;
+use crate::clef::Clef;
+use crate::event::Event;
+use crate::grace::Grace;
 use crate::note::Note;
-use crate::ptr::Ptr;
 use crate::rest::Rest;
-use crate::tag::Tag;
+use crate::tag::{Tag, TagType};
+use crate::tag_id::TagId;
 use crate::voice::Voice;
 
-pub trait Visitor {
-    fn on_chord(&mut self, chord: &Chord);
-    fn on_note(&mut self, note: &Note);
-    fn on_rest(&mut self, rest: &Rest);
-    fn on_staff_begin(&mut self);
-    fn on_staff_end(&mut self);
-    fn on_tag(&mut self, tag: &Tag);
-    fn on_voice(&mut self, voice: &Voice);
+/// Where the traversal is in the score.
+#[derive(Clone, Debug, Default)]
+pub struct VisitContext {
+    /// Number of the current staff.
+    pub staff: u8,
+    /// Index of the current voice in its staff.
+    pub voice: usize,
+    /// Time of the current event from the start of the voice, in whole notes.
+    pub onset: f32,
+    /// Range tags and open `Begin` tags around the current event, outermost
+    /// first, without their nested events.
+    pub tags: Vec<Tag>,
+    /// Number of the current measure, from 1.
+    pub measure: u32,
+    /// Onset of the current measure.
+    pub measure_onset: f32,
+    /// Clef given by the last `\clef`, a treble clef before any.
+    pub clef: Clef,
+    /// Length of the measures given by the last `\meter`.
+    meter: Option<f32>,
+    /// Octaves given by the last `\octava` position tag.
+    octava: i8,
+    chords: usize,
 }
 
-pub type VisitorPtr = Ptr<Box<dyn Visitor>>;
+impl VisitContext {
+    pub fn new(staff: u8, voice: usize) -> Self {
+        Self {
+            staff,
+            voice,
+            measure: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Innermost enclosing tag with the given id.
+    pub fn enclosing(&self, id: TagId) -> Option<&Tag> {
+        self.tags.iter().rev().find(|t| t.id == id)
+    }
+
+    pub fn is_within(&self, id: TagId) -> bool {
+        self.enclosing(id).is_some()
+    }
+
+    /// How the current event is played, when it is a grace note.
+    pub fn grace(&self) -> Option<Grace> {
+        self.enclosing(TagId::Grace).and_then(Grace::from_tag)
+    }
+
+    /// Whether the current event is part of a cue passage, drawn smaller
+    /// and optionally silent.
+    pub fn is_cue(&self) -> bool {
+        self.is_within(TagId::Cue)
+    }
+
+    /// Octaves the current event sounds above its written pitch, from the
+    /// innermost enclosing `\octava` or the last one before it.
+    pub fn octava(&self) -> i8 {
+        self.enclosing(TagId::Octava).map_or(self.octava, octaves)
+    }
+
+    /// Where a note is written on the staff, with the current clef and
+    /// `\octava`.
+    pub fn staff_position(&self, note: &Note) -> i32 {
+        self.clef.staff_position(note, self.octava())
+    }
+
+    /// Whether the current event is part of a chord.
+    pub fn in_chord(&self) -> bool {
+        self.chords > 0
+    }
+
+    /// Moves to the measures an event starts in, when the meter implies
+    /// barlines before it.
+    fn start(&mut self) {
+        let Some(length) = self.meter.filter(|l| *l > 0.0) else {
+            return;
+        };
+
+        while self.onset >= self.measure_onset + length - f32::EPSILON {
+            self.measure += 1;
+            self.measure_onset += length;
+        }
+    }
+
+    /// Follows the barlines and meter changes.
+    fn read(&mut self, tag: &Tag) {
+        match tag.id {
+            TagId::Bar
+            | TagId::DoubleBar
+            | TagId::EndBar
+            | TagId::RepeatBegin
+            | TagId::RepeatEnd => {
+                self.start();
+
+                // Unless the meter already implied it
+                if self.onset > self.measure_onset + f32::EPSILON {
+                    self.measure += 1;
+                    self.measure_onset = self.onset;
+                }
+            },
+            TagId::Meter => {
+                self.meter = tag.as_str().and_then(meter_length);
+                self.measure_onset = self.onset.max(self.measure_onset);
+            },
+            TagId::Clef => {
+                if let Some(clef) = Clef::from_tag(tag) {
+                    self.clef = clef;
+                }
+            },
+            TagId::Octava if matches!(tag.ty, TagType::Position) => {
+                self.octava = octaves(tag);
+            },
+            _ => {},
+        }
+    }
+
+    /// Length in whole notes of the current measures, 4/4 without a meter.
+    pub fn measure_length(&self) -> f32 {
+        self.meter.filter(|l| *l > 0.0).unwrap_or(1.0)
+    }
+
+    /// Makes a `\mrest` take its measures from `onset`, whatever it
+    /// encloses.
+    fn rest_measures(&mut self, tag: &Tag, onset: f32) {
+        let Some(measures) = tag.rest_measures() else {
+            return;
+        };
+
+        if !self.in_chord() {
+            self.onset = onset + measures as f32 * self.measure_length();
+            self.measure += measures;
+            self.measure_onset = self.onset;
+        }
+    }
+
+    fn advance(&mut self, duration: f32) {
+        // The notes of a chord all start with it, and grace notes take no
+        // time
+        if !self.in_chord() && !self.is_within(TagId::Grace) {
+            self.onset += duration;
+        }
+    }
+
+    fn enter(&mut self, tag: &Tag) {
+        self.tags.push(header(tag));
+    }
+
+    fn exit(&mut self) {
+        self.tags.pop();
+    }
+
+    /// Keeps track of `Begin`/`End` pairs, which enclose the events between
+    /// them like range tags.
+    fn mark(&mut self, tag: &Tag) {
+        match tag.ty {
+            TagType::Begin(_) => self.enter(tag),
+            TagType::End(suffix) => {
+                if let Some(i) = self.tags.iter().rposition(|t| {
+                    t.id == tag.id
+                        && matches!(t.ty, TagType::Begin(s) if s == suffix)
+                }) {
+                    self.tags.remove(i);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Length in whole notes of the measures of a meter like "3/4", "C" or
+/// "2+3+3/8".
+pub(crate) fn meter_length(meter: &str) -> Option<f32> {
+    match meter {
+        "C" | "C/" => return Some(1.0),
+        _ => {},
+    }
+
+    let (beats, unit) = meter.split_once('/')?;
+    let beats = beats
+        .split('+')
+        .map(|b| b.trim().parse::<f32>().ok())
+        .sum::<Option<f32>>()?;
+    let unit = unit.trim().parse::<f32>().ok()?;
+
+    Some(beats / unit)
+}
+
+/// Octaves of an `\octava` tag, like 1 for `\octava<1>`.
+fn octaves(tag: &Tag) -> i8 {
+    tag.get_number("i").or_else(|| tag.as_number()).unwrap_or(0.0) as i8
+}
+
+fn header(tag: &Tag) -> Tag {
+    Tag::new(tag.id, tag.ty, tag.params.clone(), Vec::new())
+}
+
+/// Read-only traversal of a score.
+///
+/// Nested events of chords and range tags are visited between the matching
+/// enter and exit hooks. Every method does nothing by default, and the
+/// visited events can be kept for as long as the score.
+pub trait Visitor<'a> {
+    fn on_chord(&mut self, _chord: &'a Chord, _cx: &VisitContext) {}
+    fn on_chord_end(&mut self, _chord: &'a Chord, _cx: &VisitContext) {}
+    fn on_note(&mut self, _note: &'a Note, _cx: &VisitContext) {}
+    fn on_rest(&mut self, _rest: &'a Rest, _cx: &VisitContext) {}
+    fn on_staff_begin(&mut self, _cx: &VisitContext) {}
+    fn on_staff_end(&mut self, _cx: &VisitContext) {}
+    fn on_tag(&mut self, _tag: &'a Tag, _cx: &VisitContext) {}
+    fn on_tag_end(&mut self, _tag: &'a Tag, _cx: &VisitContext) {}
+    fn on_voice(&mut self, _voice: &'a Voice, _cx: &VisitContext) {}
+    fn on_voice_end(&mut self, _voice: &'a Voice, _cx: &VisitContext) {}
+}
+
+/// Traversal changing the events in place.
+///
+/// Durations are read after the hooks ran, so the onsets that follow take
+/// the changes into account.
+pub trait VisitorMut {
+    fn on_chord(&mut self, _chord: &mut Chord, _cx: &VisitContext) {}
+    fn on_chord_end(&mut self, _chord: &mut Chord, _cx: &VisitContext) {}
+    fn on_note(&mut self, _note: &mut Note, _cx: &VisitContext) {}
+    fn on_rest(&mut self, _rest: &mut Rest, _cx: &VisitContext) {}
+    fn on_staff_begin(&mut self, _cx: &VisitContext) {}
+    fn on_staff_end(&mut self, _cx: &VisitContext) {}
+    fn on_tag(&mut self, _tag: &mut Tag, _cx: &VisitContext) {}
+    fn on_tag_end(&mut self, _tag: &mut Tag, _cx: &VisitContext) {}
+    fn on_voice(&mut self, _voice: &mut Voice, _cx: &VisitContext) {}
+    fn on_voice_end(&mut self, _voice: &mut Voice, _cx: &VisitContext) {}
+}
+
+/// Rewrites the event sequences of a score, bottom up.
+pub trait Fold {
+    /// Replaces an event, whose nested events were already folded, with any
+    /// number of events.
+    fn fold_event(
+        &mut self,
+        event: Box<dyn Event>,
+        _cx: &VisitContext,
+    ) -> Vec<Box<dyn Event>> {
+        vec![event]
+    }
+}
+
+/// Implemented by the events, to be walked by visitors.
+pub trait Visitable {
+    fn accept<'a>(&'a self, visitor: &mut dyn Visitor<'a>, cx: &mut VisitContext);
+
+    fn accept_mut(
+        &mut self,
+        visitor: &mut dyn VisitorMut,
+        cx: &mut VisitContext,
+    );
+}
+
+impl Visitable for Note {
+    fn accept<'a>(
+        &'a self,
+        visitor: &mut dyn Visitor<'a>,
+        cx: &mut VisitContext,
+    ) {
+        cx.start();
+        visitor.on_note(self, cx);
+        cx.advance(self.full_duration().as_f32());
+    }
+
+    fn accept_mut(
+        &mut self,
+        visitor: &mut dyn VisitorMut,
+        cx: &mut VisitContext,
+    ) {
+        cx.start();
+        visitor.on_note(self, cx);
+        cx.advance(self.full_duration().as_f32());
+    }
+}
+
+impl Visitable for Rest {
+    fn accept<'a>(
+        &'a self,
+        visitor: &mut dyn Visitor<'a>,
+        cx: &mut VisitContext,
+    ) {
+        cx.start();
+        visitor.on_rest(self, cx);
+        cx.advance(self.full_duration().as_f32());
+    }
+
+    fn accept_mut(
+        &mut self,
+        visitor: &mut dyn VisitorMut,
+        cx: &mut VisitContext,
+    ) {
+        cx.start();
+        visitor.on_rest(self, cx);
+        cx.advance(self.full_duration().as_f32());
+    }
+}
+
+impl Visitable for Chord {
+    fn accept<'a>(
+        &'a self,
+        visitor: &mut dyn Visitor<'a>,
+        cx: &mut VisitContext,
+    ) {
+        cx.start();
+        visitor.on_chord(self, cx);
+
+        cx.chords += 1;
+        for symbol in &self.symbols {
+            symbol.visit(visitor, cx);
+        }
+        cx.chords -= 1;
+
+        visitor.on_chord_end(self, cx);
+        cx.advance(self.full_duration().as_f32());
+    }
+
+    fn accept_mut(
+        &mut self,
+        visitor: &mut dyn VisitorMut,
+        cx: &mut VisitContext,
+    ) {
+        cx.start();
+        visitor.on_chord(self, cx);
+
+        cx.chords += 1;
+        for symbol in &mut self.symbols {
+            symbol.visit_mut(visitor, cx);
+        }
+        cx.chords -= 1;
+
+        visitor.on_chord_end(self, cx);
+        cx.advance(self.full_duration().as_f32());
+    }
+}
+
+impl Visitable for Tag {
+    fn accept<'a>(
+        &'a self,
+        visitor: &mut dyn Visitor<'a>,
+        cx: &mut VisitContext,
+    ) {
+        cx.mark(self);
+        cx.read(self);
+
+        let onset = cx.onset;
+        if self.rest_measures().is_some() {
+            cx.start();
+        }
+
+        visitor.on_tag(self, cx);
+
+        if !self.events.is_empty() {
+            cx.enter(self);
+            for event in &self.events {
+                event.visit(visitor, cx);
+            }
+            cx.exit();
+        }
+
+        cx.rest_measures(self, onset);
+
+        visitor.on_tag_end(self, cx);
+    }
+
+    fn accept_mut(
+        &mut self,
+        visitor: &mut dyn VisitorMut,
+        cx: &mut VisitContext,
+    ) {
+        cx.mark(self);
+        cx.read(self);
+
+        let onset = cx.onset;
+        if self.rest_measures().is_some() {
+            cx.start();
+        }
+
+        visitor.on_tag(self, cx);
+
+        if !self.events.is_empty() {
+            cx.enter(self);
+            for event in &mut self.events {
+                event.visit_mut(visitor, cx);
+            }
+            cx.exit();
+        }
+
+        cx.rest_measures(self, onset);
+
+        visitor.on_tag_end(self, cx);
+    }
+}
+
+/// Folds a sequence of events, keeping `cx` on the onset of each of them.
+pub fn fold_events(
+    events: Vec<Box<dyn Event>>,
+    folder: &mut dyn Fold,
+    cx: &mut VisitContext,
+) -> Vec<Box<dyn Event>> {
+    let mut folded = Vec::with_capacity(events.len());
+
+    for mut event in events {
+        let any = event.as_any_mut();
+        let onset = cx.onset;
+
+        // Tags start with the next event, but multi-measure rests
+        let tag = any.downcast_ref::<Tag>();
+        if tag.is_none_or(|t| t.rest_measures().is_some()) {
+            cx.start();
+        }
+
+        if let Some(tag) = any.downcast_mut::<Tag>() {
+            cx.mark(tag);
+            cx.read(tag);
+
+            if !tag.events.is_empty() {
+                cx.enter(tag);
+                tag.events =
+                    fold_events(std::mem::take(&mut tag.events), folder, cx);
+                cx.exit();
+            }
+        } else if let Some(chord) = any.downcast_mut::<Chord>() {
+            cx.chords += 1;
+            chord.symbols =
+                fold_events(std::mem::take(&mut chord.symbols), folder, cx);
+            cx.chords -= 1;
+        }
+
+        // Nested events were folded from the same onset
+        cx.onset = onset;
+
+        for event in folder.fold_event(event, cx) {
+            match event.as_any().downcast_ref::<Tag>() {
+                Some(tag) if tag.rest_measures().is_some() => {
+                    cx.rest_measures(tag, cx.onset)
+                },
+                _ => cx.advance(duration(event.as_ref(), cx.measure_length())),
+            }
+
+            folded.push(event);
+        }
+    }
+
+    folded
+}
+
+/// Time taken by an event in a sequence, in whole notes, for measures of
+/// the given length.
+fn duration(event: &dyn Event, measure: f32) -> f32 {
+    let any = event.as_any();
+
+    if let Some(note) = any.downcast_ref::<Note>() {
+        note.full_duration().as_f32()
+    } else if let Some(rest) = any.downcast_ref::<Rest>() {
+        rest.full_duration().as_f32()
+    } else if let Some(chord) = any.downcast_ref::<Chord>() {
+        chord.full_duration().as_f32()
+    } else if let Some(tag) = any.downcast_ref::<Tag>() {
+        match tag.rest_measures() {
+            _ if tag.id == TagId::Grace => 0.0,
+            Some(measures) => measures as f32 * measure,
+            None => {
+                tag.events.iter().map(|e| duration(e.as_ref(), measure)).sum()
+            },
+        }
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{duration::Duration, note::Diatonic, score::Score};
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl Visitor<'_> for Recorder {
+        fn on_chord(&mut self, _: &Chord, cx: &VisitContext) {
+            self.events.push(format!("chord@{}", cx.onset));
+        }
+
+        fn on_chord_end(&mut self, _: &Chord, _: &VisitContext) {
+            self.events.push("/chord".into());
+        }
+
+        fn on_note(&mut self, note: &Note, cx: &VisitContext) {
+            let tags = cx.tags.iter().map(|t| format!("{:?}", t.id));
+
+            self.events.push(format!(
+                "{:?}@{}:{}.{}{}",
+                note.name,
+                cx.onset,
+                cx.staff,
+                cx.voice,
+                tags.map(|t| format!(" {t}")).collect::<String>(),
+            ));
+        }
+
+        fn on_tag(&mut self, tag: &Tag, _: &VisitContext) {
+            self.events.push(format!("{:?}", tag.id));
+        }
+
+        fn on_tag_end(&mut self, tag: &Tag, _: &VisitContext) {
+            self.events.push(format!("/{:?}", tag.id));
+        }
+    }
+
+    #[test]
+    fn traversal() -> Result<()> {
+        let score = Score::parse(
+            "{ [ c/4 \\slur(d { e, g }) \\tieBegin f \\tieEnd a ], [ \\staff<2> b ] }",
+        )?;
+
+        let mut recorder = Recorder::default();
+        score.visit(&mut recorder);
+
+        assert_eq!(
+            recorder.events,
+            vec![
+                "Diatonic(C)@0:1.0",
+                "Slur",
+                "Diatonic(D)@0.25:1.0 Slur",
+                "chord@0.5",
+                "Diatonic(E)@0.5:1.0 Slur",
+                "Diatonic(G)@0.5:1.0 Slur",
+                "/chord",
+                "/Slur",
+                "Tie",
+                "/Tie",
+                "Diatonic(F)@0.75:1.0 Tie",
+                "Tie",
+                "/Tie",
+                "Diatonic(A)@1:1.0",
+                "Staff",
+                "/Staff",
+                "Diatonic(B)@0:2.0",
+            ]
+        );
+
+        Ok(())
+    }
+
+    struct Transpose;
+
+    impl VisitorMut for Transpose {
+        fn on_note(&mut self, note: &mut Note, _: &VisitContext) {
+            note.octave += 1;
+            note.duration = Duration::new(1, 8);
+        }
+    }
+
+    #[derive(Default)]
+    struct Measures {
+        measures: Vec<u32>,
+    }
+
+    impl Visitor<'_> for Measures {
+        fn on_note(&mut self, _: &Note, cx: &VisitContext) {
+            self.measures.push(cx.measure);
+        }
+    }
+
+    #[test]
+    fn measures() -> Result<()> {
+        let measures = |input| -> Result<Vec<u32>> {
+            let mut visitor = Measures::default();
+            Score::parse(input)?.visit(&mut visitor);
+            Ok(visitor.measures)
+        };
+
+        assert_eq!(measures("[ c d | e f | g ]")?, vec![1, 1, 2, 2, 3]);
+        assert_eq!(
+            measures("[ \\meter<\"2/4\"> c d e f | g/2 a/4 ]")?,
+            vec![1, 1, 2, 2, 3, 4]
+        );
+        assert_eq!(
+            measures("[ \\meter<\"3/4\"> c | d e f | g ]")?,
+            vec![1, 2, 2, 2, 3]
+        );
+        assert_eq!(
+            measures("[ \\meter<\"3/4\"> c/2. \\mrest<3> | d ]")?,
+            vec![1, 5]
+        );
+        assert_eq!(measures("[ c/1 | \\mrest<2>(_/1 _) | d ]")?, vec![1, 4]);
+        assert_eq!(measures("[ \\meter<\"1/4\"> c \\grace(d/8) e ]")?, vec![
+            1, 2, 2
+        ]);
+
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct Positions {
+        positions: Vec<i32>,
+    }
+
+    impl Visitor<'_> for Positions {
+        fn on_note(&mut self, note: &Note, cx: &VisitContext) {
+            self.positions.push(cx.staff_position(note));
+        }
+    }
+
+    #[test]
+    fn staff_positions() -> Result<()> {
+        let mut visitor = Positions::default();
+        Score::parse(
+            "[ e1 \\clef<\"f\"> e \\octava<1>(e2) e2 \\clef<\"g\"> \\oct<1> e2
+               \\octava<0> e1 ]",
+        )?
+        .visit(&mut visitor);
+
+        assert_eq!(visitor.positions, [0, 12, 12, 19, 0, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn meter_lengths() {
+        assert_eq!(meter_length("3/4"), Some(0.75));
+        assert_eq!(meter_length("C"), Some(1.0));
+        assert_eq!(meter_length("2+3+3/8"), Some(1.0));
+        assert_eq!(meter_length("free"), None);
+    }
+
+    #[test]
+    fn visit_mut() -> Result<()> {
+        let mut score = Score::parse("[ c \\slur(d e) ]")?;
+        score.visit_mut(&mut Transpose);
+
+        let mut recorder = Recorder::default();
+        score.visit(&mut recorder);
+
+        assert_eq!(recorder.events[3], "Diatonic(E)@0.25:1.0 Slur");
+
+        let voice = &score.staffs[0].voices[0];
+        assert!(voice.events[0].equals(
+            &Note::from_name(Diatonic::C)
+                .with_octave(2)
+                .with_duration(1, 8)
+        ));
+
+        Ok(())
+    }
+
+    /// Doubles every note, and drops the rests.
+    struct Double {
+        onsets: Vec<f32>,
+    }
+
+    impl Fold for Double {
+        fn fold_event(
+            &mut self,
+            event: Box<dyn Event>,
+            cx: &VisitContext,
+        ) -> Vec<Box<dyn Event>> {
+            if event.as_any().is::<Rest>() {
+                return vec![];
+            }
+
+            if event.as_any().is::<Note>() {
+                self.onsets.push(cx.onset);
+                return vec![event.clone(), event];
+            }
+
+            vec![event]
+        }
+    }
+
+    #[test]
+    fn fold() -> Result<()> {
+        let mut score = Score::parse("[ c _ \\slur(d) e ]")?;
+        let mut folder = Double { onsets: vec![] };
+        score.fold(&mut folder);
+
+        let voice = &score.staffs[0].voices[0];
+        assert_eq!(voice.events.len(), 5);
+        assert_eq!(folder.onsets, vec![0.0, 0.5, 1.0]);
+
+        let slur = voice.events[2].as_any().downcast_ref::<Tag>().unwrap();
+        assert_eq!(slur.events.len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::{fs::File, io::Write, path::Path};
+
+use anyhow::Result;
+
+/// Writes mono samples in [-1, 1] as a 16-bit PCM WAV file.
+pub fn write_wav(
+    samples: &[f32],
+    sample_rate: u32,
+    out: &mut impl Write,
+) -> Result<()> {
+    const CHANNELS: u16 = 1;
+    const BITS: u16 = 16;
+
+    let block_align = CHANNELS * BITS / 8;
+    let data_len = samples.len() as u32 * block_align as u32;
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?; // PCM
+    out.write_all(&CHANNELS.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    out.write_all(&block_align.to_le_bytes())?;
+    out.write_all(&BITS.to_le_bytes())?;
+
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+
+    let data = samples
+        .iter()
+        .flat_map(|s| {
+            ((s.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)
+                .to_le_bytes()
+        })
+        .collect::<Vec<_>>();
+    out.write_all(&data)?;
+
+    Ok(())
+}
+
+pub fn save_wav(samples: &[f32], sample_rate: u32, path: &Path) -> Result<()> {
+    let mut file = File::create(path)?;
+
+    write_wav(samples, sample_rate, &mut file)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{performance::PerformanceModel, score::Score, synth::Synth};
+
+    /// FNV-1a, stable unlike the hashers of the standard library.
+    fn hash(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    #[test]
+    fn header_and_data() -> Result<()> {
+        let mut out = Vec::new();
+        write_wav(&[0.0, 1.0, -1.0, 2.0], 8000, &mut out)?;
+
+        assert_eq!(out.len(), 44 + 8);
+        assert_eq!(&out[0..4], b"RIFF");
+        assert_eq!(&out[4..8], &44u32.to_le_bytes());
+        assert_eq!(&out[8..16], b"WAVEfmt ");
+        assert_eq!(&out[24..28], &8000u32.to_le_bytes());
+        assert_eq!(&out[36..40], b"data");
+        assert_eq!(&out[40..44], &8u32.to_le_bytes());
+        assert_eq!(
+            &out[44..],
+            &[0x00, 0x00, 0xff, 0x7f, 0x01, 0x80, 0xff, 0x7f]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot() -> Result<()> {
+        let score = Score::parse(
+            "{ [ \\i<\"p\"> \\cresc(c/8 d e f) \\accel(g/4 a) b/2 ],
+               [ c0/2 \\rit(g-1 c0) ] }",
+        )?;
+        let performance = PerformanceModel::default().perform(&score);
+        let synth = Synth::default();
+
+        let mut out = Vec::new();
+        write_wav(&synth.render(&performance), synth.sample_rate, &mut out)?;
+
+        assert_eq!(hash(&out), 0xcd93_43c5_6f23_83d0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,19 @@
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use munote::preprocess::Source;
+
+/// Every score of the documentation parses.
+#[test]
+fn examples() -> Result<()> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/examples");
+
+    for file in fs::read_dir(dir)? {
+        let path = file?.path();
+        if path.extension().is_some_and(|e| e == "gmn") {
+            Source::load(&path)?.parse()?;
+        }
+    }
+
+    Ok(())
+}
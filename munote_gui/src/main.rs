@@ -5,18 +5,19 @@ use eframe::{
         text::FontData,
     },
 };
-use egui::{pos2, TextStyle, Visuals};
+use egui::{
+    pos2, vec2, ComboBox, ScrollArea, Slider, Stroke, TextStyle, Visuals,
+};
 
+use munote::layout::{Layout, LayoutOptions, PageFormat, Point};
+use munote::output::{self, MidiOutputSink, PortSelector};
+use munote::performance::{PerformanceModel, TimeMap};
+use munote::scheduler::Scheduler;
 use munote::score::Score;
-use munote::visitor::VisitorPtr;
 
 use crate::drawing_context::DrawingContext;
-use crate::playback_context::PlaybackContext;
 
 mod drawing_context;
-mod symbol;
-mod playback_context;
-mod ui;
 
 fn main() -> Result<(), eframe::Error> {
     // Log to stdout (if you run with `RUST_LOG=debug`).
@@ -32,8 +33,25 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native("Munote", options, Box::new(|cc| Box::new(App::new(cc))))
 }
 
+/// Scale of the score, in points per staff space.
+const SCALE: f32 = 16.0;
+/// Room between two pages, in staff spaces.
+const PAGE_GAP: f32 = 2.0;
+/// What playing without choosing a port connects to.
+const DEFAULT_PORT: &str = "Default port (first)";
+
 struct App {
-    score: Score
+    score: Score,
+    /// The layout of the score, with the width it was computed for. The
+    /// score itself never changes once loaded.
+    layout: Option<(f32, Layout)>,
+    /// Converts the playback position to the time of the score.
+    time_map: TimeMap,
+    scheduler: Option<Scheduler>,
+    looping: bool,
+    ports: Vec<String>,
+    port: Option<PortSelector>,
+    error: Option<String>,
 }
 
 impl App {
@@ -49,12 +67,34 @@ impl App {
         // let score = Score::parse("[ \\clef c/4. ]")
         //     .expect("Cannot parse score");
 
-        Self {
-            score
-        }
+        let mut app = Self {
+            time_map: PerformanceModel::default().time_map(&score),
+            score,
+            layout: None,
+            scheduler: None,
+            looping: false,
+            ports: Vec::new(),
+            port: None,
+            error: None,
+        };
+        app.refresh_ports();
+
+        app
     }
 }
 
+/// A page as wide as the window, with the proportions of an A4 one.
+fn page_format(width: f32) -> PageFormat {
+    let margin = 2.0;
+
+    PageFormat::new(width, width * 29.7 / 21.0).with_margins(
+        margin,
+        margin + 4.0,
+        margin,
+        margin,
+    )
+}
+
 fn setup_custom_fonts(ctx: &egui::Context) {
     // Start with the default fonts (we will be adding to them rather than replacing them).
     let mut fonts = egui::FontDefinitions::default();
@@ -78,11 +118,7 @@ impl eframe::App for App {
         ctx.set_visuals(Visuals::light());
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                if ui.button("Play").clicked() {
-                    self.play()
-                }
-            });
+            ui.horizontal(|ui| self.transport(ui));
 
             let color = if ui.visuals().dark_mode {
                 Color32::from_additive_luminance(196)
@@ -90,40 +126,219 @@ impl eframe::App for App {
                 Color32::from_black_alpha(240)
             };
 
-            let size = 64.0;
-            let padding = 50.0;
-            let margin_top = 50.0;
-            let width = ui.available_width();
-            let height = ui.available_height();
+            let width = ui.available_width() / SCALE;
 
             let mut font_id = TextStyle::Body.resolve(ui.style());
-            font_id.size = size;
-            let painter = ui.painter().clone();
-
-            let context = Box::new(DrawingContext::new(
-                painter,
-                pos2(padding, margin_top + padding),
-                width - 2.0 * padding,
-                height - 2.0 * padding,
-                color,
-                size,
-                font_id,
-            ));
-
-            self.score.visit(VisitorPtr::new(context));
+            font_id.size = 4.0 * SCALE;
+
+            ScrollArea::vertical().show(ui, |ui| {
+                let origin = ui.cursor().min;
+                let painter = ui.painter().clone();
+                let layout = self.layout(width);
+
+                // Pages one below the other
+                let mut top = origin.y;
+                for page in &layout.pages {
+                    let context = DrawingContext::new(
+                        painter.clone(),
+                        pos2(origin.x, top),
+                        SCALE,
+                        color,
+                        font_id.clone(),
+                    );
+                    context.draw(page);
+
+                    top += (page.height + PAGE_GAP) * SCALE;
+                }
+
+                ui.allocate_space(vec2(width * SCALE, top - origin.y));
+
+                self.cursor(&painter, origin);
+            });
+
+            if self.scheduler.as_ref().is_some_and(Scheduler::is_playing) {
+                ctx.request_repaint();
+            }
         });
     }
-
 }
 
 impl App {
-    fn play(&self) {
-        let tempo = 60.0 * 1000.0 / 130.0;
+    /// The layout of the score on pages as wide as the window, computed
+    /// again only when its width changes.
+    fn layout(&mut self, width: f32) -> &Layout {
+        if self.layout.as_ref().is_some_and(|(w, _)| *w != width) {
+            self.layout = None;
+        }
+
+        let score = &self.score;
+        let (_, layout) = self.layout.get_or_insert_with(|| {
+            let options = LayoutOptions {
+                page: page_format(width),
+                ..Default::default()
+            };
+
+            (width, Layout::new(score, &options))
+        });
+
+        layout
+    }
+
+    /// Draws the playback cursor across the system being played.
+    fn cursor(&self, painter: &egui::Painter, origin: egui::Pos2) {
+        let (Some(scheduler), Some((_, layout))) =
+            (&self.scheduler, &self.layout)
+        else {
+            return;
+        };
+
+        let time = self.time_map.time(scheduler.position());
+        let Some((page, top, bottom)) = layout.cursor(time) else {
+            return;
+        };
 
-        println!("Tempo: {tempo}");
+        let page_top = layout.pages[..page]
+            .iter()
+            .map(|p| p.height + PAGE_GAP)
+            .sum::<f32>();
+        let point = |p: Point| {
+            pos2(origin.x + p.x * SCALE, origin.y + (page_top + p.y) * SCALE)
+        };
+
+        painter.line_segment(
+            [
+                point(Point::new(top.x, top.y - 1.0)),
+                point(Point::new(bottom.x, bottom.y + 1.0)),
+            ],
+            Stroke::new(2.0, Color32::from_rgb(64, 128, 255)),
+        );
+    }
+
+    fn transport(&mut self, ui: &mut egui::Ui) {
+        self.port_selection(ui);
+
+        let playing =
+            self.scheduler.as_ref().is_some_and(Scheduler::is_playing);
+
+        if ui.button(if playing { "Pause" } else { "Play" }).clicked() {
+            if let Some(scheduler) = self.scheduler() {
+                if playing {
+                    scheduler.pause();
+                } else {
+                    scheduler.play();
+                }
+            }
+        }
+
+        if ui.button("Stop").clicked() {
+            if let Some(scheduler) = &self.scheduler {
+                scheduler.stop();
+            }
+        }
+
+        if ui.checkbox(&mut self.looping, "Loop").changed() {
+            let looping = self.looping;
+
+            if let Some(scheduler) = self.scheduler() {
+                scheduler.set_loop(looping.then(|| (0.0, scheduler.duration())));
+            }
+        }
+
+        if let Some(scheduler) = &self.scheduler {
+            let duration = scheduler.duration();
+            let mut position = scheduler.position();
+
+            let slider = Slider::new(&mut position, 0.0..=duration)
+                .show_value(false);
+            if ui.add(slider).changed() {
+                scheduler.seek(position);
+            }
+
+            ui.label(format!("{position:.1}s / {duration:.1}s"));
+        }
+
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
+
+    fn port_selection(&mut self, ui: &mut egui::Ui) {
+        let selected = match &self.port {
+            Some(PortSelector::Name(name)) => name.clone(),
+            Some(PortSelector::Virtual(_)) => "Virtual port".to_string(),
+            _ => DEFAULT_PORT.to_string(),
+        };
+
+        let mut port = self.port.clone();
+
+        ComboBox::from_label("Output")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut port, None, DEFAULT_PORT);
+
+                for name in &self.ports {
+                    let selector = Some(PortSelector::Name(name.clone()));
+                    ui.selectable_value(&mut port, selector, name);
+                }
+
+                if cfg!(unix) {
+                    ui.selectable_value(
+                        &mut port,
+                        Some(PortSelector::Virtual("Munote".to_string())),
+                        "Virtual port",
+                    );
+                }
+            });
+
+        if ui.button("Refresh").clicked() {
+            self.refresh_ports();
+        }
+
+        if port != self.port {
+            self.select_port(port);
+        }
+    }
+
+    /// Lists the ports again, as they come and go, keeping the selected one
+    /// if it is still there, or else going back to the default one.
+    fn refresh_ports(&mut self) {
+        let ports = output::ports();
+        self.ports = ports.as_ref().cloned().unwrap_or_default();
+
+        let available = match &self.port {
+            Some(PortSelector::Name(name)) => self.ports.contains(name),
+            _ => true,
+        };
+
+        if !available {
+            self.select_port(None);
+        }
+
+        if let Err(e) = ports {
+            self.error = Some(e.to_string());
+        }
+    }
+
+    fn select_port(&mut self, port: Option<PortSelector>) {
+        // Reconnect on the next playback
+        self.port = port;
+        self.scheduler = None;
+        self.error = None;
+    }
+
+    fn scheduler(&mut self) -> Option<&Scheduler> {
+        if self.scheduler.is_none() {
+            let performance = PerformanceModel::default().perform(&self.score);
+
+            match MidiOutputSink::connect(self.port.as_ref()) {
+                Ok(sink) => {
+                    self.scheduler = Some(Scheduler::new(&performance, sink));
+                    self.error = None;
+                },
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
 
-        let context = PlaybackContext::new(tempo)
-            .expect("Cannot create MIDI output");
-        self.score.visit(VisitorPtr::new(Box::new(context)));
+        self.scheduler.as_ref()
     }
 }
@@ -1,3 +0,0 @@
-pub use rotated_text::*;
-
-pub mod rotated_text;
\ No newline at end of file
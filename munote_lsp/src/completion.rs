@@ -0,0 +1,113 @@
+use lazy_static::lazy_static;
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, MarkupContent,
+    MarkupKind, Position,
+};
+use regex::Regex;
+
+use crate::{document::Document, hover::documentation};
+
+/// Tag names after a backslash, and parameter names between the angle
+/// brackets of a tag.
+pub fn completion(
+    document: &Document,
+    position: Position,
+) -> Vec<CompletionItem> {
+    lazy_static! {
+        static ref NAME: Regex = Regex::new(r"\\[A-Za-z]*$").unwrap();
+        static ref PARAMS: Regex =
+            Regex::new(r"\\([A-Za-z]+)(?::\d+)?<([^<>]*)$").unwrap();
+    }
+
+    let offset = document.offset(position);
+    let line = document.text[..offset]
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default();
+    let defs = document.defs();
+
+    if NAME.is_match(line) {
+        return defs
+            .iter()
+            .map(|(id, def)| CompletionItem {
+                label: id.to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                documentation: Some(markdown(documentation(id, def))),
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    let Some(captures) = PARAMS.captures(line) else {
+        return Vec::new();
+    };
+
+    let name = &captures[1];
+    let name = name
+        .strip_suffix("Begin")
+        .or_else(|| name.strip_suffix("End"))
+        .filter(|base| defs.lookup(name).is_err() && !base.is_empty())
+        .unwrap_or(name);
+    let Some(def) = defs.lookup(name).ok().and_then(|id| defs.get(id)) else {
+        return Vec::new();
+    };
+
+    let given = &captures[2];
+
+    def.params
+        .iter()
+        .filter(|p| !given.contains(&format!("{}=", p.name)))
+        .map(|param| CompletionItem {
+            label: param.name.clone(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some(param.ty.to_string()),
+            insert_text: Some(format!("{}=", param.name)),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn markdown(value: String) -> Documentation {
+    Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Url;
+
+    use super::*;
+
+    fn labels(text: &str) -> Vec<String> {
+        let document = Document::new(
+            Url::parse("file:///a.gmn").unwrap(),
+            text.to_string(),
+        );
+        let position = document.end();
+
+        completion(&document, position)
+            .into_iter()
+            .map(|item| item.label)
+            .collect()
+    }
+
+    #[test]
+    fn names() {
+        let names = labels("[ c \\sl");
+
+        assert!(names.contains(&"slur".to_string()));
+        assert!(names.contains(&"staccato".to_string()));
+        assert!(labels("[ c d").is_empty());
+    }
+
+    #[test]
+    fn params() {
+        assert_eq!(
+            labels("[ \\slurBegin<curve=\"up\", dy1=2, "),
+            ["dx1", "dx2", "dy2", "r3", "h"]
+        );
+        assert!(labels("[ \\slur<curve=\"up\">(c) ").is_empty());
+    }
+}
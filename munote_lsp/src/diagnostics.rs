@@ -0,0 +1,106 @@
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range,
+};
+use munote::lint::{Linter, Severity};
+
+use crate::document::Document;
+
+/// The syntax errors of a score and what the linter finds in it, with the
+/// rules and tags of the closest `munote.toml`.
+///
+/// Diagnostics of included files are left to the editors of these files.
+pub fn diagnostics(document: &Document) -> Vec<Diagnostic> {
+    let source = match document.source() {
+        Ok(source) => source,
+        Err(e) => {
+            return vec![diagnostic(
+                Range::default(),
+                DiagnosticSeverity::ERROR,
+                "syntax",
+                e.to_string(),
+            )]
+        },
+    };
+
+    let settings = &document.settings;
+    if let Some(error) = &settings.error {
+        return vec![diagnostic(
+            Range::default(),
+            DiagnosticSeverity::ERROR,
+            "config",
+            error.clone(),
+        )];
+    }
+
+    Linter::with_defs(settings.config.clone(), settings.defs.clone())
+        .lint_source(&source)
+        .into_iter()
+        .filter_map(|d| {
+            let range = match d.locations.first() {
+                Some(location) => document.range(&source, *location)?,
+                None => Range::new(Position::default(), Position::default()),
+            };
+
+            Some(diagnostic(range, severity(d.severity), d.rule, d.message))
+        })
+        .collect()
+}
+
+fn severity(severity: Severity) -> DiagnosticSeverity {
+    match severity {
+        Severity::Info => DiagnosticSeverity::INFORMATION,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Error => DiagnosticSeverity::ERROR,
+    }
+}
+
+fn diagnostic(
+    range: Range,
+    severity: DiagnosticSeverity,
+    rule: &str,
+    message: String,
+) -> Diagnostic {
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        code: Some(NumberOrString::String(rule.to_string())),
+        source: Some("munote".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Url;
+
+    use super::*;
+
+    fn diagnostics(text: &str) -> Vec<(Range, String)> {
+        let document = Document::new(
+            Url::parse("file:///a.gmn").unwrap(),
+            text.to_string(),
+        );
+
+        super::diagnostics(&document)
+            .into_iter()
+            .map(|d| (d.range, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn syntax_and_lint() {
+        let range = |line, start, end| {
+            Range::new(Position::new(line, start), Position::new(line, end))
+        };
+
+        assert_eq!(
+            diagnostics("[ c d )"),
+            [(range(0, 6, 6), "Unexpected \")\"".to_string())]
+        );
+        assert_eq!(
+            diagnostics("[ c\n  \\clef<\"x\"> ]"),
+            [(range(1, 2, 12), "Unknown clef \"x\"".to_string())]
+        );
+    }
+}
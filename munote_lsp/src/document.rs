@@ -0,0 +1,212 @@
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use lsp_types::{Position, Range, Url};
+use munote::{
+    context::Context, lint::Config, location::Location, preprocess::Source,
+    score::Score, tag_definitions::TagDefinitions,
+};
+
+/// The rules and tags of a `munote.toml`, shared by the scores it applies
+/// to.
+#[derive(Default)]
+pub struct Settings {
+    pub config: Config,
+    pub defs: TagDefinitions,
+    /// Why the configuration could not be loaded, the defaults being used
+    /// instead.
+    pub error: Option<String>,
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Self {
+        let loaded =
+            Config::load(path).and_then(|config| Ok((config.defs()?, config)));
+
+        match loaded {
+            Ok((defs, config)) => Self {
+                config,
+                defs,
+                error: None,
+            },
+            Err(e) => Self {
+                error: Some(e.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// A score opened in the editor, with its unsaved changes.
+pub struct Document {
+    pub uri: Url,
+    pub text: String,
+    pub settings: Rc<Settings>,
+}
+
+impl Document {
+    pub fn new(uri: Url, text: String) -> Self {
+        Self {
+            uri,
+            text,
+            settings: Rc::default(),
+        }
+    }
+
+    pub fn with_settings(mut self, settings: Rc<Settings>) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Path the includes of the score are relative to.
+    pub fn path(&self) -> PathBuf {
+        self.uri
+            .to_file_path()
+            .unwrap_or_else(|_| PathBuf::from(self.uri.path()))
+    }
+
+    /// The score with its includes and variables expanded.
+    pub fn source(&self) -> Result<Source> {
+        Source::expand(&self.text, &self.path())
+    }
+
+    /// The built-in tags, with the ones of the configuration.
+    pub fn defs(&self) -> &TagDefinitions {
+        &self.settings.defs
+    }
+
+    /// The expanded score, keeping the tags of the wrong type as the linter
+    /// does.
+    pub fn parse(
+        &self,
+        source: &Source,
+        defs: &TagDefinitions,
+    ) -> Result<Score> {
+        let context = Context {
+            defs: defs.clone(),
+            lenient: true,
+            ..Default::default()
+        };
+
+        Score::parse_with(&source.text, context)
+    }
+
+    /// Where a location of the expanded source is in the document, when it
+    /// was written there rather than in an included file.
+    pub fn range(&self, source: &Source, location: Location) -> Option<Range> {
+        let start = source.origin(location.offset);
+        if start.file != source.path() {
+            return None;
+        }
+
+        let start = self.position(start.line, start.column);
+        let end = source.origin(location.offset + location.length);
+
+        // Unless the location ends in a variable
+        let end = if end.file == source.path() {
+            self.position(end.line, end.column)
+        } else {
+            start
+        };
+
+        Some(Range::new(start, end.max(start)))
+    }
+
+    /// Position of a line and a column in characters, both from 1.
+    fn position(&self, line: u32, column: usize) -> Position {
+        let text = self
+            .text
+            .split('\n')
+            .nth(line.saturating_sub(1) as usize)
+            .unwrap_or_default();
+        let character = text
+            .chars()
+            .take(column.saturating_sub(1))
+            .map(char::len_utf16)
+            .sum::<usize>();
+
+        Position::new(line.saturating_sub(1), character as u32)
+    }
+
+    /// Byte offset of a position in the text.
+    pub fn offset(&self, position: Position) -> usize {
+        let line = self
+            .text
+            .split_inclusive('\n')
+            .take(position.line as usize)
+            .map(str::len)
+            .sum::<usize>();
+
+        let mut units = 0;
+        let column = self.text[line..]
+            .char_indices()
+            .take_while(|(_, c)| {
+                units += c.len_utf16();
+                *c != '\n' && units <= position.character as usize
+            })
+            .last()
+            .map_or(0, |(i, c)| i + c.len_utf8());
+
+        line + column
+    }
+
+    /// Position of the end of the text.
+    pub fn end(&self) -> Position {
+        let line = self.text.matches('\n').count() as u32;
+        let last = self.text.rsplit('\n').next().unwrap_or_default();
+
+        Position::new(line, last.encode_utf16().count() as u32)
+    }
+}
+
+pub fn contains(range: Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(text: &str) -> Document {
+        Document::new(Url::parse("file:///a.gmn").unwrap(), text.to_string())
+    }
+
+    #[test]
+    fn positions() {
+        let document = document("[ c\n  \\text<\"é\"> d ]");
+
+        assert_eq!(document.position(2, 14), Position::new(1, 13));
+        assert_eq!(document.offset(Position::new(1, 13)), 18);
+        assert_eq!(document.offset(Position::new(0, 9)), 3);
+        assert_eq!(document.end(), Position::new(1, 16));
+    }
+
+    #[test]
+    fn ranges() -> Result<()> {
+        let document = document("$v = [ c ]\n[ d $v ]");
+        let source = document.source()?;
+
+        let d = source.text.find('d').unwrap();
+        let c = source.text.find('c').unwrap();
+        let location = |offset| Location {
+            offset,
+            length: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            document.range(&source, location(d)),
+            Some(Range::new(Position::new(1, 2), Position::new(1, 3)))
+        );
+        // Written in the variable
+        assert_eq!(
+            document.range(&source, location(c)),
+            Some(Range::new(Position::new(0, 7), Position::new(0, 8)))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+/// Indentation of each level of nesting.
+const INDENT: &str = "  ";
+
+/// Reindents a score by the brackets, braces and parentheses lines start
+/// within, with single spaces between events, at most one blank line in a
+/// row and no trailing whitespaces.
+///
+/// Comments and strings are kept as written.
+pub fn format(text: &str) -> String {
+    let mut lines = Vec::<String>::new();
+    let mut depth = 0usize;
+    let mut comment = false;
+
+    for line in text.lines() {
+        // Inside a multi-line comment
+        if comment {
+            let indent = line.len() - line.trim_start().len();
+            let (spaced, change) = scan(line, &mut comment);
+            depth = depth.saturating_add_signed(change);
+
+            lines.push(line[..indent].to_string() + spaced.trim_start());
+            continue;
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            if lines.last().is_some_and(|l| !l.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+
+        let closing = line
+            .chars()
+            .take_while(|c| matches!(c, '}' | ']' | ')' | ' ' | ','))
+            .filter(|c| matches!(c, '}' | ']' | ')'))
+            .count();
+        let level = depth.saturating_sub(closing);
+
+        let (spaced, change) = scan(line, &mut comment);
+        depth = depth.saturating_add_signed(change);
+
+        lines.push(INDENT.repeat(level) + &spaced);
+    }
+
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+
+    let mut formatted = lines.join("\n");
+    formatted.push('\n');
+    formatted
+}
+
+/// Collapses the whitespaces of a line outside of comments and strings,
+/// with how much its brackets change the nesting.
+fn scan(line: &str, comment: &mut bool) -> (String, isize) {
+    let mut out = String::new();
+    let mut change = 0;
+    let mut string = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if *comment {
+            out.push(c);
+            if c == '*' && chars.peek() == Some(&')') {
+                out.extend(chars.next());
+                *comment = false;
+            }
+            continue;
+        }
+
+        if string {
+            out.push(c);
+            string = c != '"';
+            continue;
+        }
+
+        match c {
+            '%' => {
+                out.push(c);
+                out.extend(chars.by_ref());
+            },
+            '(' if chars.peek() == Some(&'*') => {
+                out.push(c);
+                out.extend(chars.next());
+                *comment = true;
+            },
+            '"' => {
+                out.push(c);
+                string = true;
+            },
+            c if c.is_whitespace() => {
+                if !out.ends_with(' ') {
+                    out.push(' ');
+                }
+            },
+            '{' | '[' | '(' => {
+                out.push(c);
+                change += 1;
+            },
+            '}' | ']' | ')' => {
+                out.push(c);
+                change -= 1;
+            },
+            _ => out.push(c),
+        }
+    }
+
+    (out.trim_end().to_string(), change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indentation() {
+        let text = "
+{
+[ c   d  \\slur(e
+f) ],
+
+
+        [ \\staff<2>
+    c0 ]
+   }
+";
+
+        assert_eq!(
+            format(text),
+            "{
+  [ c d \\slur(e
+      f) ],
+
+  [ \\staff<2>
+    c0 ]
+}
+"
+        );
+    }
+
+    #[test]
+    fn comments_and_strings() {
+        let text = "[ c  % a  comment [\n  (* a\n     (  comment *)  \
+                    \\text<\"a   b\">  d ]";
+
+        assert_eq!(
+            format(text),
+            "[ c % a  comment [\n  (* a\n     (  comment *) \\text<\"a   b\"> \
+             d ]\n"
+        );
+    }
+}
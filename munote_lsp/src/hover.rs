@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position};
+use munote::{
+    duration::fraction, instruments::pitch_name, tag::TagType,
+    tag_definitions::TagDefinition, tag_id::TagId,
+};
+
+use crate::{
+    document::{contains, Document},
+    index::{self, Index},
+};
+
+/// What the note or tag under the cursor is.
+pub fn hover(document: &Document, position: Position) -> Option<Hover> {
+    let source = document.source().ok()?;
+    let defs = document.defs();
+    let score = document.parse(&source, defs).ok()?;
+    let index = Index::new(&score);
+
+    let note = index.notes.iter().find_map(|note| {
+        let range = document.range(&source, note.location)?;
+        contains(range, position).then_some((note, range))
+    });
+
+    if let Some((note, range)) = note {
+        let mut text = match note.pitch {
+            Some(pitch) => {
+                format!("**{}**, MIDI {pitch}", pitch_name(pitch.into()))
+            },
+            None => "**Empty event**".to_string(),
+        };
+
+        write!(
+            text,
+            "\n\nStaff {}, voice {}, measure {}, onset {}",
+            note.staff,
+            note.voice + 1,
+            note.measure,
+            fraction(note.onset)
+        )
+        .unwrap();
+
+        return Some(markdown(text, range));
+    }
+
+    index.tags.iter().find_map(|entry| {
+        let location = index::name(entry.tag, &source.text);
+        let range = document.range(&source, location)?;
+        if !contains(range, position) {
+            return None;
+        }
+
+        let def = defs.get(entry.tag.id)?;
+
+        Some(markdown(documentation(entry.tag.id, def), range))
+    })
+}
+
+/// The type, description, aliases and parameters of a tag, in Markdown.
+pub fn documentation(id: TagId, def: &TagDefinition) -> String {
+    let ty = match def.ty {
+        TagType::Any => "position or range tag",
+        TagType::Position => "position tag",
+        TagType::Begin(_) | TagType::End(_) | TagType::Range => "range tag",
+    };
+
+    let mut text = format!("**\\{id}**, {ty}");
+
+    if !def.description.is_empty() {
+        write!(text, "\n\n{}", def.description).unwrap();
+    }
+
+    if !def.alternatives.is_empty() {
+        let aliases = def
+            .alternatives
+            .iter()
+            .map(|a| format!("`\\{a}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(text, "\n\nAliases: {aliases}").unwrap();
+    }
+
+    if !def.params.is_empty() {
+        text.push_str("\n\nParameters:");
+
+        for param in &def.params {
+            write!(text, "\n- `{}`: {}", param.name, param.ty).unwrap();
+
+            if param.optional {
+                text.push_str(", optional");
+            }
+            if let Some(default) = &param.default {
+                write!(text, ", default `{default}`").unwrap();
+            }
+        }
+    }
+
+    text
+}
+
+fn markdown(value: String, range: lsp_types::Range) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(range),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Url;
+
+    use super::*;
+
+    fn text(source: &str, line: u32, character: u32) -> Option<String> {
+        let document = Document::new(
+            Url::parse("file:///a.gmn").unwrap(),
+            source.to_string(),
+        );
+
+        hover(&document, Position::new(line, character)).map(|h| {
+            match h.contents {
+                HoverContents::Markup(m) => m.value,
+                _ => unreachable!(),
+            }
+        })
+    }
+
+    #[test]
+    fn notes() {
+        let source = "[ \\meter<\"2/4\"> c1 d e/8 f#2 ]";
+
+        assert_eq!(
+            text(source, 0, 25).as_deref(),
+            Some("**F#5**, MIDI 78\n\nStaff 1, voice 1, measure 2, onset 5/8")
+        );
+        assert_eq!(text(source, 0, 1), None);
+    }
+
+    #[test]
+    fn tags() {
+        let text = text("[ \\slur(c d) ]", 0, 4).unwrap();
+
+        assert!(text.starts_with(
+            "**\\slur**, range tag\n\nSlur over the enclosed notes\n\n\
+             Aliases: `\\sl`"
+        ));
+        assert!(text.contains("- `curve`: string, optional"));
+    }
+}
@@ -0,0 +1,171 @@
+use munote::{
+    location::Location,
+    note::Note,
+    score::Score,
+    tag::{Tag, TagType},
+    visitor::{VisitContext, Visitor},
+    voice::Voice,
+};
+
+/// The events of a score editors point at, with where they are in the
+/// score.
+#[derive(Default)]
+pub struct Index<'a> {
+    pub notes: Vec<NoteEntry>,
+    pub tags: Vec<TagEntry<'a>>,
+    pub voices: Vec<VoiceEntry>,
+}
+
+pub struct NoteEntry {
+    pub location: Location,
+    pub pitch: Option<u8>,
+    pub staff: u8,
+    pub voice: usize,
+    pub onset: f32,
+    pub measure: u32,
+}
+
+pub struct TagEntry<'a> {
+    pub tag: &'a Tag,
+    pub staff: u8,
+    pub voice: usize,
+}
+
+pub struct VoiceEntry {
+    pub staff: u8,
+    pub voice: usize,
+    /// From the start of the first event to the end of the last one.
+    pub location: Location,
+}
+
+impl<'a> Index<'a> {
+    pub fn new(score: &'a Score) -> Self {
+        let mut index = Self::default();
+        score.visit(&mut index);
+
+        index
+    }
+
+    /// Pairs of `Begin` and `End` tags, by index in `tags`.
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        let mut open = Vec::<usize>::new();
+
+        for (i, entry) in self.tags.iter().enumerate() {
+            match entry.tag.ty {
+                TagType::Begin(_) => open.push(i),
+                TagType::End(suffix) => {
+                    let begin = open.iter().rposition(|&j| {
+                        let other = &self.tags[j];
+
+                        (other.staff, other.voice) == (entry.staff, entry.voice)
+                            && other.tag.id == entry.tag.id
+                            && matches!(
+                                other.tag.ty,
+                                TagType::Begin(s) if s == suffix
+                            )
+                    });
+
+                    if let Some(j) = begin {
+                        pairs.push((open.remove(j), i));
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        pairs
+    }
+}
+
+impl<'a> Visitor<'a> for Index<'a> {
+    fn on_voice(&mut self, voice: &'a Voice, cx: &VisitContext) {
+        let (Some(first), Some(last)) =
+            (voice.events.first(), voice.events.last())
+        else {
+            return;
+        };
+
+        let (first, last) = (first.location(), last.location());
+
+        self.voices.push(VoiceEntry {
+            staff: cx.staff,
+            voice: cx.voice,
+            location: Location {
+                length: last.offset + last.length - first.offset,
+                ..first
+            },
+        });
+    }
+
+    fn on_note(&mut self, note: &'a Note, cx: &VisitContext) {
+        self.notes.push(NoteEntry {
+            location: note.location,
+            pitch: note.midi_pitch(),
+            staff: cx.staff,
+            voice: cx.voice,
+            onset: cx.onset,
+            measure: cx.measure,
+        });
+    }
+
+    fn on_tag(&mut self, tag: &'a Tag, cx: &VisitContext) {
+        self.tags.push(TagEntry {
+            tag,
+            staff: cx.staff,
+            voice: cx.voice,
+        });
+    }
+}
+
+/// The location of the name of a tag, like `\slurBegin:2` without its
+/// parameters and events.
+pub fn name(tag: &Tag, source: &str) -> Location {
+    let text = tag.location.text(source);
+    let length = text
+        .find(|c: char| c == '<' || c == '(' || c.is_whitespace())
+        .unwrap_or(text.len());
+
+    Location {
+        length,
+        ..tag.location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn pairs() -> Result<()> {
+        let score = Score::parse(
+            "{ [ \\slurBegin c \\slurBegin:2 d \\slurEnd e \\slurEnd:2 ],
+               [ \\slurEnd c ] }",
+        )?;
+        let index = Index::new(&score);
+
+        assert_eq!(index.pairs(), [(0, 2), (1, 3)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn names() -> Result<()> {
+        let source = "[ \\slur<\"up\">(c d) \\bar e ]";
+        let score = Score::parse(source)?;
+        let index = Index::new(&score);
+
+        let names = index
+            .tags
+            .iter()
+            .map(|t| name(t.tag, source).text(source))
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, ["\\slur", "\\bar"]);
+        assert_eq!(index.voices[0].location.text(source), &source[2..25]);
+
+        Ok(())
+    }
+}
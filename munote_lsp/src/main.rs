@@ -0,0 +1,27 @@
+//! Language server for GUIDO scores, talking to editors on stdio.
+
+use anyhow::Result;
+use lsp_server::Connection;
+
+use crate::server::Server;
+
+mod completion;
+mod diagnostics;
+mod document;
+mod formatting;
+mod hover;
+mod index;
+mod navigation;
+mod server;
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = serde_json::to_value(Server::capabilities())?;
+    connection.initialize(capabilities)?;
+
+    Server::new(connection).run()?;
+    io_threads.join()?;
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use lsp_types::{DocumentSymbol, Position, Range, SymbolKind};
+
+use crate::{
+    document::{contains, Document},
+    index::{self, Index},
+};
+
+/// The `End` tag of the `Begin` tag under the cursor, or the other way
+/// around.
+pub fn matching_tag(
+    document: &Document,
+    position: Position,
+) -> Option<lsp_types::Location> {
+    let source = document.source().ok()?;
+    let score = document.parse(&source, document.defs()).ok()?;
+    let index = Index::new(&score);
+
+    let range = |i: usize| {
+        let name = index::name(index.tags[i].tag, &source.text);
+        document.range(&source, name)
+    };
+
+    let (begin, end) = index.pairs().into_iter().find(|&(begin, end)| {
+        [begin, end]
+            .into_iter()
+            .any(|i| range(i).is_some_and(|r| contains(r, position)))
+    })?;
+
+    let other = if range(begin).is_some_and(|r| contains(r, position)) {
+        end
+    } else {
+        begin
+    };
+
+    Some(lsp_types::Location::new(
+        document.uri.clone(),
+        range(other)?,
+    ))
+}
+
+/// The staffs of the score, with their voices.
+pub fn symbols(document: &Document) -> Vec<DocumentSymbol> {
+    let Ok(source) = document.source() else {
+        return Vec::new();
+    };
+    let Ok(score) = document.parse(&source, document.defs()) else {
+        return Vec::new();
+    };
+    let index = Index::new(&score);
+
+    score
+        .staffs
+        .iter()
+        .filter_map(|staff| {
+            let voices = index
+                .voices
+                .iter()
+                .filter(|v| v.staff == staff.id)
+                .filter_map(|v| {
+                    let range = document.range(&source, v.location)?;
+
+                    Some(symbol(
+                        format!("Voice {}", v.voice + 1),
+                        None,
+                        SymbolKind::ARRAY,
+                        range,
+                        Vec::new(),
+                    ))
+                })
+                .collect::<Vec<_>>();
+
+            let start = voices.iter().map(|v| v.range.start).min()?;
+            let end = voices.iter().map(|v| v.range.end).max()?;
+
+            Some(symbol(
+                format!("Staff {}", staff.id),
+                staff.name.clone(),
+                SymbolKind::NAMESPACE,
+                Range::new(start, end),
+                voices,
+            ))
+        })
+        .collect()
+}
+
+#[allow(deprecated)]
+fn symbol(
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    range: Range,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: (!children.is_empty()).then_some(children),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Url;
+
+    use super::*;
+
+    fn document(text: &str) -> Document {
+        Document::new(Url::parse("file:///a.gmn").unwrap(), text.to_string())
+    }
+
+    #[test]
+    fn matching_tags() {
+        let document =
+            document("[ \\slurBegin c \\crescBegin d\n  \\slurEnd ]");
+        let target = |line, character| {
+            matching_tag(&document, Position::new(line, character))
+                .map(|l| l.range.start)
+        };
+
+        assert_eq!(target(0, 5), Some(Position::new(1, 2)));
+        assert_eq!(target(1, 4), Some(Position::new(0, 2)));
+        assert_eq!(target(0, 18), None);
+    }
+
+    #[test]
+    fn staffs_and_voices() {
+        let document = document(
+            "{ [ \\instr<\"Flute\"> c d ],\n  [ e ],\n  [ \\staff<2> c0 ] }",
+        );
+        let symbols = symbols(&document);
+
+        let names = symbols
+            .iter()
+            .map(|s| {
+                let voices = s.children.iter().flatten().map(|v| &*v.name);
+                (s.name.as_str(), s.detail.as_deref(), voices.count())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            [("Staff 1", Some("Flute"), 2), ("Staff 2", None, 1)]
+        );
+        assert_eq!(
+            symbols[0].range,
+            Range::new(Position::new(0, 4), Position::new(1, 5))
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashMap, path::PathBuf, rc::Rc};
+
+use anyhow::Result;
+use lsp_server::{
+    Connection, ErrorCode, Message, Notification, Request, RequestId, Response,
+};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        DidSaveTextDocument, Notification as _, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, Formatting, GotoDefinition,
+        HoverRequest, Request as _,
+    },
+    CompletionOptions, CompletionResponse, DocumentSymbolResponse,
+    GotoDefinitionResponse, HoverProviderCapability, OneOf,
+    PublishDiagnosticsParams, Range, SaveOptions, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+    TextDocumentSyncSaveOptions, TextEdit, Url,
+};
+use munote::lint::Config;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    completion::completion,
+    diagnostics::diagnostics,
+    document::{Document, Settings},
+    formatting::format,
+    hover::hover,
+    navigation::{matching_tag, symbols},
+};
+
+/// Answers the requests of an editor about the scores it opened.
+pub struct Server {
+    connection: Connection,
+    documents: HashMap<Url, Document>,
+    /// The settings of each `munote.toml` of the workspace, read again when
+    /// a file is saved.
+    settings: HashMap<PathBuf, Rc<Settings>>,
+}
+
+impl Server {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            documents: HashMap::new(),
+            settings: HashMap::new(),
+        }
+    }
+
+    pub fn capabilities() -> ServerCapabilities {
+        ServerCapabilities {
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::FULL),
+                    save: Some(TextDocumentSyncSaveOptions::SaveOptions(
+                        SaveOptions::default(),
+                    )),
+                    ..Default::default()
+                },
+            )),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            completion_provider: Some(CompletionOptions {
+                trigger_characters: Some(vec![
+                    "\\".to_string(),
+                    "<".to_string(),
+                    ",".to_string(),
+                ]),
+                ..Default::default()
+            }),
+            definition_provider: Some(OneOf::Left(true)),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    /// Handles the messages of the editor until it shuts the server down.
+    pub fn run(mut self) -> Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+
+                    self.request(request)?;
+                },
+                Message::Notification(notification) => {
+                    self.notification(notification)?;
+                },
+                Message::Response(_) => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    fn request(&self, request: Request) -> Result<()> {
+        let id = request.id.clone();
+
+        match request.method.as_str() {
+            HoverRequest::METHOD => {
+                let Some(params) = self.params::<lsp_types::HoverParams>(
+                    request,
+                    HoverRequest::METHOD,
+                )?
+                else {
+                    return Ok(());
+                };
+                let at = params.text_document_position_params;
+                let result = self
+                    .documents
+                    .get(&at.text_document.uri)
+                    .and_then(|document| hover(document, at.position));
+
+                self.respond(id, result)
+            },
+            Completion::METHOD => {
+                let Some(params) = self.params::<lsp_types::CompletionParams>(
+                    request,
+                    Completion::METHOD,
+                )?
+                else {
+                    return Ok(());
+                };
+                let at = params.text_document_position;
+                let result = self
+                    .documents
+                    .get(&at.text_document.uri)
+                    .map(|document| completion(document, at.position))
+                    .map(CompletionResponse::Array);
+
+                self.respond(id, result)
+            },
+            GotoDefinition::METHOD => {
+                let Some(params) = self
+                    .params::<lsp_types::GotoDefinitionParams>(
+                        request,
+                        GotoDefinition::METHOD,
+                    )?
+                else {
+                    return Ok(());
+                };
+                let at = params.text_document_position_params;
+                let result = self
+                    .documents
+                    .get(&at.text_document.uri)
+                    .and_then(|document| matching_tag(document, at.position))
+                    .map(GotoDefinitionResponse::Scalar);
+
+                self.respond(id, result)
+            },
+            DocumentSymbolRequest::METHOD => {
+                let Some(params) = self
+                    .params::<lsp_types::DocumentSymbolParams>(
+                        request,
+                        DocumentSymbolRequest::METHOD,
+                    )?
+                else {
+                    return Ok(());
+                };
+                let result = self
+                    .documents
+                    .get(&params.text_document.uri)
+                    .map(symbols)
+                    .map(DocumentSymbolResponse::Nested);
+
+                self.respond(id, result)
+            },
+            Formatting::METHOD => {
+                let Some(params) = self
+                    .params::<lsp_types::DocumentFormattingParams>(
+                        request,
+                        Formatting::METHOD,
+                    )?
+                else {
+                    return Ok(());
+                };
+                let result = self.documents.get(&params.text_document.uri).map(
+                    |document| {
+                        let formatted = format(&document.text);
+                        if formatted == document.text {
+                            return Vec::new();
+                        }
+
+                        vec![TextEdit::new(
+                            Range::new(Default::default(), document.end()),
+                            formatted,
+                        )]
+                    },
+                );
+
+                self.respond(id, result)
+            },
+            _ => {
+                let message = format!("Unknown method {}", request.method);
+
+                self.fail(id, ErrorCode::MethodNotFound, message)
+            },
+        }
+    }
+
+    /// The params of a request, answering it with an error instead when
+    /// they are invalid.
+    fn params<P: DeserializeOwned>(
+        &self,
+        request: Request,
+        method: &str,
+    ) -> Result<Option<P>> {
+        let id = request.id.clone();
+
+        match request.extract(method) {
+            Ok((_, params)) => Ok(Some(params)),
+            Err(e) => {
+                self.fail(id, ErrorCode::InvalidParams, e.to_string())?;
+                Ok(None)
+            },
+        }
+    }
+
+    fn notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(
+                    DidOpenTextDocument::METHOD,
+                )?;
+                let document = params.text_document;
+
+                self.open(document.uri, document.text)
+            },
+            DidChangeTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidChangeTextDocumentParams>(
+                    DidChangeTextDocument::METHOD,
+                )?;
+
+                // The whole text is sent with each change
+                let Some(change) = params.content_changes.into_iter().last()
+                else {
+                    return Ok(());
+                };
+
+                self.open(params.text_document.uri, change.text)
+            },
+            DidSaveTextDocument::METHOD => {
+                // The configuration or the tags it loads may have changed
+                self.settings.clear();
+
+                let documents = std::mem::take(&mut self.documents);
+                for (_, document) in documents {
+                    self.open(document.uri, document.text)?;
+                }
+
+                Ok(())
+            },
+            DidCloseTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidCloseTextDocumentParams>(
+                    DidCloseTextDocument::METHOD,
+                )?;
+                let uri = params.text_document.uri;
+
+                self.documents.remove(&uri);
+                self.publish(uri, Vec::new())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn open(&mut self, uri: Url, text: String) -> Result<()> {
+        let document = Document::new(uri.clone(), text);
+        let settings = self.settings_of(&document);
+        let document = document.with_settings(settings);
+        let diagnostics = diagnostics(&document);
+
+        self.documents.insert(uri.clone(), document);
+        self.publish(uri, diagnostics)
+    }
+
+    /// The settings of the closest `munote.toml`, loaded once for all the
+    /// scores it applies to.
+    fn settings_of(&mut self, document: &Document) -> Rc<Settings> {
+        let Some(path) = Config::find(&document.path()) else {
+            return Rc::default();
+        };
+
+        self.settings
+            .entry(path)
+            .or_insert_with_key(|path| Rc::new(Settings::load(path)))
+            .clone()
+    }
+
+    fn publish(
+        &self,
+        uri: Url,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+    ) -> Result<()> {
+        let notification = Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            PublishDiagnosticsParams::new(uri, diagnostics, None),
+        );
+
+        Ok(self.connection.sender.send(notification.into())?)
+    }
+
+    fn respond(&self, id: RequestId, result: impl Serialize) -> Result<()> {
+        let response = Response::new_ok(id, result);
+
+        Ok(self.connection.sender.send(response.into())?)
+    }
+
+    fn fail(
+        &self,
+        id: RequestId,
+        code: ErrorCode,
+        message: String,
+    ) -> Result<()> {
+        let response = Response::new_err(id, code as i32, message);
+
+        Ok(self.connection.sender.send(response.into())?)
+    }
+}
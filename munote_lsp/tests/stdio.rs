@@ -0,0 +1,234 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use serde_json::{json, Value};
+
+/// An editor talking to the server on its stdio.
+struct Client {
+    server: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut server = Command::new(env!("CARGO_BIN_EXE_munote-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Could not start the server");
+
+        let stdin = server.stdin.take().unwrap();
+        let stdout = BufReader::new(server.stdout.take().unwrap());
+
+        Self {
+            server,
+            stdin,
+            stdout,
+            next_id: 1,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len())
+            .unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length: ") {
+                length = value.parse().unwrap();
+            }
+        }
+
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.call(method, params)["result"].clone()
+    }
+
+    /// The whole response to a request, with its result or error.
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }));
+
+        loop {
+            let message = self.receive();
+            if message["id"] == id {
+                return message;
+            }
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    fn stop(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+
+        assert!(self.server.wait().unwrap().success());
+    }
+}
+
+#[test]
+fn session() {
+    let uri = "file:///tmp/munote-lsp/score.gmn";
+    let document = json!({ "uri": uri });
+    let at = |line: u32, character: u32| {
+        json!({
+            "textDocument": document,
+            "position": { "line": line, "character": character },
+        })
+    };
+
+    let mut client = Client::start();
+
+    let initialized =
+        client.request("initialize", json!({ "capabilities": {} }));
+    assert_eq!(initialized["capabilities"]["hoverProvider"], true);
+    client.notify("initialized", json!({}));
+
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": uri,
+                "languageId": "guido",
+                "version": 1,
+                "text": "[ \\slurBegin c1 d\n\\clef<\"x\"> \\slurEnd ]",
+            },
+        }),
+    );
+
+    let published = client.receive();
+    assert_eq!(published["method"], "textDocument/publishDiagnostics");
+    assert_eq!(
+        published["params"]["diagnostics"][0]["code"],
+        "unknown-clef"
+    );
+
+    let hover = client.request("textDocument/hover", at(0, 13));
+    let hover = hover["contents"]["value"].as_str().unwrap();
+    assert!(hover.starts_with("**C4**, MIDI 60"));
+
+    let matching = client.request("textDocument/definition", at(1, 13));
+    assert_eq!(
+        matching["range"]["start"],
+        json!({ "line": 0, "character": 2 })
+    );
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": document }),
+    );
+    assert_eq!(symbols[0]["name"], "Staff 1");
+
+    let completions = client.request("textDocument/completion", at(0, 4));
+    assert!(completions
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|item| item["label"] == "slur"));
+
+    let edits = client.request(
+        "textDocument/formatting",
+        json!({
+            "textDocument": document,
+            "options": { "tabSize": 2, "insertSpaces": true },
+        }),
+    );
+    assert_eq!(
+        edits[0]["newText"],
+        "[ \\slurBegin c1 d\n  \\clef<\"x\"> \\slurEnd ]\n"
+    );
+
+    let invalid = client.call("textDocument/hover", json!({ "line": 0 }));
+    assert_eq!(invalid["error"]["code"], -32602);
+    let hover = client.request("textDocument/hover", at(0, 13));
+    assert!(hover["contents"]["value"].is_string());
+
+    client.stop();
+}
+
+#[test]
+fn config() {
+    let dir = std::env::temp_dir()
+        .join(format!("munote-lsp-config-{}", std::process::id()));
+    let config = dir.join("munote.toml");
+    let score = dir.join("score.gmn");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&config, "rules = 1").unwrap();
+
+    let uri = format!("file://{}", score.display());
+    let diagnostics = |client: &mut Client| loop {
+        let message = client.receive();
+        if message["params"]["uri"] == uri.as_str() {
+            return message["params"]["diagnostics"].clone();
+        }
+    };
+
+    let mut client = Client::start();
+    client.request("initialize", json!({ "capabilities": {} }));
+    client.notify("initialized", json!({}));
+
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": uri,
+                "languageId": "guido",
+                "version": 1,
+                "text": "[ \\clef<\"x\"> c ]",
+            },
+        }),
+    );
+    let published = diagnostics(&mut client);
+    assert_eq!(published[0]["code"], "config");
+    assert!(published[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid"));
+
+    fs::write(&config, "[rules]\nstructure = false\n").unwrap();
+    client.notify(
+        "textDocument/didSave",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    assert_eq!(diagnostics(&mut client), json!([]));
+
+    client.stop();
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,5 +1,5 @@
 use cucumber::*;
-use munote::score::Score;
+use munote::{event::Event, note::Note, score::Score, tag::Tag};
 use std::collections::HashMap;
 
 #[derive(Debug, Default, World)]
@@ -16,4 +16,30 @@ impl MusicWorld {
             0
         }
     }
+
+    pub fn count_voices(&self, staff: u8) -> usize {
+        self.score
+            .as_ref()
+            .and_then(|score| score.staff(staff))
+            .map_or(0, |staff| staff.voices.len())
+    }
+
+    /// First note of the `voice`-th voice (from 1) of a staff, looking into
+    /// range tags like `\beam(e/8 c d e)`.
+    pub fn first_note(&self, staff: u8, voice: usize) -> Option<&Note> {
+        let staff = self.score.as_ref()?.staff(staff)?;
+        let voice = staff.voices.get(voice.checked_sub(1)?)?;
+
+        first_note(&voice.events)
+    }
+}
+
+fn first_note(events: &[Box<dyn Event>]) -> Option<&Note> {
+    events.iter().find_map(|e| {
+        let any = e.as_any();
+
+        any.downcast_ref::<Note>().or_else(|| {
+            any.downcast_ref::<Tag>().and_then(|t| first_note(&t.events))
+        })
+    })
 }
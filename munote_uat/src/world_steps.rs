@@ -2,7 +2,7 @@ use std::string::String;
 
 use anyhow::Result;
 use cucumber::{codegen::anyhow, gherkin::Step, given, then, when};
-use munote::{context::ContextPtr, score::Score};
+use munote::score::Score;
 
 use crate::MusicWorld;
 
@@ -17,14 +17,36 @@ fn given_filename(w: &mut MusicWorld, step: &Step, file_name: String) {
 fn parse_filename(w: &mut MusicWorld, file_name: String) -> Result<()> {
     let content = &w.files[&file_name];
 
-    let score = Score::parse(content.as_str(), ContextPtr::default())?;
+    let score = Score::parse(content.as_str())?;
 
     w.score = Some(score);
 
     Ok(())
 }
 
-#[then(expr = "there are {int} staff(s)")]
+#[then(expr = "there is/are {int} staff(s)")]
 fn check_staff_count(w: &mut MusicWorld, num: usize) {
     assert_eq!(w.count_staffs(), num)
 }
+
+#[then(expr = "staff {int} has {int} voice(s)")]
+fn check_voice_count(w: &mut MusicWorld, staff: u8, num: usize) {
+    assert_eq!(w.count_voices(staff), num)
+}
+
+#[then(expr = "voice {int} of staff {int} starts in octave {int} lasting {string}")]
+fn check_first_note(
+    w: &mut MusicWorld,
+    voice: usize,
+    staff: u8,
+    octave: i8,
+    duration: String,
+) {
+    let note = w.first_note(staff, voice).expect("No note found");
+
+    assert_eq!(note.octave, octave);
+    assert_eq!(
+        format!("{}/{}", note.duration.num, note.duration.denom),
+        duration
+    );
+}
@@ -0,0 +1,86 @@
+Feature: Voices
+
+  Every sequence starts with octave 1 and quarter notes, and is placed on a
+  staff only by the tags it contains.
+
+  Scenario: Voices on two staves
+    Given "4voices.gmn" file with:
+      """
+      {
+          [
+              \pageFormat<lm=1cm, tm=1cm, bm=1cm, rm=1cm>
+              \meter<"2/4"> \stemsUp
+              \beam(g2*1/32 e*1/16 c*3/32) c2*1/8 \beam(a1*1/16 c2 f)
+              \beam(g/32 d/16 h1*3/32) d2*1/8 \beam(h1*1/16 d2 g2)
+          ],
+          [   \staff<1> \stemsDown g1*1/8 e \beam(g/16 d f a) a/8 e
+              \beam(a/16 e g h)
+          ],
+          [   \staff<2> \meter<"2/4"> \stemsUp a0*1/4 f h c1 ],
+          [   \staff<2> \stemsDown f0*1/4 d g a ]
+      }
+      """
+    When I parse "4voices.gmn"
+    Then there are 2 staffs
+    And staff 1 has 2 voices
+    And staff 2 has 2 voices
+    And voice 2 of staff 1 starts in octave 1 lasting "1/8"
+    And voice 2 of staff 2 starts in octave 0 lasting "1/4"
+
+  Scenario: Durations do not leak into the next voice
+    Given "barlines.gmn" file with:
+      """
+      {
+          [
+              \pageFormat<lm=1cm, tm=1cm, bm=1cm, rm=1cm>
+              \accol<range="1-3", dx=-1, type="straightBrace">
+              \barFormat<"system", range="1-3">
+              c c | f g \doubleBar a f \repeatBegin  d e \repeatEnd e/2 ],
+          [   c c | f g \doubleBar a f \repeatBegin  d e \repeatEnd e/2 ],
+          [   c c | f g \doubleBar a f \repeatBegin  d e \repeatEnd e/2 ]
+      }
+      """
+    When I parse "barlines.gmn"
+    Then there is 1 staff
+    And staff 1 has 3 voices
+    And voice 1 of staff 1 starts in octave 1 lasting "1/4"
+    And voice 2 of staff 1 starts in octave 1 lasting "1/4"
+    And voice 3 of staff 1 starts in octave 1 lasting "1/4"
+
+  Scenario: Octaves do not leak into the next voice
+    Given "clefkeymeter.gmn" file with:
+      """
+      {
+          [
+              \clef<"tenor"> \key<-2>  \meter<"2+3+3/8"> c/8 b&0 a g f e& d c d e& f g a b c1 d
+          ],
+          [
+              \clef<"alto">  \key<2>   \meter<"C"> c c c c \acc(c#) c# c# c#
+          ]
+      }
+      """
+    When I parse "clefkeymeter.gmn"
+    Then staff 1 has 2 voices
+    And voice 2 of staff 1 starts in octave 1 lasting "1/4"
+
+  Scenario: Cross-staff chords keep the voice on its staff
+    Given "bach.gmn" file with:
+      """
+      {
+          [
+              \clef<"g"> \meter<"C">
+              f g a b c3 a2 b g c3/8 g2 e d/16 c
+              \fermata< position="above">(c2/1)
+          ],
+          [   \staff<2>
+              \clef<"f"> \meter<"C">
+              \beam (e/8 c d e) f/16 d e f g/8 g-1 |
+              \fermata< position="below">(\arpeggio< dy=1>({ c-1/1, c0, \staff<1> e1, g, c2 }))
+          ]
+      }
+      """
+    When I parse "bach.gmn"
+    Then there are 2 staffs
+    And staff 1 has 1 voice
+    And staff 2 has 1 voice
+    And voice 1 of staff 2 starts in octave 1 lasting "1/8"